@@ -0,0 +1,93 @@
+## Authentication
+
+invalid-credentials = Ungültige Anmeldedaten
+too-many-login-attempts = Zu viele fehlgeschlagene Anmeldeversuche, bitte später erneut versuchen
+account-disabled = Das Konto ist deaktiviert
+password-reset-required = Das Passwort muss vor der Anmeldung zurückgesetzt werden
+user-already-exists = Es gibt bereits einen Benutzer mit der E-Mail-Adresse { $email }
+password-hash-failed = Das Passwort konnte nicht gehasht werden
+token-generation-failed = Die Tokens konnten nicht erzeugt werden
+registration-disabled = Die Registrierung ist deaktiviert
+invalid-invite = Für die Registrierung ist eine gültige Einladung erforderlich
+password-policy = Das Passwort erfüllt die Passwortrichtlinie nicht
+invalid-reset-token = Ungültiger oder abgelaufener Code zum Zurücksetzen
+invalid-verification-token = Ungültiger oder abgelaufener Bestätigungscode
+current-password-incorrect = Das aktuelle Passwort ist falsch
+missing-token = Kein gültiges Token gefunden
+unauthorized = Nicht autorisiert
+missing-scope = Dem Token fehlt der Bereich { $scope }
+first-party-only = Für Anwendungen von Drittanbietern nicht verfügbar
+admin-required = Administratorrechte erforderlich
+too-many-requests = Zu viele Anfragen
+database-error = Datenbankabfrage fehlgeschlagen
+email-send-failed = Die Bestätigungs-E-Mail konnte nicht gesendet werden
+
+## Identity provider login
+
+oidc-not-configured = Die OIDC-Anmeldung ist nicht eingerichtet
+oidc-missing-code = Code oder State fehlt
+oidc-unknown-state = Unbekannter oder abgelaufener Anmeldestatus
+oidc-login-failed = Die Anmeldung beim Identitätsanbieter ist fehlgeschlagen
+oidc-provider-error = Der Identitätsanbieter hat einen Fehler gemeldet: { $description }
+oidc-unverified-email = Es gibt bereits ein Konto für { $email } und der Identitätsanbieter hat diese E-Mail-Adresse nicht bestätigt
+
+## Third-party applications
+
+oauth-unknown-client = Unbekannter Client
+oauth-invalid-redirect-uri = Die Weiterleitungs-URI ist für diesen Client nicht registriert
+oauth-invalid-scope = Ungültiger Bereich { $scope }
+oauth-unsupported-response-type = Nicht unterstützter Antworttyp
+oauth-invalid-code-challenge = Eine S256-PKCE-Code-Challenge ist erforderlich
+oauth-invalid-grant = Ungültige oder abgelaufene Berechtigung
+oauth-invalid-client = Die Client-Authentifizierung ist fehlgeschlagen
+oauth-unsupported-grant-type = Nicht unterstützter Berechtigungstyp
+oauth-client-not-found = Client nicht gefunden
+
+## Administration
+
+user-not-found = Benutzer nicht gefunden
+admin-self-modification = Administratoren können sich nicht selbst deaktivieren oder löschen
+
+## Lights
+
+light-not-found = Lampe nicht gefunden
+room-not-found = Raum nicht gefunden
+location-not-found = Standort nicht gefunden
+zone-not-found = Zone nicht gefunden
+device-not-found = Gerät nicht gefunden
+unsupported-capability = Das Gerät unterstützt { $capability } nicht
+invalid-device-state = Helligkeit und Akku müssen zwischen 0 und 100 liegen
+device-profile-not-found = Geräteprofil nicht gefunden
+device-profile-kind = Das Profil gehört zu einer anderen Geräteart
+invalid-reading = Messwerte müssen endliche Zahlen sein
+invalid-reading-range = Der Zeitraum darf nicht vor seinem Beginn enden und höchstens in 10000 Intervalle von mindestens einer Sekunde aufgeteilt werden
+
+## Lists
+
+invalid-cursor = Der Seitencursor ist ungültig oder gehört zu einer anderen Sortierung
+precondition-failed = Es wurde seit dem letzten Laden von jemand anderem geändert
+
+## Retries
+
+invalid-idempotency-key = Der Idempotency-Key-Header muss aus 1 bis 255 druckbaren ASCII-Zeichen bestehen
+idempotency-key-reused = Dieser Idempotency-Key wurde bereits für eine andere Anfrage verwendet
+idempotency-key-in-progress = Eine Anfrage mit diesem Idempotency-Key wird noch bearbeitet, versuche es gleich noch einmal
+request-body-too-large = Der Inhalt der Anfrage ist zu groß
+
+## Requests
+
+invalid-json = Der Inhalt der Anfrage ist kein gültiges JSON
+invalid-request-body = Im Inhalt der Anfrage fehlen Felder oder Felder haben den falschen Typ
+unsupported-media-type = Der Inhalt der Anfrage muss als application/json gesendet werden
+invalid-path = Die Adresse enthält eine ungültige Kennung
+invalid-query = Die Abfrageparameter sind ungültig
+
+## Emails
+
+email-verification-subject = Bestätige deine neue E-Mail-Adresse
+email-verification-body =
+    Mit diesem Code bestätigst du { $email } als E-Mail-Adresse deines homehub-Kontos:
+
+    { $code }
+
+    Der Code ist { $hours } Stunden gültig.
@@ -0,0 +1,93 @@
+## Authentication
+
+invalid-credentials = Invalid credentials
+too-many-login-attempts = Too many failed login attempts, try again later
+account-disabled = Account is disabled
+password-reset-required = Password must be reset before logging in
+user-already-exists = User with email { $email } already exists
+password-hash-failed = Could not hash password
+token-generation-failed = Could not generate tokens
+registration-disabled = Registration is disabled
+invalid-invite = A valid invite is required to register
+password-policy = Password does not meet the password policy
+invalid-reset-token = Invalid or expired reset token
+invalid-verification-token = Invalid or expired verification token
+current-password-incorrect = Current password is incorrect
+missing-token = No valid token found
+unauthorized = Unauthorized
+missing-scope = Token is missing the { $scope } scope
+first-party-only = Not available to third-party applications
+admin-required = Administrator access required
+too-many-requests = Too many requests
+database-error = Failed to query database
+email-send-failed = Could not send verification email
+
+## Identity provider login
+
+oidc-not-configured = OIDC login is not configured
+oidc-missing-code = Missing code or state
+oidc-unknown-state = Unknown or expired login state
+oidc-login-failed = Identity provider login failed
+oidc-provider-error = The identity provider reported an error: { $description }
+oidc-unverified-email = An account for { $email } already exists and the identity provider has not verified this email
+
+## Third-party applications
+
+oauth-unknown-client = Unknown client
+oauth-invalid-redirect-uri = Redirect URI is not registered for this client
+oauth-invalid-scope = Invalid scope { $scope }
+oauth-unsupported-response-type = Unsupported response type
+oauth-invalid-code-challenge = A S256 PKCE code challenge is required
+oauth-invalid-grant = Invalid or expired grant
+oauth-invalid-client = Client authentication failed
+oauth-unsupported-grant-type = Unsupported grant type
+oauth-client-not-found = Client not found
+
+## Administration
+
+user-not-found = User not found
+admin-self-modification = Administrators cannot disable or delete themselves
+
+## Lights
+
+light-not-found = Light not found
+room-not-found = Room not found
+location-not-found = Location not found
+zone-not-found = Zone not found
+device-not-found = Device not found
+unsupported-capability = The device does not support { $capability }
+invalid-device-state = Brightness and battery must be between 0 and 100
+device-profile-not-found = Device profile not found
+device-profile-kind = The profile is for a different kind of device
+invalid-reading = Readings must be finite numbers
+invalid-reading-range = The range must not end before it starts, and may be split into at most 10000 buckets of at least a second
+
+## Lists
+
+invalid-cursor = The page cursor is invalid or was issued for a different sort order
+precondition-failed = It was changed by someone else since you last loaded it
+
+## Retries
+
+invalid-idempotency-key = The Idempotency-Key header must be between 1 and 255 printable ASCII characters
+idempotency-key-reused = This Idempotency-Key was already used for a different request
+idempotency-key-in-progress = A request with this Idempotency-Key is still being processed, try again shortly
+request-body-too-large = The request body is too large
+
+## Requests
+
+invalid-json = The request body is not valid JSON
+invalid-request-body = The request body is missing fields or has fields of the wrong type
+unsupported-media-type = The request body must be sent as application/json
+invalid-path = The address contains an invalid identifier
+invalid-query = The query string is invalid
+
+## Emails
+
+email-verification-subject = Confirm your new email address
+email-verification-body =
+    Use this code to confirm { $email } as the email address of your homehub account:
+
+    { $code }
+
+    The code expires in { $hours } hours.
@@ -0,0 +1,163 @@
+use chrono::{NaiveDateTime, Utc};
+pub use homehub_db::queries::app_user::UserSort;
+use homehub_db::DatabaseConnection;
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::error::Error;
+use crate::oauth::{hash_token, random_token};
+use crate::pagination::{Page, PageRequest};
+
+const PASSWORD_RESET_TTL_HOURS: i64 = 24;
+const INVITE_TTL_DAYS: i64 = 7;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AdminUserDto {
+    pub id: Uuid,
+    pub name: String,
+    pub email: String,
+    pub locale: Option<String>,
+    pub is_admin: bool,
+    pub disabled_at: Option<NaiveDateTime>,
+    pub password_reset_required: bool,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl From<homehub_db::app_user::Model> for AdminUserDto {
+    fn from(value: homehub_db::app_user::Model) -> Self {
+        AdminUserDto {
+            id: value.id,
+            name: value.name,
+            email: value.email,
+            locale: value.locale,
+            is_admin: value.is_admin,
+            disabled_at: value.disabled_at,
+            password_reset_required: value.password_reset_required,
+            created_at: value.created_at,
+        }
+    }
+}
+
+/// A one-time token the user exchanges for a new password. Only its hash is
+/// stored, so it has to be handed to the user when it is created.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PasswordReset {
+    pub user: AdminUserDto,
+    pub reset_token: String,
+    pub expires_at: NaiveDateTime,
+}
+
+pub async fn list_users(
+    query: Option<&str>,
+    page: &PageRequest<UserSort>,
+    db: &DatabaseConnection,
+) -> Result<Page<AdminUserDto>, Error> {
+    let users = homehub_db::queries::app_user::search_users(query, page, db)
+        .await
+        .map_err(Error::DbError)?;
+    Ok(users.map(Into::into))
+}
+
+pub async fn disable_user(
+    admin_id: Uuid,
+    user_id: Uuid,
+    db: &DatabaseConnection,
+) -> Result<AdminUserDto, Error> {
+    if admin_id == user_id {
+        return Err(Error::AdminSelfModificationError);
+    }
+    set_disabled_at(user_id, Some(Utc::now().naive_utc()), db).await
+}
+
+pub async fn enable_user(
+    user_id: Uuid,
+    db: &DatabaseConnection,
+) -> Result<AdminUserDto, Error> {
+    set_disabled_at(user_id, None, db).await
+}
+
+async fn set_disabled_at(
+    user_id: Uuid,
+    disabled_at: Option<NaiveDateTime>,
+    db: &DatabaseConnection,
+) -> Result<AdminUserDto, Error> {
+    homehub_db::queries::app_user::set_disabled_at(user_id, disabled_at, db)
+        .await
+        .map_err(Error::DbError)?
+        .map(Into::into)
+        .ok_or(Error::UserNotFoundError)
+}
+
+/// Locks the user out until they choose a new password with the returned
+/// reset token.
+pub async fn force_password_reset(
+    user_id: Uuid,
+    db: &DatabaseConnection,
+) -> Result<PasswordReset, Error> {
+    let reset_token = random_token();
+    let expires_at = Utc::now().naive_utc()
+        + chrono::Duration::hours(PASSWORD_RESET_TTL_HOURS);
+
+    let user = homehub_db::queries::app_user::require_password_reset(
+        user_id,
+        &hash_token(&reset_token),
+        expires_at,
+        db,
+    )
+    .await
+    .map_err(Error::DbError)?
+    .ok_or(Error::UserNotFoundError)?;
+
+    Ok(PasswordReset {
+        user: user.into(),
+        reset_token,
+        expires_at,
+    })
+}
+
+pub async fn delete_user(
+    admin_id: Uuid,
+    user_id: Uuid,
+    db: &DatabaseConnection,
+) -> Result<(), Error> {
+    if admin_id == user_id {
+        return Err(Error::AdminSelfModificationError);
+    }
+    match homehub_db::queries::app_user::delete_user(user_id, db).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(Error::UserNotFoundError),
+        Err(e) => Err(Error::DbError(e)),
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Invite {
+    pub invite_token: String,
+    pub expires_at: NaiveDateTime,
+}
+
+/// Issues a single-use invite for registering while registration is
+/// invite-only.
+pub async fn create_invite(
+    admin_id: Uuid,
+    db: &DatabaseConnection,
+) -> Result<Invite, Error> {
+    let invite_token = random_token();
+    let expires_at =
+        Utc::now().naive_utc() + chrono::Duration::days(INVITE_TTL_DAYS);
+
+    homehub_db::queries::registration_invite::create_invite(
+        &hash_token(&invite_token),
+        admin_id,
+        expires_at,
+        db,
+    )
+    .await
+    .map_err(Error::DbError)?;
+
+    Ok(Invite {
+        invite_token,
+        expires_at,
+    })
+}
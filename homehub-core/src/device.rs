@@ -0,0 +1,302 @@
+use chrono::NaiveDateTime;
+pub use homehub_db::device::{Capability, DeviceKind, DeviceState};
+use homehub_db::queries::device::ProfileLink;
+pub use homehub_db::queries::device::{DeviceFilter, DeviceSort};
+use homehub_db::DatabaseConnection;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::error::Error;
+use crate::light::{LightEvents, RoomDto};
+use crate::pagination::{Page, PageRequest};
+use crate::profile::{find_profile, get_profile, DeviceProfile};
+
+/// A light, switch, plug, sensor or thermostat. What it can do is described
+/// by its capabilities, and its state only has the fields they own.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DeviceDto {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub kind: DeviceKind,
+    pub capabilities: Vec<Capability>,
+    pub state: DeviceState,
+    pub room: Option<RoomDto>,
+    /// The make and model of the device, when known.
+    pub profile: Option<DeviceProfile>,
+    pub updated_at: NaiveDateTime,
+    pub version: i32,
+}
+
+impl From<(homehub_db::device::Model, Option<homehub_db::room::Model>)>
+    for DeviceDto
+{
+    fn from(
+        value: (homehub_db::device::Model, Option<homehub_db::room::Model>),
+    ) -> Self {
+        DeviceDto {
+            id: value.0.id,
+            name: value.0.name,
+            kind: value.0.kind,
+            capabilities: value.0.capabilities.0,
+            state: value.0.state,
+            room: value.1.map(Into::into),
+            profile: value
+                .0
+                .profile_id
+                .as_deref()
+                .and_then(get_profile)
+                .cloned(),
+            updated_at: value.0.updated_at,
+            version: value.0.version,
+        }
+    }
+}
+
+/// Capabilities every device of a kind has, whatever else it was given.
+fn required_capabilities(kind: DeviceKind) -> &'static [Capability] {
+    match kind {
+        DeviceKind::Light | DeviceKind::Switch | DeviceKind::Plug => {
+            &[Capability::OnOff]
+        }
+        DeviceKind::Thermostat => &[Capability::Setpoint],
+        DeviceKind::Sensor => &[],
+    }
+}
+
+/// Capabilities a device of a kind gets when none are given.
+pub(crate) fn default_capabilities(kind: DeviceKind) -> &'static [Capability] {
+    match kind {
+        DeviceKind::Light => &[Capability::OnOff, Capability::Colour],
+        DeviceKind::Switch | DeviceKind::Plug => &[Capability::OnOff],
+        DeviceKind::Sensor => {
+            &[Capability::TemperatureReading, Capability::Battery]
+        }
+        DeviceKind::Thermostat => {
+            &[Capability::TemperatureReading, Capability::Setpoint]
+        }
+    }
+}
+
+/// `capabilities` together with those the kind requires, each once.
+pub(crate) fn with_required(
+    kind: DeviceKind,
+    capabilities: &[Capability],
+) -> Vec<Capability> {
+    let mut capabilities: Vec<_> = capabilities
+        .iter()
+        .chain(required_capabilities(kind))
+        .copied()
+        .collect();
+    capabilities.sort();
+    capabilities.dedup();
+    capabilities
+}
+
+/// Checks that a state only sets fields the capabilities own, with values in
+/// range.
+pub(crate) fn validate_state(
+    state: &DeviceState,
+    capabilities: &[Capability],
+) -> Result<(), Error> {
+    let fields = [
+        (state.on.is_some(), Capability::OnOff),
+        (state.brightness.is_some(), Capability::Dimmable),
+        (state.colour.is_some(), Capability::Colour),
+        (state.temperature.is_some(), Capability::TemperatureReading),
+        (state.humidity.is_some(), Capability::HumidityReading),
+        (state.illuminance.is_some(), Capability::IlluminanceReading),
+        (state.power.is_some(), Capability::PowerReading),
+        (state.setpoint.is_some(), Capability::Setpoint),
+        (state.battery.is_some(), Capability::Battery),
+        (state.open.is_some(), Capability::Contact),
+        (state.motion.is_some(), Capability::Motion),
+    ];
+    if let Some((_, capability)) = fields
+        .into_iter()
+        .find(|(set, capability)| *set && !capabilities.contains(capability))
+    {
+        return Err(Error::UnsupportedCapabilityError(
+            capability.name().to_owned(),
+        ));
+    }
+    if state.brightness.is_some_and(|brightness| brightness > 100)
+        || state.battery.is_some_and(|battery| battery > 100)
+    {
+        return Err(Error::InvalidDeviceStateError);
+    }
+    Ok(())
+}
+
+/// Rooms are referenced by id in requests, so make sure one exists before
+/// linking a device to it.
+async fn ensure_room_exists(
+    room_id: &uuid::Uuid,
+    db: &DatabaseConnection,
+) -> Result<(), Error> {
+    homehub_db::queries::room::get_room(room_id, db)
+        .await
+        .map_err(Error::DbError)?
+        .ok_or(Error::RoomNotFoundError)?;
+    Ok(())
+}
+
+/// Explains why a conditional update changed nothing: either the device is
+/// gone, or it is no longer at the version the client expected.
+async fn not_updated_error(
+    id: &uuid::Uuid,
+    if_match: Option<&[i32]>,
+    db: &DatabaseConnection,
+) -> Error {
+    match homehub_db::queries::device::get_device(id, db).await {
+        Ok(Some(_)) if if_match.is_some() => Error::PreconditionFailedError,
+        Ok(_) => Error::DeviceNotFoundError,
+        Err(e) => Error::DbError(e),
+    }
+}
+
+/// Creates a device that is switched off, if it can be switched at all.
+/// The capabilities come from the profile if there is one, then from
+/// `capabilities`, and otherwise are what is usual for the kind. They always
+/// include what the kind requires.
+pub async fn create_device(
+    name: &str,
+    kind: DeviceKind,
+    capabilities: Option<&[Capability]>,
+    room_id: Option<uuid::Uuid>,
+    profile_id: Option<&str>,
+    db: &DatabaseConnection,
+) -> Result<DeviceDto, Error> {
+    if let Some(room_id) = &room_id {
+        ensure_room_exists(room_id, db).await?;
+    }
+    let profile = profile_id
+        .map(|profile_id| find_profile(profile_id, kind))
+        .transpose()?;
+    let capabilities = with_required(
+        kind,
+        match profile {
+            Some(profile) => &profile.capabilities,
+            None => capabilities.unwrap_or(default_capabilities(kind)),
+        },
+    );
+    let state = DeviceState {
+        on: capabilities.contains(&Capability::OnOff).then_some(false),
+        ..Default::default()
+    };
+
+    homehub_db::queries::device::create_device(
+        name,
+        kind,
+        capabilities,
+        state,
+        room_id,
+        profile_id,
+        db,
+    )
+    .await
+    .map(Into::into)
+    .map_err(Error::DbError)
+}
+
+pub async fn get_device(
+    id: &uuid::Uuid,
+    db: &DatabaseConnection,
+) -> Result<DeviceDto, Error> {
+    homehub_db::queries::device::get_device(id, db)
+        .await
+        .map_err(Error::DbError)?
+        .map(Into::into)
+        .ok_or(Error::DeviceNotFoundError)
+}
+
+pub async fn get_devices(
+    filter: &DeviceFilter,
+    page: &PageRequest<DeviceSort>,
+    db: &DatabaseConnection,
+) -> Result<Page<DeviceDto>, Error> {
+    let devices = homehub_db::queries::device::get_devices(filter, page, db)
+        .await
+        .map_err(Error::DbError)?;
+    Ok(devices.map(Into::into))
+}
+
+/// Linking a profile replaces the device's capabilities with the profile's.
+/// `if_match` lists the versions the client expects the device to be at, as
+/// sent in an `If-Match` header. `None` updates whatever version is current.
+pub async fn update_device(
+    id: &uuid::Uuid,
+    name: Option<&str>,
+    room_id: Option<Option<uuid::Uuid>>,
+    profile_id: Option<Option<&str>>,
+    if_match: Option<&[i32]>,
+    db: &DatabaseConnection,
+) -> Result<DeviceDto, Error> {
+    if let Some(Some(room_id)) = &room_id {
+        ensure_room_exists(room_id, db).await?;
+    }
+    let capabilities;
+    let profile = match profile_id {
+        Some(Some(profile_id)) => {
+            let kind = get_device(id, db).await?.kind;
+            let profile = find_profile(profile_id, kind)?;
+            capabilities = with_required(kind, &profile.capabilities);
+            Some(Some(ProfileLink {
+                id: &profile.id,
+                capabilities: &capabilities,
+            }))
+        }
+        Some(None) => Some(None),
+        None => None,
+    };
+    match homehub_db::queries::device::update_device(
+        id, name, room_id, profile, if_match, db,
+    )
+    .await
+    .map_err(Error::DbError)?
+    {
+        Some(device) => Ok(device.into()),
+        None => Err(not_updated_error(id, if_match, db).await),
+    }
+}
+
+/// Changes the fields of the state that are set in `state`, leaving the rest
+/// as they are. Values the device's profile cannot show are brought within
+/// its limits. Changes to lights are also told to light listeners.
+pub async fn set_device_state(
+    id: &uuid::Uuid,
+    state: &DeviceState,
+    if_match: Option<&[i32]>,
+    db: &DatabaseConnection,
+    events: &LightEvents,
+) -> Result<DeviceDto, Error> {
+    let device = get_device(id, db).await?;
+    validate_state(state, &device.capabilities)?;
+    let state = match &device.profile {
+        Some(profile) => profile.adapt_device_state(state.clone()),
+        None => state.clone(),
+    };
+    let device: DeviceDto = match homehub_db::queries::device::set_device_state(
+        id, &state, if_match, db,
+    )
+    .await
+    .map_err(Error::DbError)?
+    {
+        Some(device) => device.into(),
+        None => return Err(not_updated_error(id, if_match, db).await),
+    };
+    if device.kind == DeviceKind::Light {
+        events.publish(&crate::light::get_light(id, db).await?);
+    }
+    Ok(device)
+}
+
+pub async fn delete_device(
+    id: &uuid::Uuid,
+    db: &DatabaseConnection,
+) -> Result<(), Error> {
+    match homehub_db::queries::device::delete_device(id, db).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(Error::DeviceNotFoundError),
+        Err(e) => Err(Error::DbError(e)),
+    }
+}
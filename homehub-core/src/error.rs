@@ -0,0 +1,274 @@
+use std::time::Duration;
+
+use fluent_bundle::FluentValue;
+use thiserror::Error;
+
+use crate::i18n::Locale;
+use crate::password::PasswordViolation;
+
+/// Everything that can go wrong in a request. Each variant has a stable
+/// machine-readable code for clients to branch on, and a localised message
+/// for people to read.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Invalid credentials")]
+    InvalidCredentialError,
+    #[error("Too many failed login attempts")]
+    TooManyLoginAttemptsError { retry_after: Duration },
+    #[error("Account is disabled")]
+    AccountDisabledError,
+    #[error("Password must be reset before logging in")]
+    PasswordResetRequiredError,
+    #[error("User with email already exists")]
+    UserAlreadyExistsError { email: String },
+    #[error("Registration is disabled")]
+    RegistrationDisabledError,
+    #[error("A valid invite is required to register")]
+    InvalidInviteError,
+    #[error("Password does not meet the password policy")]
+    PasswordPolicyError(Vec<PasswordViolation>),
+    #[error("Invalid or expired reset token")]
+    InvalidResetTokenError,
+    #[error("Invalid or expired verification token")]
+    InvalidVerificationTokenError,
+    #[error("Current password is incorrect")]
+    CurrentPasswordIncorrectError,
+    #[error("No valid token found")]
+    MissingTokenError,
+    #[error("Unauthorized")]
+    UnauthorizedError,
+    #[error("Token is missing a scope")]
+    MissingScopeError(String),
+    #[error("Not available to third-party applications")]
+    FirstPartyOnlyError,
+    #[error("Administrator access required")]
+    AdminRequiredError,
+    #[error("Too many requests")]
+    TooManyRequestsError { retry_after: Duration },
+
+    #[error("OIDC login is not configured")]
+    OidcNotConfiguredError,
+    #[error("Missing code or state")]
+    OidcMissingCodeError,
+    #[error("Unknown or expired login state")]
+    OidcUnknownStateError,
+    #[error("Identity provider returned an error")]
+    OidcProviderError(String),
+    #[error("Failed to exchange authorization code")]
+    OidcCodeExchangeError(String),
+    #[error("Invalid ID token")]
+    OidcInvalidIdTokenError(String),
+    #[error("Email is not verified by the identity provider")]
+    OidcUnverifiedEmailError(String),
+
+    #[error("Unknown client")]
+    OAuthUnknownClientError,
+    #[error("Redirect URI is not registered for this client")]
+    OAuthInvalidRedirectUriError,
+    #[error("Invalid scope")]
+    OAuthInvalidScopeError(String),
+    #[error("Unsupported response type")]
+    OAuthUnsupportedResponseTypeError,
+    #[error("A S256 PKCE code challenge is required")]
+    OAuthInvalidCodeChallengeError,
+    #[error("Invalid or expired grant")]
+    OAuthInvalidGrantError,
+    #[error("Client authentication failed")]
+    OAuthInvalidClientError,
+    #[error("Unsupported grant type")]
+    OAuthUnsupportedGrantTypeError,
+    #[error("Client not found")]
+    OAuthClientNotFoundError,
+
+    #[error("User not found")]
+    UserNotFoundError,
+    #[error("Administrators cannot disable or delete themselves")]
+    AdminSelfModificationError,
+
+    #[error("Light not found")]
+    LightNotFoundError,
+    #[error("Room not found")]
+    RoomNotFoundError,
+    #[error("Location not found")]
+    LocationNotFoundError,
+    #[error("Zone not found")]
+    ZoneNotFoundError,
+    #[error("Device not found")]
+    DeviceNotFoundError,
+    #[error("Device does not support {0}")]
+    UnsupportedCapabilityError(String),
+    #[error("Invalid device state")]
+    InvalidDeviceStateError,
+    #[error("Device profile not found")]
+    DeviceProfileNotFoundError,
+    #[error("Device profile is for a different kind of device")]
+    DeviceProfileKindError,
+    #[error("Invalid sensor reading")]
+    InvalidReadingError,
+    #[error("Invalid reading range")]
+    InvalidReadingRangeError,
+
+    #[error("Invalid page cursor")]
+    InvalidCursorError,
+    #[error("Resource was modified since it was fetched")]
+    PreconditionFailedError,
+
+    #[error("Invalid idempotency key")]
+    InvalidIdempotencyKeyError,
+    #[error("Idempotency key was used for a different request")]
+    IdempotencyKeyReusedError,
+    #[error("A request with this idempotency key is still in progress")]
+    IdempotencyKeyInProgressError,
+    #[error("Request body is too large")]
+    RequestBodyTooLargeError,
+
+    #[error("Request body is not valid JSON")]
+    InvalidJsonError(String),
+    #[error("Request body does not match the expected shape")]
+    InvalidRequestBodyError(String),
+    #[error("Request body must be JSON")]
+    UnsupportedMediaTypeError,
+    #[error("Invalid path parameter")]
+    InvalidPathError(String),
+    #[error("Invalid query string")]
+    InvalidQueryError(String),
+
+    #[error("Failed to query database")]
+    DbError(anyhow::Error),
+    #[error("Could not hash password")]
+    CouldNotHashError,
+    #[error("Token generation failed")]
+    TokenGenerationError,
+    #[error("Could not send email")]
+    MailError(anyhow::Error),
+}
+
+impl Error {
+    /// The stable code identifying the error. Several variants may share a
+    /// code when clients have no reason to tell them apart.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::InvalidCredentialError => "invalid_credentials",
+            Error::TooManyLoginAttemptsError { .. } => {
+                "too_many_login_attempts"
+            }
+            Error::AccountDisabledError => "account_disabled",
+            Error::PasswordResetRequiredError => "password_reset_required",
+            Error::UserAlreadyExistsError { .. } => "user_already_exists",
+            Error::RegistrationDisabledError => "registration_disabled",
+            Error::InvalidInviteError => "invalid_invite",
+            Error::PasswordPolicyError(_) => "password_policy",
+            Error::InvalidResetTokenError => "invalid_reset_token",
+            Error::InvalidVerificationTokenError => {
+                "invalid_verification_token"
+            }
+            Error::CurrentPasswordIncorrectError => {
+                "current_password_incorrect"
+            }
+            Error::MissingTokenError => "missing_token",
+            Error::UnauthorizedError => "unauthorized",
+            Error::MissingScopeError(_) => "missing_scope",
+            Error::FirstPartyOnlyError => "first_party_only",
+            Error::AdminRequiredError => "admin_required",
+            Error::TooManyRequestsError { .. } => "too_many_requests",
+            Error::OidcNotConfiguredError => "oidc_not_configured",
+            Error::OidcMissingCodeError => "oidc_missing_code",
+            Error::OidcUnknownStateError => "oidc_unknown_state",
+            Error::OidcProviderError(_) => "oidc_provider_error",
+            Error::OidcCodeExchangeError(_)
+            | Error::OidcInvalidIdTokenError(_) => "oidc_login_failed",
+            Error::OidcUnverifiedEmailError(_) => "oidc_unverified_email",
+            Error::OAuthUnknownClientError => "oauth_unknown_client",
+            Error::OAuthInvalidRedirectUriError => "oauth_invalid_redirect_uri",
+            Error::OAuthInvalidScopeError(_) => "oauth_invalid_scope",
+            Error::OAuthUnsupportedResponseTypeError => {
+                "oauth_unsupported_response_type"
+            }
+            Error::OAuthInvalidCodeChallengeError => {
+                "oauth_invalid_code_challenge"
+            }
+            Error::OAuthInvalidGrantError => "oauth_invalid_grant",
+            Error::OAuthInvalidClientError => "oauth_invalid_client",
+            Error::OAuthUnsupportedGrantTypeError => {
+                "oauth_unsupported_grant_type"
+            }
+            Error::OAuthClientNotFoundError => "oauth_client_not_found",
+            Error::UserNotFoundError => "user_not_found",
+            Error::AdminSelfModificationError => "admin_self_modification",
+            Error::LightNotFoundError => "light_not_found",
+            Error::RoomNotFoundError => "room_not_found",
+            Error::LocationNotFoundError => "location_not_found",
+            Error::ZoneNotFoundError => "zone_not_found",
+            Error::DeviceNotFoundError => "device_not_found",
+            Error::UnsupportedCapabilityError(_) => "unsupported_capability",
+            Error::InvalidDeviceStateError => "invalid_device_state",
+            Error::DeviceProfileNotFoundError => "device_profile_not_found",
+            Error::DeviceProfileKindError => "device_profile_kind",
+            Error::InvalidReadingError => "invalid_reading",
+            Error::InvalidReadingRangeError => "invalid_reading_range",
+            Error::InvalidCursorError => "invalid_cursor",
+            Error::PreconditionFailedError => "precondition_failed",
+            Error::InvalidIdempotencyKeyError => "invalid_idempotency_key",
+            Error::IdempotencyKeyReusedError => "idempotency_key_reused",
+            Error::IdempotencyKeyInProgressError => {
+                "idempotency_key_in_progress"
+            }
+            Error::RequestBodyTooLargeError => "request_body_too_large",
+            Error::InvalidJsonError(_) => "invalid_json",
+            Error::InvalidRequestBodyError(_) => "invalid_request_body",
+            Error::UnsupportedMediaTypeError => "unsupported_media_type",
+            Error::InvalidPathError(_) => "invalid_path",
+            Error::InvalidQueryError(_) => "invalid_query",
+            Error::DbError(_) => "database_error",
+            Error::CouldNotHashError => "password_hash_failed",
+            Error::TokenGenerationError => "token_generation_failed",
+            Error::MailError(_) => "email_send_failed",
+        }
+    }
+
+    /// The error message in the given locale. Catalogue ids are the error
+    /// codes in kebab case.
+    pub fn message(&self, locale: &Locale) -> String {
+        let id = self.code().replace('_', "-");
+        let args: Vec<(&str, FluentValue<'_>)> = match self {
+            Error::UserAlreadyExistsError { email }
+            | Error::OidcUnverifiedEmailError(email) => {
+                vec![("email", email.as_str().into())]
+            }
+            Error::MissingScopeError(scope)
+            | Error::OAuthInvalidScopeError(scope) => {
+                vec![("scope", scope.as_str().into())]
+            }
+            Error::OidcProviderError(description) => {
+                vec![("description", description.as_str().into())]
+            }
+            Error::UnsupportedCapabilityError(capability) => {
+                vec![("capability", capability.as_str().into())]
+            }
+            _ => Vec::new(),
+        };
+        locale.message_with(&id, &args)
+    }
+
+    /// Why a request was rejected before reaching its handler, in the words
+    /// of the parser that rejected it.
+    pub fn reason(&self) -> Option<&str> {
+        match self {
+            Error::InvalidJsonError(reason)
+            | Error::InvalidRequestBodyError(reason)
+            | Error::InvalidPathError(reason)
+            | Error::InvalidQueryError(reason) => Some(reason),
+            _ => None,
+        }
+    }
+
+    /// How long the client should wait before trying again, for errors that
+    /// are only temporary.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Error::TooManyLoginAttemptsError { retry_after }
+            | Error::TooManyRequestsError { retry_after } => Some(*retry_after),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use std::sync::OnceLock;
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource, FluentValue};
+use fluent_langneg::{
+    accepted_languages, negotiate_languages, NegotiationStrategy,
+};
+use unic_langid::{langid, LanguageIdentifier};
+
+static DEFAULT_LOCALE: LanguageIdentifier = langid!("en-GB");
+
+const CATALOGUES: &[(LanguageIdentifier, &str)] = &[
+    (langid!("en-GB"), include_str!("../locales/en-GB/main.ftl")),
+    (langid!("de-DE"), include_str!("../locales/de-DE/main.ftl")),
+];
+
+struct Catalogue {
+    locales: Vec<LanguageIdentifier>,
+    bundles: Vec<FluentBundle<FluentResource>>,
+}
+
+fn catalogue() -> &'static Catalogue {
+    static CATALOGUE: OnceLock<Catalogue> = OnceLock::new();
+    CATALOGUE.get_or_init(|| {
+        let (locales, bundles) = CATALOGUES
+            .iter()
+            .map(|(locale, source)| {
+                let resource = FluentResource::try_new(source.to_string())
+                    .unwrap_or_else(|_| {
+                        panic!("{} catalogue is not valid Fluent", locale)
+                    });
+                let mut bundle =
+                    FluentBundle::new_concurrent(vec![locale.clone()]);
+                // Messages end up in JSON and plain text emails, where the
+                // bidi isolation marks would only get in the way.
+                bundle.set_use_isolating(false);
+                bundle.add_resource(resource).unwrap_or_else(|_| {
+                    panic!("{} catalogue has duplicate messages", locale)
+                });
+                (locale.clone(), bundle)
+            })
+            .unzip();
+        Catalogue { locales, bundles }
+    })
+}
+
+/// One of the locales we have translations for, picked to best match what
+/// the user asked for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locale(LanguageIdentifier);
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale(DEFAULT_LOCALE.clone())
+    }
+}
+
+impl std::fmt::Display for Locale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Locale {
+    /// Picks the best supported locale for the requested ones, in order of
+    /// preference. Unparseable tags are ignored.
+    pub fn negotiate<'a>(requested: impl IntoIterator<Item = &'a str>) -> Self {
+        let requested: Vec<LanguageIdentifier> = requested
+            .into_iter()
+            .filter_map(|locale| locale.parse().ok())
+            .collect();
+        let catalogue = catalogue();
+        let negotiated = negotiate_languages(
+            &requested,
+            &catalogue.locales,
+            Some(&DEFAULT_LOCALE),
+            NegotiationStrategy::Lookup,
+        );
+        negotiated
+            .first()
+            .map(|locale| Locale((*locale).clone()))
+            .unwrap_or_default()
+    }
+
+    /// Parses the locales out of an `Accept-Language` header, most preferred
+    /// first.
+    pub fn requested_in_accept_language(header: &str) -> Vec<String> {
+        accepted_languages::parse(header)
+            .into_iter()
+            .map(|locale| locale.to_string())
+            .collect()
+    }
+
+    pub fn message(&self, id: &str) -> String {
+        self.format(id, None)
+    }
+
+    pub fn message_with(
+        &self,
+        id: &str,
+        args: &[(&str, FluentValue<'_>)],
+    ) -> String {
+        let args = args.iter().cloned().collect::<FluentArgs>();
+        self.format(id, Some(&args))
+    }
+
+    /// Falls back to the default locale for messages that have not been
+    /// translated, and to the message id if even that is missing.
+    fn format(&self, id: &str, args: Option<&FluentArgs>) -> String {
+        let catalogue = catalogue();
+        [&self.0, &DEFAULT_LOCALE]
+            .into_iter()
+            .filter_map(|locale| {
+                let index =
+                    catalogue.locales.iter().position(|l| l == locale)?;
+                let bundle = &catalogue.bundles[index];
+                let pattern = bundle.get_message(id)?.value()?;
+                let mut errors = Vec::new();
+                let message = bundle.format_pattern(pattern, args, &mut errors);
+                if !errors.is_empty() {
+                    tracing::warn!(
+                        "Errors formatting {} for {}: {:?}",
+                        id,
+                        locale,
+                        errors
+                    );
+                }
+                Some(message.into_owned())
+            })
+            .next()
+            .unwrap_or_else(|| id.to_string())
+    }
+}
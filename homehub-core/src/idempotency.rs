@@ -0,0 +1,170 @@
+use std::time::Duration;
+
+use chrono::{NaiveDateTime, Utc};
+use homehub_db::DatabaseConnection;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::error::Error;
+
+const MAX_KEY_LENGTH: usize = 255;
+
+/// A response stored for replaying to retries of the request.
+#[derive(Debug, Clone)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+pub enum IdempotentRequest {
+    /// The key has not been used before. The request should be handled and
+    /// then either completed or abandoned.
+    New,
+    /// The request was already handled, and this was the response.
+    Replay(StoredResponse),
+}
+
+/// Identifies a request, so that a key cannot be reused for a different one.
+pub fn fingerprint(method: &str, path: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_bytes());
+    hasher.update(b" ");
+    hasher.update(path.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(body);
+    format!("{:x}", hasher.finalize())
+}
+
+/// `now - duration`, or the beginning of time if that is out of range.
+fn before_now(duration: Duration) -> NaiveDateTime {
+    chrono::Duration::from_std(duration)
+        .ok()
+        .and_then(|duration| {
+            Utc::now().naive_utc().checked_sub_signed(duration)
+        })
+        .unwrap_or(NaiveDateTime::MIN)
+}
+
+/// Claims an `Idempotency-Key` for a request, or returns the response of the
+/// request that already used it within `ttl`. A request that has held the
+/// key for longer than `lease` without completing or calling `renew` is
+/// presumed lost, and the key is handed to this one instead.
+pub async fn begin(
+    user_id: Uuid,
+    key: &str,
+    fingerprint: &str,
+    ttl: Duration,
+    lease: Duration,
+    db: &DatabaseConnection,
+) -> Result<IdempotentRequest, Error> {
+    if key.is_empty()
+        || key.len() > MAX_KEY_LENGTH
+        || !key
+            .bytes()
+            .all(|byte| byte.is_ascii_graphic() || byte == b' ')
+    {
+        return Err(Error::InvalidIdempotencyKeyError);
+    }
+
+    homehub_db::queries::idempotency_key::delete_expired_keys(
+        before_now(ttl),
+        db,
+    )
+    .await
+    .map_err(Error::DbError)?;
+
+    let inserted = homehub_db::queries::idempotency_key::insert_key(
+        user_id,
+        key,
+        fingerprint,
+        db,
+    )
+    .await
+    .map_err(Error::DbError)?;
+    if inserted {
+        return Ok(IdempotentRequest::New);
+    }
+
+    // The key may have been abandoned in the meantime, in which case the
+    // client can simply try again.
+    let stored =
+        homehub_db::queries::idempotency_key::get_key(user_id, key, db)
+            .await
+            .map_err(Error::DbError)?
+            .ok_or(Error::IdempotencyKeyInProgressError)?;
+    if stored.fingerprint != fingerprint {
+        return Err(Error::IdempotencyKeyReusedError);
+    }
+    let (Some(status), Some(headers), Some(body)) = (
+        stored.response_status,
+        stored.response_headers,
+        stored.response_body,
+    ) else {
+        let reclaimed = homehub_db::queries::idempotency_key::reclaim_key(
+            user_id,
+            key,
+            before_now(lease),
+            db,
+        )
+        .await
+        .map_err(Error::DbError)?;
+        return match reclaimed {
+            true => Ok(IdempotentRequest::New),
+            false => Err(Error::IdempotencyKeyInProgressError),
+        };
+    };
+
+    Ok(IdempotentRequest::Replay(StoredResponse {
+        status: u16::try_from(status).map_err(|e| Error::DbError(e.into()))?,
+        headers: serde_json::from_value(headers)
+            .map_err(|e| Error::DbError(e.into()))?,
+        body,
+    }))
+}
+
+/// Extends the lease on the key of a request begun with `begin`, which has
+/// to be done more often than every `lease` while the request runs. Returns
+/// `false` if the key was lost, in which case another request may be
+/// running with it.
+pub async fn renew(
+    user_id: Uuid,
+    key: &str,
+    db: &DatabaseConnection,
+) -> Result<bool, Error> {
+    homehub_db::queries::idempotency_key::renew_key(user_id, key, db)
+        .await
+        .map_err(Error::DbError)
+}
+
+/// Stores the response to a request begun with `begin`.
+pub async fn complete(
+    user_id: Uuid,
+    key: &str,
+    response: StoredResponse,
+    db: &DatabaseConnection,
+) -> Result<(), Error> {
+    let headers = serde_json::to_value(response.headers)
+        .map_err(|e| Error::DbError(e.into()))?;
+    homehub_db::queries::idempotency_key::complete_key(
+        user_id,
+        key,
+        response.status.into(),
+        headers,
+        response.body,
+        db,
+    )
+    .await
+    .map_err(Error::DbError)
+}
+
+/// Releases the key of a request that failed, so that it can be retried.
+pub async fn abandon(
+    user_id: Uuid,
+    key: &str,
+    db: &DatabaseConnection,
+) -> Result<(), Error> {
+    homehub_db::queries::idempotency_key::delete_key(user_id, key, db)
+        .await
+        .map_err(Error::DbError)
+}
@@ -0,0 +1,407 @@
+use anyhow::Context;
+use base64::{engine::general_purpose, Engine as _};
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve,
+    EllipticCurveKeyParameters, EllipticCurveKeyType, Jwk, JwkSet,
+    KeyAlgorithm, OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse,
+    RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use pkcs1::der::Decode;
+use sha2::{Digest, Sha256};
+use spki::ObjectIdentifier;
+
+const RSA_ENCRYPTION: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.1");
+const EC_PUBLIC_KEY: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+const SECP256R1: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.2.840.10045.3.1.7");
+const ED25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.112");
+
+pub const SUPPORTED_ALGORITHMS: &[Algorithm] =
+    &[Algorithm::RS256, Algorithm::ES256, Algorithm::EdDSA];
+
+#[derive(Clone)]
+pub struct SigningKey {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    pub jwk: Jwk,
+    encoding_key: Option<EncodingKey>,
+    decoding_key: DecodingKey,
+}
+
+impl std::fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SigningKey")
+            .field("kid", &self.kid)
+            .field("algorithm", &self.algorithm)
+            .field("can_sign", &self.encoding_key.is_some())
+            .finish()
+    }
+}
+
+impl SigningKey {
+    /// Builds a key from base64 encoded PEMs, as they are stored in the
+    /// environment. The algorithm follows from the type of the public key.
+    /// Keys without a private half can only verify tokens.
+    pub fn from_base64_pem(
+        public_key: &str,
+        private_key: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        let public_pem = decode_base64_pem(public_key)?;
+        let (algorithm, jwk_parameters) = parse_public_key(&public_pem)?;
+        let kid = thumbprint(&jwk_parameters);
+
+        let encoding_key = private_key
+            .map(|private_key| {
+                let private_pem = decode_base64_pem(private_key)?;
+                let encoding_key = match algorithm {
+                    Algorithm::ES256 => EncodingKey::from_ec_pem(&private_pem),
+                    Algorithm::EdDSA => EncodingKey::from_ed_pem(&private_pem),
+                    _ => EncodingKey::from_rsa_pem(&private_pem),
+                };
+                encoding_key.with_context(|| {
+                    format!("Private key is not a {:?} key", algorithm)
+                })
+            })
+            .transpose()?;
+
+        let decoding_key = match algorithm {
+            Algorithm::ES256 => DecodingKey::from_ec_pem(&public_pem)?,
+            Algorithm::EdDSA => DecodingKey::from_ed_pem(&public_pem)?,
+            _ => DecodingKey::from_rsa_pem(&public_pem)?,
+        };
+
+        Ok(Self {
+            jwk: Jwk {
+                common: CommonParameters {
+                    public_key_use: Some(PublicKeyUse::Signature),
+                    key_algorithm: Some(match algorithm {
+                        Algorithm::ES256 => KeyAlgorithm::ES256,
+                        Algorithm::EdDSA => KeyAlgorithm::EdDSA,
+                        _ => KeyAlgorithm::RS256,
+                    }),
+                    key_id: Some(kid.clone()),
+                    ..Default::default()
+                },
+                algorithm: jwk_parameters,
+            },
+            kid,
+            algorithm,
+            encoding_key,
+            decoding_key,
+        })
+    }
+
+    pub fn encoding_key(&self) -> Option<&EncodingKey> {
+        self.encoding_key.as_ref()
+    }
+
+    pub fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding_key
+    }
+}
+
+/// The key used to sign new tokens plus any retired keys whose tokens are
+/// still accepted, so that keys can be rotated without logging users out.
+#[derive(Debug, Clone)]
+pub struct KeySet {
+    keys: Vec<SigningKey>,
+}
+
+impl KeySet {
+    /// `algorithm` pins the algorithm new tokens are signed with, so that a
+    /// key of the wrong type is caught at startup rather than silently used.
+    pub fn from_base64_pems(
+        private_key: &str,
+        public_key: &str,
+        previous_public_keys: &[String],
+        algorithm: Option<Algorithm>,
+    ) -> anyhow::Result<Self> {
+        let active_key =
+            SigningKey::from_base64_pem(public_key, Some(private_key))?;
+        if let Some(algorithm) = algorithm {
+            anyhow::ensure!(
+                active_key.algorithm == algorithm,
+                "Signing key is a {:?} key but {:?} is configured",
+                active_key.algorithm,
+                algorithm
+            );
+        }
+
+        let mut keys = vec![active_key];
+        for previous_public_key in previous_public_keys {
+            keys.push(SigningKey::from_base64_pem(previous_public_key, None)?);
+        }
+        Ok(Self { keys })
+    }
+
+    pub fn active(&self) -> &SigningKey {
+        &self.keys[0]
+    }
+
+    pub fn find(&self, kid: &str) -> Option<&SigningKey> {
+        self.keys.iter().find(|key| key.kid == kid)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SigningKey> {
+        self.keys.iter()
+    }
+
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self.keys.iter().map(|key| key.jwk.clone()).collect(),
+        }
+    }
+}
+
+fn decode_base64_pem(key: &str) -> anyhow::Result<Vec<u8>> {
+    general_purpose::STANDARD
+        .decode(key.trim())
+        .context("Key is not valid base64")
+}
+
+fn parse_public_key(
+    public_pem: &[u8],
+) -> anyhow::Result<(Algorithm, AlgorithmParameters)> {
+    let pem = pem::parse(public_pem)?;
+    if pem.tag() == "RSA PUBLIC KEY" {
+        return Ok((Algorithm::RS256, rsa_jwk_parameters(pem.contents())?));
+    }
+    anyhow::ensure!(
+        pem.tag() == "PUBLIC KEY",
+        "Unsupported public key PEM: {}",
+        pem.tag()
+    );
+
+    let public_key = spki::SubjectPublicKeyInfoRef::from_der(pem.contents())
+        .map_err(|e| anyhow::anyhow!("Invalid public key: {}", e))?;
+    let key_bytes = public_key.subject_public_key.raw_bytes();
+
+    match public_key.algorithm.oid {
+        RSA_ENCRYPTION => {
+            Ok((Algorithm::RS256, rsa_jwk_parameters(key_bytes)?))
+        }
+        EC_PUBLIC_KEY => {
+            anyhow::ensure!(
+                public_key.algorithm.parameters_oid().ok() == Some(SECP256R1),
+                "Only P-256 elliptic curve keys are supported"
+            );
+            // An uncompressed point: 0x04 followed by the x and y coordinates
+            anyhow::ensure!(
+                key_bytes.len() == 65 && key_bytes[0] == 0x04,
+                "Elliptic curve key is not an uncompressed P-256 point"
+            );
+            Ok((
+                Algorithm::ES256,
+                AlgorithmParameters::EllipticCurve(
+                    EllipticCurveKeyParameters {
+                        key_type: EllipticCurveKeyType::EC,
+                        curve: EllipticCurve::P256,
+                        x: general_purpose::URL_SAFE_NO_PAD
+                            .encode(&key_bytes[1..33]),
+                        y: general_purpose::URL_SAFE_NO_PAD
+                            .encode(&key_bytes[33..]),
+                    },
+                ),
+            ))
+        }
+        ED25519 => Ok((
+            Algorithm::EdDSA,
+            AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: general_purpose::URL_SAFE_NO_PAD.encode(key_bytes),
+            }),
+        )),
+        oid => anyhow::bail!("Unsupported public key algorithm: {}", oid),
+    }
+}
+
+fn rsa_jwk_parameters(
+    rsa_public_key_der: &[u8],
+) -> anyhow::Result<AlgorithmParameters> {
+    let rsa_public_key = pkcs1::RsaPublicKey::from_der(rsa_public_key_der)
+        .map_err(|e| anyhow::anyhow!("Invalid RSA public key: {}", e))?;
+
+    Ok(AlgorithmParameters::RSA(RSAKeyParameters {
+        key_type: RSAKeyType::RSA,
+        n: general_purpose::URL_SAFE_NO_PAD
+            .encode(rsa_public_key.modulus.as_bytes()),
+        e: general_purpose::URL_SAFE_NO_PAD
+            .encode(rsa_public_key.public_exponent.as_bytes()),
+    }))
+}
+
+/// RFC 7638 JWK thumbprint, used as the key id so that it is stable for a
+/// given key without having to be configured.
+fn thumbprint(parameters: &AlgorithmParameters) -> String {
+    let canonical = match parameters {
+        AlgorithmParameters::RSA(rsa) => {
+            format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, rsa.e, rsa.n)
+        }
+        AlgorithmParameters::EllipticCurve(ec) => format!(
+            r#"{{"crv":"P-256","kty":"EC","x":"{}","y":"{}"}}"#,
+            ec.x, ec.y
+        ),
+        AlgorithmParameters::OctetKeyPair(okp) => {
+            format!(r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#, okp.x)
+        }
+        AlgorithmParameters::OctetKey(_) => {
+            unreachable!("symmetric keys are never parsed")
+        }
+    };
+    general_purpose::URL_SAFE_NO_PAD
+        .encode(Sha256::digest(canonical.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RSA_SPKI_PEM: &str = "-----BEGIN PUBLIC KEY-----
+MIGfMA0GCSqGSIb3DQEBAQUAA4GNADCBiQKBgQC+SY68b8PM1huULqpEcN+OLcvO
+Qr4KQmkJJ65GbXdTCeFtMfG+qoYjn9VJWlNdawuYypMtFhm0eU+MqR/kANCIJ/dJ
+1Fw23mwPkmp3m/nVLnfTWi9wJw2tdVCTiigu59USdsHgkFIK/uvu9maG/5Y27lCJ
+QIh3SzRQz0q0HNJWNQIDAQAB
+-----END PUBLIC KEY-----";
+
+    /// The same key as `RSA_SPKI_PEM`, in PKCS#1 form.
+    const RSA_PKCS1_PEM: &str = "-----BEGIN RSA PUBLIC KEY-----
+MIGJAoGBAL5Jjrxvw8zWG5QuqkRw344ty85CvgpCaQknrkZtd1MJ4W0x8b6qhiOf
+1UlaU11rC5jKky0WGbR5T4ypH+QA0Ign90nUXDbebA+Saneb+dUud9NaL3AnDa11
+UJOKKC7n1RJ2weCQUgr+6+72Zob/ljbuUIlAiHdLNFDPSrQc0lY1AgMBAAE=
+-----END RSA PUBLIC KEY-----";
+
+    const P256_PEM: &str = "-----BEGIN PUBLIC KEY-----
+MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEGpk1mK9oIhzS/COzJiGqFZkaJu39
+38chczUa7tzKJy01wlfdPV9Lt0prgUIqnFANu4NG2V0RrrKV93zpFzSmAQ==
+-----END PUBLIC KEY-----";
+
+    const P384_PEM: &str = "-----BEGIN PUBLIC KEY-----
+MHYwEAYHKoZIzj0CAQYFK4EEACIDYgAEt+EEgq9XLuOmliyoYZkplFUk/0phfzyx
+YJ4Ni0SwuEjoVvqZUDXjNxca2A97mLZ5P5C38oAzbqpliQ16XXsX66XFzw67qSrD
+8VaRhBoDaYGwZwDDZ/Pg0ivOSp2eTMfC
+-----END PUBLIC KEY-----";
+
+    /// The example key of RFC 8037, appendix A.2.
+    const ED25519_PEM: &str = "-----BEGIN PUBLIC KEY-----
+MCowBQYDK2VwAyEA11qYAYKxCrfVS/7TyWQHOg7hcvPapiMlrwIaaPcHURo=
+-----END PUBLIC KEY-----";
+
+    const X25519_PEM: &str = "-----BEGIN PUBLIC KEY-----
+MCowBQYDK2VuAyEAsJ3NY9FV4Q8/FPlKTfDCUNF25dW14bQsCeJhYsX6owA=
+-----END PUBLIC KEY-----";
+
+    #[test]
+    fn parses_rsa_keys() {
+        let (algorithm, parameters) =
+            parse_public_key(RSA_SPKI_PEM.as_bytes()).unwrap();
+
+        assert_eq!(algorithm, Algorithm::RS256);
+        let AlgorithmParameters::RSA(rsa) = parameters else {
+            panic!("not an RSA key: {:?}", parameters);
+        };
+        assert_eq!(rsa.e, "AQAB");
+        // A 1024 bit modulus
+        assert_eq!(
+            general_purpose::URL_SAFE_NO_PAD
+                .decode(&rsa.n)
+                .unwrap()
+                .len(),
+            128
+        );
+    }
+
+    #[test]
+    fn parses_pkcs1_rsa_keys_like_their_spki_form() {
+        let (spki_algorithm, spki) =
+            parse_public_key(RSA_SPKI_PEM.as_bytes()).unwrap();
+        let (pkcs1_algorithm, pkcs1) =
+            parse_public_key(RSA_PKCS1_PEM.as_bytes()).unwrap();
+
+        assert_eq!(pkcs1_algorithm, spki_algorithm);
+        assert_eq!(pkcs1, spki);
+        assert_eq!(thumbprint(&pkcs1), thumbprint(&spki));
+    }
+
+    #[test]
+    fn parses_p256_keys() {
+        let (algorithm, parameters) =
+            parse_public_key(P256_PEM.as_bytes()).unwrap();
+
+        assert_eq!(algorithm, Algorithm::ES256);
+        let AlgorithmParameters::EllipticCurve(ec) = parameters else {
+            panic!("not an elliptic curve key: {:?}", parameters);
+        };
+        assert_eq!(ec.curve, EllipticCurve::P256);
+        assert_eq!(ec.x, "Gpk1mK9oIhzS_COzJiGqFZkaJu3938chczUa7tzKJy0");
+        assert_eq!(ec.y, "NcJX3T1fS7dKa4FCKpxQDbuDRtldEa6ylfd86Rc0pgE");
+    }
+
+    #[test]
+    fn rejects_other_elliptic_curves() {
+        let error = parse_public_key(P384_PEM.as_bytes()).unwrap_err();
+
+        assert!(error.to_string().contains("P-256"), "{}", error);
+    }
+
+    #[test]
+    fn parses_ed25519_keys() {
+        let (algorithm, parameters) =
+            parse_public_key(ED25519_PEM.as_bytes()).unwrap();
+
+        assert_eq!(algorithm, Algorithm::EdDSA);
+        let AlgorithmParameters::OctetKeyPair(okp) = &parameters else {
+            panic!("not an octet key pair: {:?}", parameters);
+        };
+        assert_eq!(okp.curve, EllipticCurve::Ed25519);
+        assert_eq!(okp.x, "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo");
+        // RFC 8037, appendix A.3
+        assert_eq!(
+            thumbprint(&parameters),
+            "kPrK_qmxVWaYVA9wwBF6Iuo3vVzz7TxHCTwXBygrS4k"
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_key_algorithms() {
+        let error = parse_public_key(X25519_PEM.as_bytes()).unwrap_err();
+
+        assert!(error.to_string().contains("1.3.101.110"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_other_pems() {
+        let pem = P256_PEM.replace("PUBLIC KEY", "CERTIFICATE");
+
+        assert!(parse_public_key(pem.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn thumbprints_rsa_keys_as_in_rfc_7638() {
+        let parameters = AlgorithmParameters::RSA(RSAKeyParameters {
+            key_type: RSAKeyType::RSA,
+            n: "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw".to_string(),
+            e: "AQAB".to_string(),
+        });
+
+        assert_eq!(
+            thumbprint(&parameters),
+            "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs"
+        );
+    }
+
+    #[test]
+    fn uses_the_thumbprint_as_key_id() {
+        let key = SigningKey::from_base64_pem(
+            &general_purpose::STANDARD.encode(P256_PEM),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(key.kid, thumbprint(&key.jwk.algorithm));
+        assert_eq!(key.jwk.common.key_id.as_deref(), Some(key.kid.as_str()));
+    }
+}
@@ -1,4 +1,20 @@
+pub mod admin;
 pub mod config;
+pub mod device;
+pub mod error;
+pub mod i18n;
+pub mod idempotency;
+pub mod keys;
 pub mod light;
+pub mod location;
+pub mod login_throttle;
+pub mod mail;
+pub mod oauth;
+pub mod oidc;
+pub mod pagination;
+pub mod password;
+pub mod profile;
+pub mod sensor;
 pub mod token;
 pub mod user;
+pub mod zone;
@@ -1,19 +1,116 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::NaiveDateTime;
 pub use homehub_db::light::LightState;
+use homehub_db::queries::device::ProfileLink;
+pub use homehub_db::queries::light::{LightFilter, LightSelector, LightSort};
 use homehub_db::DatabaseConnection;
 use serde::Serialize;
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+
+use crate::device::{
+    default_capabilities, validate_state, with_required, DeviceKind,
+    DeviceState,
+};
+use crate::error::Error;
+use crate::pagination::{Page, PageRequest};
+use crate::profile::{find_profile, get_profile, DeviceProfile};
+
+/// How many state changes a slow listener may fall behind before it starts
+/// missing them.
+const LIGHT_EVENTS_CAPACITY: usize = 256;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct LightDto {
     pub id: uuid::Uuid,
     pub name: String,
     pub state: LightState,
     pub room: Option<RoomDto>,
+    /// The make and model of the light, when known.
+    pub profile: Option<DeviceProfile>,
+    pub updated_at: NaiveDateTime,
+    pub version: i32,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct RoomDto {
     pub id: uuid::Uuid,
     pub name: String,
+    pub location_id: uuid::Uuid,
+    pub version: i32,
+    /// The combined state of the room's lights. Not included when the room
+    /// is part of a light.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<AggregateState>,
+}
+
+impl From<homehub_db::room::Model> for RoomDto {
+    fn from(value: homehub_db::room::Model) -> Self {
+        RoomDto {
+            id: value.id,
+            name: value.name,
+            location_id: value.location_id,
+            version: value.version,
+            state: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AggregatePower {
+    AllOn,
+    SomeOn,
+    /// Also used when there are no lights at all.
+    AllOff,
+}
+
+/// The combined state of a group of lights, such as those of a room.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct AggregateState {
+    pub power: AggregatePower,
+    /// The average colour of the lights that are on and have a colour.
+    pub colour: Option<[u8; 3]>,
+    pub light_count: usize,
+}
+
+impl AggregateState {
+    pub fn of<'a>(states: impl IntoIterator<Item = &'a LightState>) -> Self {
+        let mut light_count = 0;
+        let mut on_count = 0;
+        let mut colour_count = 0u64;
+        let mut colour_sums = [0u64; 3];
+        for state in states {
+            light_count += 1;
+            if !state.on {
+                continue;
+            }
+            on_count += 1;
+            if let Some(colour) = state.colour {
+                colour_count += 1;
+                for (sum, channel) in colour_sums.iter_mut().zip(colour) {
+                    *sum += u64::from(channel);
+                }
+            }
+        }
+
+        let power = match on_count {
+            0 => AggregatePower::AllOff,
+            _ if on_count == light_count => AggregatePower::AllOn,
+            _ => AggregatePower::SomeOn,
+        };
+        // The average of channels can never exceed 255.
+        let colour = (colour_count > 0).then(|| {
+            colour_sums
+                .map(|sum| (sum as f64 / colour_count as f64).round() as u8)
+        });
+        AggregateState {
+            power,
+            colour,
+            light_count,
+        }
+    }
 }
 
 impl From<(homehub_db::light::Model, Option<homehub_db::room::Model>)>
@@ -26,22 +123,82 @@ impl From<(homehub_db::light::Model, Option<homehub_db::room::Model>)>
             id: value.0.id,
             name: value.0.name,
             state: value.0.state,
-            room: value.1.map(|room| RoomDto {
-                id: room.id,
-                name: room.name,
-            }),
+            room: value.1.map(Into::into),
+            profile: value
+                .0
+                .profile_id
+                .as_deref()
+                .and_then(get_profile)
+                .cloned(),
+            updated_at: value.0.updated_at,
+            version: value.0.version,
         }
     }
 }
 
+/// Broadcasts every light state change to whoever is listening, such as
+/// GraphQL subscriptions.
+#[derive(Clone)]
+pub struct LightEvents {
+    sender: broadcast::Sender<LightDto>,
+}
+
+impl Default for LightEvents {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(LIGHT_EVENTS_CAPACITY);
+        LightEvents { sender }
+    }
+}
+
+impl LightEvents {
+    pub fn subscribe(&self) -> broadcast::Receiver<LightDto> {
+        self.sender.subscribe()
+    }
+
+    pub(crate) fn publish(&self, light: &LightDto) {
+        // Sending only fails when nobody is listening.
+        let _ = self.sender.send(light.clone());
+    }
+}
+
+/// Rooms are referenced by id in requests, so make sure one exists before
+/// linking a light to it.
+async fn ensure_room_exists(
+    room_id: &uuid::Uuid,
+    db: &DatabaseConnection,
+) -> Result<(), Error> {
+    homehub_db::queries::room::get_room(room_id, db)
+        .await
+        .map_err(Error::DbError)?
+        .ok_or(Error::RoomNotFoundError)?;
+    Ok(())
+}
+
+/// Links the light to `profile_id` when given, which decides what the
+/// light can do.
 pub async fn create_light(
     name: &str,
     room_id: Option<uuid::Uuid>,
+    profile_id: Option<&str>,
     db: &DatabaseConnection,
-) -> anyhow::Result<LightDto> {
+) -> Result<LightDto, Error> {
+    if let Some(room_id) = &room_id {
+        ensure_room_exists(room_id, db).await?;
+    }
+    let profile = profile_id
+        .map(|profile_id| find_profile(profile_id, DeviceKind::Light))
+        .transpose()?;
+    let capabilities = profile
+        .map(|profile| with_required(DeviceKind::Light, &profile.capabilities))
+        .unwrap_or_default();
+    let profile = profile.map(|profile| ProfileLink {
+        id: &profile.id,
+        capabilities: &capabilities,
+    });
     let light: LightDto =
-        homehub_db::queries::light::create_light(name, room_id, db)
-            .await?
+        homehub_db::queries::light::create_light(name, room_id, profile, db)
+            .await
+            .map_err(Error::DbError)?
             .into();
 
     Ok(light)
@@ -50,34 +207,280 @@ pub async fn create_light(
 pub async fn get_light(
     id: &uuid::Uuid,
     db: &DatabaseConnection,
-) -> anyhow::Result<LightDto> {
-    let light: LightDto =
-        homehub_db::queries::light::get_light(id, db).await?.into();
-    Ok(light)
+) -> Result<LightDto, Error> {
+    homehub_db::queries::light::get_light(id, db)
+        .await
+        .map_err(Error::DbError)?
+        .map(Into::into)
+        .ok_or(Error::LightNotFoundError)
+}
+
+pub async fn get_lights(
+    filter: &LightFilter,
+    page: &PageRequest<LightSort>,
+    db: &DatabaseConnection,
+) -> Result<Page<LightDto>, Error> {
+    let lights = homehub_db::queries::light::get_lights(filter, page, db)
+        .await
+        .map_err(Error::DbError)?;
+    Ok(lights.map(Into::into))
+}
+
+/// Explains why a conditional update changed nothing: either the light is
+/// gone, or it is no longer at the version the client expected.
+async fn not_updated_error(
+    id: &uuid::Uuid,
+    if_match: Option<&[i32]>,
+    db: &DatabaseConnection,
+) -> Error {
+    match homehub_db::queries::light::get_light(id, db).await {
+        Ok(Some(_)) if if_match.is_some() => Error::PreconditionFailedError,
+        Ok(_) => Error::LightNotFoundError,
+        Err(e) => Error::DbError(e),
+    }
+}
+
+/// Checks a state against the capabilities of a light without a profile,
+/// which are those every light had before profiles existed.
+fn validate_without_profile(state: &LightState) -> Result<(), Error> {
+    validate_state(
+        &DeviceState {
+            on: Some(state.on),
+            colour: state.colour,
+            ..Default::default()
+        },
+        default_capabilities(DeviceKind::Light),
+    )
 }
 
+/// A light with a profile only accepts a colour if it can show one, and
+/// has it brought into its gamut. `if_match` lists the versions the client
+/// expects the light to be at, as sent in an `If-Match` header. `None`
+/// updates whatever version is current.
 pub async fn set_light_state(
     id: &uuid::Uuid,
     state: LightState,
+    if_match: Option<&[i32]>,
     db: &DatabaseConnection,
-) -> anyhow::Result<LightDto> {
-    let light: LightDto =
-        homehub_db::queries::light::set_light_state(id, state, db)
-            .await?
-            .into();
-    // Trigger listeners here
+    events: &LightEvents,
+) -> Result<LightDto, Error> {
+    let state = match get_light(id, db).await?.profile {
+        Some(profile) => profile.fit_light_state(state)?,
+        None => {
+            validate_without_profile(&state)?;
+            state
+        }
+    };
+    let light: LightDto = match homehub_db::queries::light::set_light_state(
+        id, state, if_match, db,
+    )
+    .await
+    .map_err(Error::DbError)?
+    {
+        Some(light) => light.into(),
+        None => return Err(not_updated_error(id, if_match, db).await),
+    };
+    events.publish(&light);
     Ok(light)
 }
 
+/// The outcome of a bulk operation for one light.
+#[derive(Debug)]
+pub struct LightResult {
+    pub id: uuid::Uuid,
+    pub result: Result<LightDto, Error>,
+}
+
+/// Sets the state of many lights at once, in a single transaction. Lights
+/// with a profile ignore a colour they cannot show rather than failing; the
+/// state must suit lights without a profile, or nothing is changed.
+/// Listeners are told about every change once it has been committed.
+///
+/// Lights selected by id get a result each, in the order asked for, with a
+/// `LightNotFoundError` for those that do not exist. A room, location or
+/// zone gets a result for each light it contains, all of them successful,
+/// and fails the whole operation if it does not exist.
+///
+/// Nothing is sent to the lights themselves: there are no device drivers,
+/// so this only records the state they should be in.
+pub async fn set_light_states(
+    selector: &LightSelector,
+    state: LightState,
+    db: &DatabaseConnection,
+    events: &LightEvents,
+) -> Result<Vec<LightResult>, Error> {
+    match selector {
+        LightSelector::Ids(_) => {}
+        LightSelector::Room(room_id) => ensure_room_exists(room_id, db).await?,
+        LightSelector::Location(location_id) => {
+            homehub_db::queries::location::get_location(location_id, db)
+                .await
+                .map_err(Error::DbError)?
+                .ok_or(Error::LocationNotFoundError)?;
+        }
+        LightSelector::Zone(zone_id) => {
+            homehub_db::queries::zone::get_zone(zone_id, db)
+                .await
+                .map_err(Error::DbError)?
+                .ok_or(Error::ZoneNotFoundError)?;
+        }
+    }
+    // Whichever lights turn out to have no profile get the state as it is.
+    validate_without_profile(&state)?;
+    let lights: Vec<LightDto> = homehub_db::queries::light::set_light_states(
+        selector,
+        |profile_id| match profile_id.and_then(get_profile) {
+            Some(profile) => profile.adapt_light_state(state.clone()),
+            None => state.clone(),
+        },
+        db,
+    )
+    .await
+    .map_err(Error::DbError)?
+    .into_iter()
+    .map(Into::into)
+    .collect();
+    lights.iter().for_each(|light| events.publish(light));
+
+    let LightSelector::Ids(ids) = selector else {
+        return Ok(lights
+            .into_iter()
+            .map(|light| LightResult {
+                id: light.id,
+                result: Ok(light),
+            })
+            .collect());
+    };
+    // Answer in the order the lights were asked for, once per light.
+    let mut lights: HashMap<_, _> =
+        lights.into_iter().map(|light| (light.id, light)).collect();
+    let mut seen = HashSet::new();
+    Ok(ids
+        .iter()
+        .filter(|id| seen.insert(**id))
+        .map(|id| LightResult {
+            id: *id,
+            result: lights.remove(id).ok_or(Error::LightNotFoundError),
+        })
+        .collect())
+}
+
+/// Linking a profile replaces the light's capabilities with the profile's.
+/// `if_match` lists the versions the client expects the light to be at, as
+/// sent in an `If-Match` header. `None` updates whatever version is current.
 pub async fn update_light(
     id: &uuid::Uuid,
     name: Option<&str>,
     room_id: Option<Option<uuid::Uuid>>,
+    profile_id: Option<Option<&str>>,
+    if_match: Option<&[i32]>,
     db: &DatabaseConnection,
-) -> anyhow::Result<LightDto> {
-    let light: LightDto =
-        homehub_db::queries::light::update_light(id, name, room_id, db)
-            .await?
-            .into();
-    Ok(light)
+) -> Result<LightDto, Error> {
+    if let Some(Some(room_id)) = &room_id {
+        ensure_room_exists(room_id, db).await?;
+    }
+    let capabilities;
+    let profile = match profile_id {
+        Some(Some(profile_id)) => {
+            let profile = find_profile(profile_id, DeviceKind::Light)?;
+            capabilities =
+                with_required(DeviceKind::Light, &profile.capabilities);
+            Some(Some(ProfileLink {
+                id: &profile.id,
+                capabilities: &capabilities,
+            }))
+        }
+        Some(None) => Some(None),
+        None => None,
+    };
+    match homehub_db::queries::light::update_light(
+        id, name, room_id, profile, if_match, db,
+    )
+    .await
+    .map_err(Error::DbError)?
+    {
+        Some(light) => Ok(light.into()),
+        None => Err(not_updated_error(id, if_match, db).await),
+    }
+}
+
+pub async fn delete_light(
+    id: &uuid::Uuid,
+    db: &DatabaseConnection,
+) -> Result<(), Error> {
+    match homehub_db::queries::light::delete_light(id, db).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(Error::LightNotFoundError),
+        Err(e) => Err(Error::DbError(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn light(on: bool, colour: Option<[u8; 3]>) -> LightState {
+        LightState { on, colour }
+    }
+
+    #[test]
+    fn no_lights_are_all_off() {
+        assert_eq!(
+            AggregateState::of([]),
+            AggregateState {
+                power: AggregatePower::AllOff,
+                colour: None,
+                light_count: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn tells_all_from_some_lights_on() {
+        let on = light(true, None);
+        let off = light(false, None);
+
+        assert_eq!(AggregateState::of([&on, &on]).power, AggregatePower::AllOn);
+        assert_eq!(
+            AggregateState::of([&on, &off]).power,
+            AggregatePower::SomeOn
+        );
+        assert_eq!(
+            AggregateState::of([&off, &off]).power,
+            AggregatePower::AllOff
+        );
+        assert_eq!(AggregateState::of([&on, &off, &off]).light_count, 3);
+    }
+
+    #[test]
+    fn averages_the_colours_of_lights_that_are_on() {
+        let states = [
+            light(true, Some([255, 0, 100])),
+            light(true, Some([0, 0, 51])),
+            // Neither of these count towards the colour.
+            light(true, None),
+            light(false, Some([0, 255, 0])),
+        ];
+
+        let state = AggregateState::of(&states);
+
+        assert_eq!(state.colour, Some([128, 0, 76]));
+        assert_eq!(state.power, AggregatePower::SomeOn);
+        assert_eq!(state.light_count, 4);
+    }
+
+    #[test]
+    fn averages_bright_colours_without_overflowing() {
+        let states = vec![light(true, Some([255, 255, 255])); 1000];
+
+        assert_eq!(AggregateState::of(&states).colour, Some([255, 255, 255]));
+    }
+
+    #[test]
+    fn lights_without_a_profile_can_show_colours() {
+        assert!(validate_without_profile(&light(true, None)).is_ok());
+        assert!(
+            validate_without_profile(&light(true, Some([255, 0, 0]))).is_ok()
+        );
+    }
 }
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+
+pub use homehub_db::queries::location::LocationSort;
+pub use homehub_db::queries::room::RoomSort;
+use homehub_db::DatabaseConnection;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::error::Error;
+use crate::light::{
+    AggregateState, LightEvents, LightSelector, LightState, RoomDto,
+};
+use crate::pagination::{Page, PageRequest};
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct LocationDto {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub version: i32,
+    /// The combined state of the lights in all of the location's rooms.
+    pub state: AggregateState,
+}
+
+/// Groups light states by the room or location they belong to.
+fn group_states(
+    states: Vec<(uuid::Uuid, LightState)>,
+) -> HashMap<uuid::Uuid, Vec<LightState>> {
+    let mut groups: HashMap<_, Vec<_>> = HashMap::new();
+    for (id, state) in states {
+        groups.entry(id).or_default().push(state);
+    }
+    groups
+}
+
+async fn with_location_states(
+    locations: Vec<homehub_db::location::Model>,
+    db: &DatabaseConnection,
+) -> Result<Vec<LocationDto>, Error> {
+    let ids: Vec<_> = locations.iter().map(|location| location.id).collect();
+    let states = homehub_db::queries::location::get_light_states(&ids, db)
+        .await
+        .map_err(Error::DbError)?;
+    let states = group_states(states);
+    Ok(locations
+        .into_iter()
+        .map(|location| LocationDto {
+            state: AggregateState::of(
+                states.get(&location.id).into_iter().flatten(),
+            ),
+            id: location.id,
+            name: location.name,
+            version: location.version,
+        })
+        .collect())
+}
+
+async fn with_room_states(
+    rooms: Vec<homehub_db::room::Model>,
+    db: &DatabaseConnection,
+) -> Result<Vec<RoomDto>, Error> {
+    let ids: Vec<_> = rooms.iter().map(|room| room.id).collect();
+    let states = homehub_db::queries::room::get_light_states(&ids, db)
+        .await
+        .map_err(Error::DbError)?;
+    let states = group_states(states);
+    Ok(rooms
+        .into_iter()
+        .map(|room| {
+            let state =
+                AggregateState::of(states.get(&room.id).into_iter().flatten());
+            RoomDto {
+                state: Some(state),
+                ..room.into()
+            }
+        })
+        .collect())
+}
+
+pub async fn get_locations(
+    page: &PageRequest<LocationSort>,
+    db: &DatabaseConnection,
+) -> Result<Page<LocationDto>, Error> {
+    let locations = homehub_db::queries::location::get_locations(page, db)
+        .await
+        .map_err(Error::DbError)?;
+    let items = with_location_states(locations.items, db).await?;
+    Ok(Page {
+        items,
+        next_cursor: locations.next_cursor,
+        limit: locations.limit,
+    })
+}
+
+pub async fn get_location(
+    id: &uuid::Uuid,
+    db: &DatabaseConnection,
+) -> Result<LocationDto, Error> {
+    let location = homehub_db::queries::location::get_location(id, db)
+        .await
+        .map_err(Error::DbError)?
+        .ok_or(Error::LocationNotFoundError)?;
+    with_location_states(vec![location], db)
+        .await?
+        .pop()
+        .ok_or(Error::LocationNotFoundError)
+}
+
+/// Looks up many locations at once, for batching. Unknown ids are left out.
+pub async fn get_locations_by_ids(
+    ids: &[uuid::Uuid],
+    db: &DatabaseConnection,
+) -> Result<Vec<LocationDto>, Error> {
+    let locations =
+        homehub_db::queries::location::get_locations_by_ids(ids, db)
+            .await
+            .map_err(Error::DbError)?;
+    with_location_states(locations, db).await
+}
+
+/// Lists all rooms, or only those of one location.
+pub async fn get_rooms(
+    location_id: Option<&uuid::Uuid>,
+    page: &PageRequest<RoomSort>,
+    db: &DatabaseConnection,
+) -> Result<Page<RoomDto>, Error> {
+    let rooms = homehub_db::queries::room::get_rooms(location_id, page, db)
+        .await
+        .map_err(Error::DbError)?;
+    let items = with_room_states(rooms.items, db).await?;
+    Ok(Page {
+        items,
+        next_cursor: rooms.next_cursor,
+        limit: rooms.limit,
+    })
+}
+
+pub async fn get_room(
+    id: &uuid::Uuid,
+    db: &DatabaseConnection,
+) -> Result<RoomDto, Error> {
+    let room = homehub_db::queries::room::get_room(id, db)
+        .await
+        .map_err(Error::DbError)?
+        .ok_or(Error::RoomNotFoundError)?;
+    with_room_states(vec![room], db)
+        .await?
+        .pop()
+        .ok_or(Error::RoomNotFoundError)
+}
+
+/// Looks up many rooms at once, for batching. Unknown ids are left out.
+pub async fn get_rooms_by_ids(
+    ids: &[uuid::Uuid],
+    db: &DatabaseConnection,
+) -> Result<Vec<RoomDto>, Error> {
+    let rooms = homehub_db::queries::room::get_rooms_by_ids(ids, db)
+        .await
+        .map_err(Error::DbError)?;
+    with_room_states(rooms, db).await
+}
+
+/// Renames a room. `if_match` lists the versions the client expects the
+/// room to be at, as sent in an `If-Match` header. `None` updates whatever
+/// version is current.
+pub async fn update_room(
+    id: &uuid::Uuid,
+    name: Option<&str>,
+    if_match: Option<&[i32]>,
+    db: &DatabaseConnection,
+) -> Result<RoomDto, Error> {
+    match homehub_db::queries::room::update_room(id, name, if_match, db)
+        .await
+        .map_err(Error::DbError)?
+    {
+        Some(room) => with_room_states(vec![room], db)
+            .await?
+            .pop()
+            .ok_or(Error::RoomNotFoundError),
+        None => match homehub_db::queries::room::get_room(id, db).await {
+            Ok(Some(_)) if if_match.is_some() => {
+                Err(Error::PreconditionFailedError)
+            }
+            Ok(_) => Err(Error::RoomNotFoundError),
+            Err(e) => Err(Error::DbError(e)),
+        },
+    }
+}
+
+/// Renames a location, checking `if_match` as [`update_room`] does.
+pub async fn update_location(
+    id: &uuid::Uuid,
+    name: Option<&str>,
+    if_match: Option<&[i32]>,
+    db: &DatabaseConnection,
+) -> Result<LocationDto, Error> {
+    match homehub_db::queries::location::update_location(id, name, if_match, db)
+        .await
+        .map_err(Error::DbError)?
+    {
+        Some(location) => with_location_states(vec![location], db)
+            .await?
+            .pop()
+            .ok_or(Error::LocationNotFoundError),
+        None => {
+            match homehub_db::queries::location::get_location(id, db).await {
+                Ok(Some(_)) if if_match.is_some() => {
+                    Err(Error::PreconditionFailedError)
+                }
+                Ok(_) => Err(Error::LocationNotFoundError),
+                Err(e) => Err(Error::DbError(e)),
+            }
+        }
+    }
+}
+
+/// Switches every light in a room at once.
+pub async fn set_room_state(
+    id: &uuid::Uuid,
+    state: LightState,
+    db: &DatabaseConnection,
+    events: &LightEvents,
+) -> Result<RoomDto, Error> {
+    crate::light::set_light_states(
+        &LightSelector::Room(*id),
+        state,
+        db,
+        events,
+    )
+    .await?;
+    get_room(id, db).await
+}
+
+/// Switches every light in all rooms of a location at once.
+pub async fn set_location_state(
+    id: &uuid::Uuid,
+    state: LightState,
+    db: &DatabaseConnection,
+    events: &LightEvents,
+) -> Result<LocationDto, Error> {
+    crate::light::set_light_states(
+        &LightSelector::Location(*id),
+        state,
+        db,
+        events,
+    )
+    .await?;
+    get_location(id, db).await
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::LoginThrottleConfig;
+
+struct Attempts {
+    failures: u32,
+    locked_until: Option<Instant>,
+    last_failure: Instant,
+}
+
+/// Tracks failed logins per account and per client IP. Once a key exceeds
+/// its free attempts it is locked out, with the lockout doubling on every
+/// further failure up to the configured maximum.
+pub struct LoginThrottle {
+    config: LoginThrottleConfig,
+    attempts: Mutex<HashMap<String, Attempts>>,
+}
+
+impl LoginThrottle {
+    pub fn new(config: LoginThrottleConfig) -> Self {
+        Self {
+            config,
+            attempts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns how long the caller has to wait if either the account or the
+    /// IP is currently locked out.
+    pub fn check(&self, email: &str, ip: IpAddr) -> Option<Duration> {
+        let now = Instant::now();
+        let attempts = self.attempts.lock().unwrap();
+
+        [account_key(email), ip_key(ip)]
+            .iter()
+            .filter_map(|key| attempts.get(key)?.locked_until)
+            .filter(|locked_until| *locked_until > now)
+            .map(|locked_until| locked_until - now)
+            .max()
+    }
+
+    pub fn record_failure(&self, email: &str, ip: IpAddr) {
+        let now = Instant::now();
+        let mut attempts = self.attempts.lock().unwrap();
+        let max_lockout = self.config.max_lockout;
+        attempts.retain(|_, attempt| {
+            now.duration_since(attempt.last_failure) < max_lockout * 2
+        });
+
+        for (key, free_attempts) in [
+            (account_key(email), self.config.max_account_attempts),
+            (ip_key(ip), self.config.max_ip_attempts),
+        ] {
+            let attempt = attempts.entry(key).or_insert(Attempts {
+                failures: 0,
+                locked_until: None,
+                last_failure: now,
+            });
+            attempt.failures += 1;
+            attempt.last_failure = now;
+
+            if attempt.failures >= free_attempts {
+                let doublings = (attempt.failures - free_attempts).min(16);
+                let lockout = self
+                    .config
+                    .base_lockout
+                    .saturating_mul(1 << doublings)
+                    .min(max_lockout);
+                attempt.locked_until = Some(now + lockout);
+            }
+        }
+    }
+
+    /// Clears the account's failures. The IP counter is left alone so that
+    /// logging into one account does not reset guessing against others.
+    pub fn record_success(&self, email: &str) {
+        self.attempts.lock().unwrap().remove(&account_key(email));
+    }
+}
+
+fn account_key(email: &str) -> String {
+    format!("account:{}", email.to_lowercase())
+}
+
+fn ip_key(ip: IpAddr) -> String {
+    format!("ip:{}", ip)
+}
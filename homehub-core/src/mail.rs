@@ -0,0 +1,51 @@
+use lettre::message::Mailbox;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use crate::config::MailConfig;
+
+/// Sends account emails over SMTP. Without a configured SMTP server the
+/// messages are logged instead, which is enough for a hub on a home network
+/// where the administrator can read the logs.
+pub struct Mailer {
+    transport: Option<(AsyncSmtpTransport<Tokio1Executor>, Mailbox)>,
+}
+
+impl Mailer {
+    pub fn new(config: Option<&MailConfig>) -> anyhow::Result<Self> {
+        let transport = config
+            .map(|config| -> anyhow::Result<_> {
+                let transport = AsyncSmtpTransport::<Tokio1Executor>::from_url(
+                    &config.smtp_url,
+                )?
+                .build();
+                Ok((transport, config.from.parse()?))
+            })
+            .transpose()?;
+        Ok(Self { transport })
+    }
+
+    pub async fn send(
+        &self,
+        to: &str,
+        subject: &str,
+        body: &str,
+    ) -> anyhow::Result<()> {
+        let Some((transport, from)) = &self.transport else {
+            tracing::info!(
+                "No SMTP server configured, email to {}: {}\n{}",
+                to,
+                subject,
+                body
+            );
+            return Ok(());
+        };
+
+        let message = Message::builder()
+            .from(from.clone())
+            .to(to.parse()?)
+            .subject(subject)
+            .body(body.to_string())?;
+        transport.send(message).await?;
+        Ok(())
+    }
+}
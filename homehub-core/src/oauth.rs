@@ -0,0 +1,582 @@
+use std::collections::BTreeSet;
+
+use argon2::{
+    password_hash::SaltString, Argon2, PasswordHash, PasswordHasher,
+    PasswordVerifier,
+};
+use base64::{engine::general_purpose, Engine as _};
+use homehub_db::DatabaseConnection;
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::config;
+use crate::error::Error;
+use crate::token::ClientGrant;
+
+pub const SUPPORTED_SCOPES: &[(&str, &str)] = &[
+    ("user:read", "View your name, email address and locale"),
+    ("lights:read", "View your lights and their state"),
+    ("lights:write", "Switch, rename and reconfigure your lights"),
+    ("devices:read", "View your devices and their state"),
+    ("devices:write", "Add, control and remove your devices"),
+    ("sensors:read", "View the readings of your sensors"),
+    ("sensors:write", "Record readings for your sensors"),
+];
+
+const AUTHORIZATION_CODE_TTL_MINUTES: i64 = 5;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OAuthClientDto {
+    pub id: Uuid,
+    pub name: String,
+    pub redirect_uris: Vec<String>,
+    pub scope: String,
+    pub confidential: bool,
+}
+
+impl From<homehub_db::oauth_client::Model> for OAuthClientDto {
+    fn from(value: homehub_db::oauth_client::Model) -> Self {
+        OAuthClientDto {
+            id: value.id,
+            name: value.name,
+            redirect_uris: value.redirect_uris.0,
+            scope: value.scope,
+            confidential: value.secret_hash.is_some(),
+        }
+    }
+}
+
+pub struct RegisteredClient {
+    pub client: OAuthClientDto,
+    pub client_secret: Option<String>,
+}
+
+pub async fn register_client(
+    owner_id: Uuid,
+    name: &str,
+    redirect_uris: Vec<String>,
+    scope: &str,
+    confidential: bool,
+    db: &DatabaseConnection,
+) -> Result<RegisteredClient, Error> {
+    let valid_redirect_uris = !redirect_uris.is_empty()
+        && redirect_uris.iter().all(|uri| {
+            url::Url::parse(uri)
+                .map(|url| url.fragment().is_none())
+                .unwrap_or(false)
+        });
+    if !valid_redirect_uris {
+        return Err(Error::OAuthInvalidRedirectUriError);
+    }
+
+    let scope = join_scopes(&parse_supported_scopes(scope)?);
+
+    let client_secret = confidential.then(random_token);
+    let secret_hash = client_secret
+        .as_deref()
+        .map(|secret| {
+            let salt = SaltString::generate(&mut OsRng);
+            Argon2::default()
+                .hash_password(secret.as_bytes(), salt.as_salt())
+                .map(|hash| hash.to_string())
+                .map_err(|_| Error::CouldNotHashError)
+        })
+        .transpose()?;
+
+    let client = homehub_db::queries::oauth::create_client(
+        name,
+        secret_hash.as_deref(),
+        redirect_uris,
+        &scope,
+        owner_id,
+        db,
+    )
+    .await
+    .map_err(Error::DbError)?;
+
+    Ok(RegisteredClient {
+        client: client.into(),
+        client_secret,
+    })
+}
+
+pub async fn list_clients(
+    owner_id: Uuid,
+    db: &DatabaseConnection,
+) -> Result<Vec<OAuthClientDto>, Error> {
+    let clients =
+        homehub_db::queries::oauth::find_clients_by_owner(owner_id, db)
+            .await
+            .map_err(Error::DbError)?;
+    Ok(clients.into_iter().map(Into::into).collect())
+}
+
+pub async fn delete_client(
+    owner_id: Uuid,
+    client_id: Uuid,
+    db: &DatabaseConnection,
+) -> Result<(), Error> {
+    let client = homehub_db::queries::oauth::find_client(client_id, db)
+        .await
+        .map_err(Error::DbError)?
+        .filter(|client| client.owner_id == owner_id)
+        .ok_or(Error::OAuthClientNotFoundError)?;
+
+    homehub_db::queries::oauth::delete_client(client.id, db)
+        .await
+        .map_err(Error::DbError)
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams, ToSchema)]
+#[into_params(parameter_in = Query)]
+pub struct AuthorizationRequest {
+    pub response_type: String,
+    pub client_id: Uuid,
+    pub redirect_uri: String,
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ScopeDto {
+    pub name: String,
+    pub description: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ConsentDetails {
+    pub client_id: Uuid,
+    pub client_name: String,
+    pub redirect_uri: String,
+    pub scopes: Vec<ScopeDto>,
+    pub previously_granted: bool,
+}
+
+pub async fn get_consent_details(
+    request: &AuthorizationRequest,
+    user_id: Uuid,
+    db: &DatabaseConnection,
+) -> Result<ConsentDetails, Error> {
+    let (client, scopes) = validate_authorization_request(request, db).await?;
+
+    let previously_granted =
+        homehub_db::queries::oauth::find_consent(user_id, client.id, db)
+            .await
+            .map_err(Error::DbError)?
+            .map(|consent| scopes.is_subset(&parse_scopes(&consent.scope)))
+            .unwrap_or(false);
+
+    Ok(ConsentDetails {
+        client_id: client.id,
+        client_name: client.name,
+        redirect_uri: request.redirect_uri.clone(),
+        scopes: scopes
+            .iter()
+            .map(|scope| ScopeDto {
+                name: scope.clone(),
+                description: describe_scope(scope).to_string(),
+            })
+            .collect(),
+        previously_granted,
+    })
+}
+
+/// Records the user's consent and returns the client redirect URL carrying
+/// a fresh authorization code.
+pub async fn approve_authorization(
+    request: &AuthorizationRequest,
+    user_id: Uuid,
+    db: &DatabaseConnection,
+) -> Result<String, Error> {
+    let (client, scopes) = validate_authorization_request(request, db).await?;
+
+    let mut granted_scopes =
+        homehub_db::queries::oauth::find_consent(user_id, client.id, db)
+            .await
+            .map_err(Error::DbError)?
+            .map(|consent| parse_scopes(&consent.scope))
+            .unwrap_or_default();
+    granted_scopes.extend(scopes.iter().cloned());
+    homehub_db::queries::oauth::save_consent(
+        user_id,
+        client.id,
+        &join_scopes(&granted_scopes),
+        db,
+    )
+    .await
+    .map_err(Error::DbError)?;
+
+    let code = random_token();
+    homehub_db::queries::oauth::create_authorization_code(
+        homehub_db::oauth_authorization_code::Model {
+            code_hash: hash_token(&code),
+            client_id: client.id,
+            user_id,
+            redirect_uri: request.redirect_uri.clone(),
+            scope: join_scopes(&scopes),
+            code_challenge: request.code_challenge.clone().unwrap_or_default(),
+            expires_at: (chrono::Utc::now()
+                + chrono::Duration::minutes(AUTHORIZATION_CODE_TTL_MINUTES))
+            .naive_utc(),
+        },
+        db,
+    )
+    .await
+    .map_err(Error::DbError)?;
+
+    Ok(redirect_url(request, &[("code", &code)]))
+}
+
+pub async fn deny_authorization(
+    request: &AuthorizationRequest,
+    db: &DatabaseConnection,
+) -> Result<String, Error> {
+    find_client_for_redirect(request, db).await?;
+    Ok(redirect_url(request, &[("error", "access_denied")]))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TokenRequest {
+    pub grant_type: String,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<String>,
+    pub refresh_token: Option<String>,
+    pub scope: Option<String>,
+    pub client_id: Option<Uuid>,
+    pub client_secret: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OAuthTokens {
+    pub access_token: String,
+    pub token_type: &'static str,
+    pub expires_in: i64,
+    pub refresh_token: String,
+    pub scope: String,
+}
+
+pub async fn exchange_token(
+    request: &TokenRequest,
+    db: &DatabaseConnection,
+    config: &config::Config,
+) -> Result<OAuthTokens, Error> {
+    match request.grant_type.as_str() {
+        "authorization_code" => {
+            exchange_authorization_code(request, db, config).await
+        }
+        "refresh_token" => exchange_refresh_token(request, db, config).await,
+        _ => Err(Error::OAuthUnsupportedGrantTypeError),
+    }
+}
+
+async fn exchange_authorization_code(
+    request: &TokenRequest,
+    db: &DatabaseConnection,
+    config: &config::Config,
+) -> Result<OAuthTokens, Error> {
+    let (Some(code), Some(code_verifier)) =
+        (&request.code, &request.code_verifier)
+    else {
+        return Err(Error::OAuthInvalidGrantError);
+    };
+
+    let code = homehub_db::queries::oauth::take_authorization_code(
+        &hash_token(code),
+        db,
+    )
+    .await
+    .map_err(Error::DbError)?
+    .filter(|code| code.expires_at > chrono::Utc::now().naive_utc())
+    .ok_or(Error::OAuthInvalidGrantError)?;
+
+    let client = authenticate_client(code.client_id, request, db).await?;
+
+    if request.redirect_uri.as_deref() != Some(code.redirect_uri.as_str())
+        || pkce_challenge(code_verifier) != code.code_challenge
+    {
+        return Err(Error::OAuthInvalidGrantError);
+    }
+
+    generate_client_tokens(code.user_id, client.id, &code.scope, config)
+}
+
+async fn exchange_refresh_token(
+    request: &TokenRequest,
+    db: &DatabaseConnection,
+    config: &config::Config,
+) -> Result<OAuthTokens, Error> {
+    let refresh_token = request
+        .refresh_token
+        .as_deref()
+        .ok_or(Error::OAuthInvalidGrantError)?;
+
+    let token_details = crate::token::verify_jwt_token(
+        &config.refresh_token_keys,
+        refresh_token,
+    )
+    .map_err(|_| Error::OAuthInvalidGrantError)?;
+    let grant = token_details.grant.ok_or(Error::OAuthInvalidGrantError)?;
+
+    let client = authenticate_client(grant.client_id, request, db).await?;
+
+    homehub_db::queries::app_user::find_by_id(token_details.user_id, db)
+        .await
+        .map_err(Error::DbError)?
+        .filter(|user| crate::user::check_can_sign_in(user).is_ok())
+        .ok_or(Error::OAuthInvalidGrantError)?;
+
+    let consented_scopes = homehub_db::queries::oauth::find_consent(
+        token_details.user_id,
+        client.id,
+        db,
+    )
+    .await
+    .map_err(Error::DbError)?
+    .map(|consent| parse_scopes(&consent.scope))
+    .ok_or(Error::OAuthInvalidGrantError)?;
+
+    let scopes = match &request.scope {
+        Some(scope) => parse_scopes(scope),
+        None => parse_scopes(&grant.scope),
+    };
+    if !scopes.is_subset(&parse_scopes(&grant.scope))
+        || !scopes.is_subset(&consented_scopes)
+    {
+        return Err(Error::OAuthInvalidScopeError(join_scopes(&scopes)));
+    }
+
+    generate_client_tokens(
+        token_details.user_id,
+        client.id,
+        &join_scopes(&scopes),
+        config,
+    )
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ConsentDto {
+    pub client_id: Uuid,
+    pub client_name: Option<String>,
+    pub scope: String,
+    pub created_at: Option<chrono::NaiveDateTime>,
+}
+
+pub async fn list_consents(
+    user_id: Uuid,
+    db: &DatabaseConnection,
+) -> Result<Vec<ConsentDto>, Error> {
+    let consents =
+        homehub_db::queries::oauth::find_consents_by_user(user_id, db)
+            .await
+            .map_err(Error::DbError)?;
+
+    Ok(consents
+        .into_iter()
+        .map(|(consent, client)| ConsentDto {
+            client_id: consent.client_id,
+            client_name: client.map(|client| client.name),
+            scope: consent.scope,
+            created_at: consent.created_at,
+        })
+        .collect())
+}
+
+pub async fn revoke_consent(
+    user_id: Uuid,
+    client_id: Uuid,
+    db: &DatabaseConnection,
+) -> Result<(), Error> {
+    let deleted =
+        homehub_db::queries::oauth::delete_consent(user_id, client_id, db)
+            .await
+            .map_err(Error::DbError)?;
+
+    match deleted {
+        true => Ok(()),
+        false => Err(Error::OAuthClientNotFoundError),
+    }
+}
+
+/// Returns true if `scope` is granted by a token. First-party tokens carry
+/// no grant and have full access.
+pub fn grant_allows(grant: Option<&ClientGrant>, scope: &str) -> bool {
+    match grant {
+        Some(grant) => parse_scopes(&grant.scope).contains(scope),
+        None => true,
+    }
+}
+
+async fn validate_authorization_request(
+    request: &AuthorizationRequest,
+    db: &DatabaseConnection,
+) -> Result<(homehub_db::oauth_client::Model, BTreeSet<String>), Error> {
+    let client = find_client_for_redirect(request, db).await?;
+
+    if request.response_type != "code" {
+        return Err(Error::OAuthUnsupportedResponseTypeError);
+    }
+
+    let valid_challenge = request.code_challenge_method.as_deref()
+        == Some("S256")
+        && request
+            .code_challenge
+            .as_ref()
+            .is_some_and(|challenge| challenge.len() == 43);
+    if !valid_challenge {
+        return Err(Error::OAuthInvalidCodeChallengeError);
+    }
+
+    let scopes = match &request.scope {
+        Some(scope) => parse_supported_scopes(scope)?,
+        None => parse_scopes(&client.scope),
+    };
+    if scopes.is_empty() || !scopes.is_subset(&parse_scopes(&client.scope)) {
+        return Err(Error::OAuthInvalidScopeError(join_scopes(&scopes)));
+    }
+
+    Ok((client, scopes))
+}
+
+async fn find_client_for_redirect(
+    request: &AuthorizationRequest,
+    db: &DatabaseConnection,
+) -> Result<homehub_db::oauth_client::Model, Error> {
+    let client = homehub_db::queries::oauth::find_client(request.client_id, db)
+        .await
+        .map_err(Error::DbError)?
+        .ok_or(Error::OAuthUnknownClientError)?;
+
+    if !client.redirect_uris.0.contains(&request.redirect_uri) {
+        return Err(Error::OAuthInvalidRedirectUriError);
+    }
+    Ok(client)
+}
+
+async fn authenticate_client(
+    client_id: Uuid,
+    request: &TokenRequest,
+    db: &DatabaseConnection,
+) -> Result<homehub_db::oauth_client::Model, Error> {
+    if request.client_id.is_some_and(|id| id != client_id) {
+        return Err(Error::OAuthInvalidClientError);
+    }
+
+    let client = homehub_db::queries::oauth::find_client(client_id, db)
+        .await
+        .map_err(Error::DbError)?
+        .ok_or(Error::OAuthInvalidClientError)?;
+
+    if let Some(secret_hash) = &client.secret_hash {
+        let secret = request
+            .client_secret
+            .as_deref()
+            .ok_or(Error::OAuthInvalidClientError)?;
+        let hash = PasswordHash::new(secret_hash)
+            .map_err(|_| Error::CouldNotHashError)?;
+        Argon2::default()
+            .verify_password(secret.as_bytes(), &hash)
+            .map_err(|_| Error::OAuthInvalidClientError)?;
+    }
+    Ok(client)
+}
+
+fn generate_client_tokens(
+    user_id: Uuid,
+    client_id: Uuid,
+    scope: &str,
+    config: &config::Config,
+) -> Result<OAuthTokens, Error> {
+    let grant = ClientGrant {
+        client_id,
+        scope: scope.to_string(),
+    };
+    let access_token = crate::token::generate_granted_jwt_token(
+        user_id,
+        Some(grant.clone()),
+        config.access_token_max_age,
+        &config.access_token_keys,
+    )
+    .map_err(|_| Error::TokenGenerationError)?;
+    let refresh_token = crate::token::generate_granted_jwt_token(
+        user_id,
+        Some(grant),
+        config.refresh_token_max_age,
+        &config.refresh_token_keys,
+    )
+    .map_err(|_| Error::TokenGenerationError)?;
+
+    Ok(OAuthTokens {
+        access_token: access_token.token.unwrap_or_default(),
+        token_type: "Bearer",
+        expires_in: config.access_token_max_age * 60,
+        refresh_token: refresh_token.token.unwrap_or_default(),
+        scope: scope.to_string(),
+    })
+}
+
+fn redirect_url(
+    request: &AuthorizationRequest,
+    params: &[(&str, &str)],
+) -> String {
+    let mut url = url::Url::parse(&request.redirect_uri)
+        .expect("registered redirect URIs are valid URLs");
+    {
+        let mut query = url.query_pairs_mut();
+        for (key, value) in params {
+            query.append_pair(key, value);
+        }
+        if let Some(state) = &request.state {
+            query.append_pair("state", state);
+        }
+    }
+    url.to_string()
+}
+
+fn parse_scopes(scope: &str) -> BTreeSet<String> {
+    scope.split_whitespace().map(str::to_string).collect()
+}
+
+fn parse_supported_scopes(scope: &str) -> Result<BTreeSet<String>, Error> {
+    let scopes = parse_scopes(scope);
+    match scopes
+        .iter()
+        .find(|scope| !SUPPORTED_SCOPES.iter().any(|(name, _)| name == scope))
+    {
+        Some(unsupported) => {
+            Err(Error::OAuthInvalidScopeError(unsupported.clone()))
+        }
+        None => Ok(scopes),
+    }
+}
+
+fn join_scopes(scopes: &BTreeSet<String>) -> String {
+    scopes.iter().cloned().collect::<Vec<_>>().join(" ")
+}
+
+fn describe_scope(scope: &str) -> &'static str {
+    SUPPORTED_SCOPES
+        .iter()
+        .find(|(name, _)| *name == scope)
+        .map(|(_, description)| *description)
+        .unwrap_or_default()
+}
+
+pub(crate) fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub(crate) fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+fn pkce_challenge(code_verifier: &str) -> String {
+    general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier))
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use argon2::password_hash::SaltString;
+use homehub_db::DatabaseConnection;
+use openidconnect::core::{
+    CoreAuthenticationFlow, CoreClient, CoreIdTokenClaims, CoreProviderMetadata,
+};
+use openidconnect::reqwest::async_http_client;
+use openidconnect::{
+    AuthorizationCode, ClientId, ClientSecret, CsrfToken, IssuerUrl, Nonce,
+    PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, Scope, TokenResponse,
+};
+use rand_core::OsRng;
+
+use crate::config;
+use crate::error::Error;
+use crate::password::hash_password;
+use crate::user::{generate_tokens, registration_policy, Tokens};
+
+/// How long an authorization request may stay pending before the callback
+/// is rejected.
+const PENDING_LOGIN_TTL: Duration = Duration::from_secs(10 * 60);
+
+struct PendingLogin {
+    pkce_verifier: PkceCodeVerifier,
+    nonce: Nonce,
+    created_at: Instant,
+}
+
+pub struct OidcProvider {
+    client: CoreClient,
+    pending: Mutex<HashMap<String, PendingLogin>>,
+}
+
+impl OidcProvider {
+    pub async fn discover(config: &config::OidcConfig) -> anyhow::Result<Self> {
+        let provider_metadata = CoreProviderMetadata::discover_async(
+            IssuerUrl::new(config.issuer_url.clone())?,
+            async_http_client,
+        )
+        .await?;
+
+        let client = CoreClient::from_provider_metadata(
+            provider_metadata,
+            ClientId::new(config.client_id.clone()),
+            config.client_secret.clone().map(ClientSecret::new),
+        )
+        .set_redirect_uri(RedirectUrl::new(config.redirect_url.clone())?);
+
+        Ok(Self {
+            client,
+            pending: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn begin_login(&self) -> String {
+        let (pkce_challenge, pkce_verifier) =
+            PkceCodeChallenge::new_random_sha256();
+        let (auth_url, csrf_token, nonce) = self
+            .client
+            .authorize_url(
+                CoreAuthenticationFlow::AuthorizationCode,
+                CsrfToken::new_random,
+                Nonce::new_random,
+            )
+            .add_scope(Scope::new("email".to_string()))
+            .add_scope(Scope::new("profile".to_string()))
+            .set_pkce_challenge(pkce_challenge)
+            .url();
+
+        let mut pending = self.pending.lock().unwrap();
+        pending
+            .retain(|_, login| login.created_at.elapsed() < PENDING_LOGIN_TTL);
+        pending.insert(
+            csrf_token.secret().clone(),
+            PendingLogin {
+                pkce_verifier,
+                nonce,
+                created_at: Instant::now(),
+            },
+        );
+
+        auth_url.to_string()
+    }
+
+    pub async fn complete_login(
+        &self,
+        db: &DatabaseConnection,
+        code: &str,
+        state: &str,
+        config: &config::Config,
+    ) -> Result<Tokens, Error> {
+        let pending_login = self
+            .pending
+            .lock()
+            .unwrap()
+            .remove(state)
+            .filter(|login| login.created_at.elapsed() < PENDING_LOGIN_TTL)
+            .ok_or(Error::OidcUnknownStateError)?;
+
+        let token_response = self
+            .client
+            .exchange_code(AuthorizationCode::new(code.to_string()))
+            .set_pkce_verifier(pending_login.pkce_verifier)
+            .request_async(async_http_client)
+            .await
+            .map_err(|e| Error::OidcCodeExchangeError(e.to_string()))?;
+
+        let id_token = token_response.id_token().ok_or_else(|| {
+            Error::OidcInvalidIdTokenError(
+                "Provider did not return an ID token".to_string(),
+            )
+        })?;
+        let claims = id_token
+            .claims(&self.client.id_token_verifier(), &pending_login.nonce)
+            .map_err(|e| Error::OidcInvalidIdTokenError(e.to_string()))?;
+
+        let user_id = find_or_create_user(claims, db, config).await?;
+        let disabled = homehub_db::queries::app_user::find_by_id(user_id, db)
+            .await
+            .map_err(Error::DbError)?
+            .is_none_or(|user| user.disabled_at.is_some());
+        if disabled {
+            return Err(Error::AccountDisabledError);
+        }
+        generate_tokens(user_id, config)
+    }
+}
+
+/// Resolves the `app_user` for an identity provider subject. Unknown
+/// subjects are linked to an existing account only when the provider vouches
+/// for the email address, otherwise a new account is created if the
+/// registration mode allows it.
+async fn find_or_create_user(
+    claims: &CoreIdTokenClaims,
+    db: &DatabaseConnection,
+    config: &config::Config,
+) -> Result<uuid::Uuid, Error> {
+    let issuer = claims.issuer().as_str();
+    let subject = claims.subject().as_str();
+
+    if let Some(identity) =
+        homehub_db::queries::user_identity::find_by_subject(issuer, subject, db)
+            .await
+            .map_err(Error::DbError)?
+    {
+        return Ok(identity.user_id);
+    }
+
+    let email =
+        claims.email().map(|email| email.as_str()).ok_or_else(|| {
+            Error::OidcInvalidIdTokenError(
+                "ID token has no email claim".to_string(),
+            )
+        })?;
+
+    let existing_user =
+        homehub_db::queries::app_user::find_user_by_email(email, db)
+            .await
+            .map_err(Error::DbError)?;
+
+    match existing_user {
+        Some(user) if claims.email_verified() == Some(true) => {
+            homehub_db::queries::user_identity::create_identity(
+                user.id, issuer, subject, db,
+            )
+            .await
+            .map_err(Error::DbError)?;
+            Ok(user.id)
+        }
+        Some(_) => Err(Error::OidcUnverifiedEmailError(email.to_string())),
+        None => {
+            // Nobody signing in through a provider can present an invite,
+            // so only the first user gets past a mode that asks for one.
+            let (first_user_admin, invite_required) =
+                registration_policy(config)?;
+            if invite_required && !first_user_admin {
+                return Err(Error::InvalidInviteError);
+            }
+            let name = claims
+                .name()
+                .and_then(|name| name.get(None))
+                .map(|name| name.as_str())
+                .or(claims
+                    .preferred_username()
+                    .map(|username| username.as_str()))
+                .unwrap_or(email);
+
+            let user =
+                homehub_db::queries::user_identity::create_user_with_identity(
+                    name,
+                    email,
+                    &unusable_password_hash(config)?,
+                    first_user_admin,
+                    invite_required,
+                    issuer,
+                    subject,
+                    db,
+                )
+                .await
+                .map_err(Error::DbError)?
+                .ok_or(Error::InvalidInviteError)?;
+            Ok(user.id)
+        }
+    }
+}
+
+/// Accounts created through an identity provider have no password, so they
+/// get the hash of a random secret that nobody knows.
+fn unusable_password_hash(config: &config::Config) -> Result<String, Error> {
+    let secret = SaltString::generate(&mut OsRng);
+    hash_password(secret.as_str(), &config.argon2)
+        .map_err(|_| Error::CouldNotHashError)
+}
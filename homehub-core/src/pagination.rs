@@ -0,0 +1,15 @@
+pub use homehub_db::pagination::{
+    Page, PageRequest, SortKey, SortOrder, DEFAULT_PAGE_LIMIT, MAX_PAGE_LIMIT,
+};
+
+use crate::error::Error;
+
+/// Reads the pagination parameters of a list request.
+pub fn page_request<S: SortKey>(
+    sort: S,
+    order: SortOrder,
+    after: Option<&str>,
+    limit: Option<u64>,
+) -> Result<PageRequest<S>, Error> {
+    PageRequest::new(sort, order, after, limit).ok_or(Error::InvalidCursorError)
+}
@@ -0,0 +1,195 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use argon2::{
+    password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash,
+    PasswordHasher, Version,
+};
+use rand_core::OsRng;
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use utoipa::ToSchema;
+
+use crate::config::PasswordPolicyConfig;
+
+/// SHA-1 hashes of the most common passwords, always checked even when no
+/// breached password list is configured.
+const BUNDLED_BREACHED_PASSWORDS: &str =
+    include_str!("../data/breached-passwords.txt");
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum PasswordViolation {
+    TooShort { min_length: usize },
+    TooWeak { min_entropy_bits: f64 },
+    Breached,
+}
+
+pub fn hash_password(
+    password: &str,
+    params: &Params,
+) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params.clone())
+        .hash_password(password.as_bytes(), salt.as_salt())
+        .map(|hash| hash.to_string())
+}
+
+/// Whether a stored hash was produced with a different algorithm or with
+/// cheaper parameters than are now configured, and so should be replaced
+/// the next time the password is known.
+pub fn needs_rehash(hash: &PasswordHash, params: &Params) -> bool {
+    if hash.algorithm != Algorithm::Argon2id.ident()
+        || hash.version != Some(Version::V0x13.into())
+    {
+        return true;
+    }
+    match Params::try_from(hash) {
+        Ok(hash_params) => {
+            hash_params.m_cost() < params.m_cost()
+                || hash_params.t_cost() < params.t_cost()
+                || hash_params.p_cost() < params.p_cost()
+        }
+        Err(_) => true,
+    }
+}
+
+/// Checks a password against the policy, returning every rule it breaks so
+/// that clients can show them all at once.
+pub fn validate_password(
+    password: &str,
+    policy: &PasswordPolicyConfig,
+) -> Vec<PasswordViolation> {
+    let mut violations = Vec::new();
+
+    if password.chars().count() < policy.min_length {
+        violations.push(PasswordViolation::TooShort {
+            min_length: policy.min_length,
+        });
+    }
+    if estimate_entropy_bits(password) < policy.min_entropy_bits {
+        violations.push(PasswordViolation::TooWeak {
+            min_entropy_bits: policy.min_entropy_bits,
+        });
+    }
+    if is_breached(password, policy.breached_passwords_path.as_deref()) {
+        violations.push(PasswordViolation::Breached);
+    }
+
+    violations
+}
+
+/// A rough estimate of how many guesses an attacker who knows the character
+/// classes in use would need. Characters that repeat or continue a run of
+/// the previous character (`aaa`, `abc`, `321`) only count for one bit.
+fn estimate_entropy_bits(password: &str) -> f64 {
+    let pool_size = [
+        (password.chars().any(|c| c.is_ascii_lowercase()), 26),
+        (password.chars().any(|c| c.is_ascii_uppercase()), 26),
+        (password.chars().any(|c| c.is_ascii_digit()), 10),
+        (
+            password
+                .chars()
+                .any(|c| c.is_ascii_punctuation() || c == ' '),
+            33,
+        ),
+        (!password.is_ascii(), 100),
+    ]
+    .iter()
+    .filter(|(present, _)| *present)
+    .map(|(_, size)| size)
+    .sum::<u32>();
+    if pool_size == 0 {
+        return 0.0;
+    }
+
+    let bits_per_char = (pool_size as f64).log2();
+    let mut previous: Option<char> = None;
+    password
+        .chars()
+        .map(|c| {
+            let predictable = previous.is_some_and(|previous| {
+                (c as i64 - previous as i64).abs() <= 1
+            });
+            previous = Some(c);
+            match predictable {
+                true => 1.0,
+                false => bits_per_char,
+            }
+        })
+        .sum()
+}
+
+fn is_breached(password: &str, breached_passwords_path: Option<&Path>) -> bool {
+    let hash = format!("{:X}", Sha1::digest(password.as_bytes()));
+
+    if bundled_breached_passwords().contains(hash.as_str()) {
+        return true;
+    }
+
+    match breached_passwords_path {
+        Some(path) => match sorted_file_contains(path, &hash) {
+            Ok(found) => found,
+            Err(e) => {
+                tracing::error!(
+                    "Could not read breached password list {}: {}",
+                    path.display(),
+                    e
+                );
+                false
+            }
+        },
+        None => false,
+    }
+}
+
+fn bundled_breached_passwords() -> &'static HashSet<&'static str> {
+    static HASHES: OnceLock<HashSet<&'static str>> = OnceLock::new();
+    HASHES.get_or_init(|| BUNDLED_BREACHED_PASSWORDS.lines().collect())
+}
+
+/// Binary searches a file of upper case SHA-1 hashes sorted by hash, one per
+/// line and optionally followed by `:count`. This is the format of the Have I
+/// Been Pwned downloads, which are far too large to load into memory.
+fn sorted_file_contains(path: &Path, hash: &str) -> std::io::Result<bool> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut low = 0;
+    let mut high = reader.get_ref().metadata()?.len();
+    let mut line = String::new();
+
+    while low < high {
+        let middle = low + (high - low) / 2;
+
+        // Find the first line that starts at or after `middle`.
+        let line_start = match middle {
+            0 => {
+                reader.seek(SeekFrom::Start(0))?;
+                0
+            }
+            _ => {
+                reader.seek(SeekFrom::Start(middle - 1))?;
+                let mut skipped = Vec::new();
+                middle - 1 + reader.read_until(b'\n', &mut skipped)? as u64
+            }
+        };
+
+        line.clear();
+        let line_length = reader.read_line(&mut line)? as u64;
+        if line_length == 0 {
+            high = middle;
+            continue;
+        }
+
+        let line_hash = line.trim_end().split(':').next().unwrap_or_default();
+        match line_hash.cmp(hash) {
+            std::cmp::Ordering::Equal => return Ok(true),
+            std::cmp::Ordering::Less => low = line_start + line_length,
+            std::cmp::Ordering::Greater => high = middle,
+        }
+    }
+
+    Ok(false)
+}
@@ -0,0 +1,404 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::device::{Capability, DeviceKind, DeviceState};
+use crate::error::Error;
+use crate::light::LightState;
+
+const BUNDLED_PROFILES: &str = include_str!("../data/device-profiles.json");
+
+static PROFILES: OnceLock<HashMap<String, DeviceProfile>> = OnceLock::new();
+
+/// The colours a light can show, as a triangle in the CIE 1931 xy
+/// chromaticity diagram.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Gamut {
+    pub red: [f64; 2],
+    pub green: [f64; 2],
+    pub blue: [f64; 2],
+}
+
+/// What a particular make and model of device can do.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DeviceProfile {
+    pub id: String,
+    pub manufacturer: String,
+    pub model: String,
+    pub kind: DeviceKind,
+    /// Linking a device to the profile gives it these capabilities.
+    pub capabilities: Vec<Capability>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gamut: Option<Gamut>,
+    /// The lowest brightness in percent the device can show while on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_brightness: Option<u8>,
+    /// The power drawn at full output, in watts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wattage: Option<f64>,
+}
+
+fn parse(profiles: &str) -> anyhow::Result<Vec<DeviceProfile>> {
+    let profiles: Vec<DeviceProfile> = serde_json::from_str(profiles)?;
+    for profile in &profiles {
+        if let Some(gamut) = &profile.gamut {
+            gamut.validate().map_err(|e| {
+                anyhow::anyhow!(
+                    "Profile {} has an invalid gamut: {}",
+                    profile.id,
+                    e
+                )
+            })?;
+        }
+    }
+    Ok(profiles)
+}
+
+/// Loads the bundled catalogue, extended by the profiles in `path`, which
+/// replace bundled profiles with the same id. Must be called before the
+/// catalogue is first used, or only the bundled profiles are known.
+pub fn load_profiles(path: Option<&Path>) -> anyhow::Result<usize> {
+    let mut profiles = parse(BUNDLED_PROFILES)?;
+    if let Some(path) = path {
+        profiles.extend(parse(&std::fs::read_to_string(path)?)?);
+    }
+    let profiles: HashMap<_, _> = profiles
+        .into_iter()
+        .map(|profile| (profile.id.clone(), profile))
+        .collect();
+    let count = profiles.len();
+    PROFILES
+        .set(profiles)
+        .map_err(|_| anyhow::anyhow!("Device profiles are already loaded"))?;
+    Ok(count)
+}
+
+fn profiles() -> &'static HashMap<String, DeviceProfile> {
+    PROFILES.get_or_init(|| {
+        parse(BUNDLED_PROFILES)
+            .expect("bundled device profiles are valid")
+            .into_iter()
+            .map(|profile| (profile.id.clone(), profile))
+            .collect()
+    })
+}
+
+pub fn get_profile(id: &str) -> Option<&'static DeviceProfile> {
+    profiles().get(id)
+}
+
+/// Every profile in the catalogue, by manufacturer and model.
+pub fn get_profiles() -> Vec<&'static DeviceProfile> {
+    let mut profiles: Vec<_> = profiles().values().collect();
+    profiles.sort_by(|a, b| {
+        (&a.manufacturer, &a.model, &a.id).cmp(&(
+            &b.manufacturer,
+            &b.model,
+            &b.id,
+        ))
+    });
+    profiles
+}
+
+/// Looks up a profile to link a device of `kind` to.
+pub(crate) fn find_profile(
+    id: &str,
+    kind: DeviceKind,
+) -> Result<&'static DeviceProfile, Error> {
+    let profile = get_profile(id).ok_or(Error::DeviceProfileNotFoundError)?;
+    if profile.kind != kind {
+        return Err(Error::DeviceProfileKindError);
+    }
+    Ok(profile)
+}
+
+impl DeviceProfile {
+    /// Rejects a colour for lights that cannot show one, and brings any
+    /// other colour into the light's gamut.
+    pub(crate) fn fit_light_state(
+        &self,
+        state: LightState,
+    ) -> Result<LightState, Error> {
+        if state.colour.is_some()
+            && !self.capabilities.contains(&Capability::Colour)
+        {
+            return Err(Error::UnsupportedCapabilityError(
+                Capability::Colour.name().to_owned(),
+            ));
+        }
+        Ok(self.adapt_light_state(state))
+    }
+
+    /// Like [`DeviceProfile::fit_light_state`], but drops a colour the light
+    /// cannot show instead, for when one state is sent to many lights.
+    pub(crate) fn adapt_light_state(
+        &self,
+        mut state: LightState,
+    ) -> LightState {
+        if !self.capabilities.contains(&Capability::Colour) {
+            state.colour = None;
+        }
+        state.colour = state.colour.map(|colour| self.clamp_colour(colour));
+        state
+    }
+
+    /// Brings the colour into the device's gamut and the brightness up to
+    /// its minimum. A brightness of zero is left alone.
+    pub(crate) fn adapt_device_state(
+        &self,
+        mut state: DeviceState,
+    ) -> DeviceState {
+        state.colour = state.colour.map(|colour| self.clamp_colour(colour));
+        if let (Some(brightness), Some(min)) =
+            (state.brightness, self.min_brightness)
+        {
+            if brightness > 0 {
+                state.brightness = Some(brightness.max(min));
+            }
+        }
+        state
+    }
+
+    fn clamp_colour(&self, colour: [u8; 3]) -> [u8; 3] {
+        match &self.gamut {
+            Some(gamut) => gamut.clamp(colour),
+            None => colour,
+        }
+    }
+}
+
+impl Gamut {
+    /// Makes sure the gamut is a proper triangle of chromaticities, which
+    /// [`Gamut::clamp`] relies on to never divide by zero.
+    fn validate(&self) -> Result<(), &'static str> {
+        for [x, y] in [self.red, self.green, self.blue] {
+            if !(x.is_finite() && y.is_finite()) {
+                return Err("coordinates must be numbers");
+            }
+            if x < 0.0 || y <= 0.0 || x + y > 1.0 {
+                return Err("coordinates must lie in the chromaticity diagram");
+            }
+        }
+        if cross(self.red, self.green, self.blue).abs() < f64::EPSILON {
+            return Err("the primaries must not lie on one line");
+        }
+        Ok(())
+    }
+
+    /// Replaces an sRGB colour outside the gamut by the closest one inside
+    /// it, keeping its luminance where the light allows.
+    fn clamp(&self, colour: [u8; 3]) -> [u8; 3] {
+        let [r, g, b] = colour.map(srgb_to_linear);
+        let x = 0.4124 * r + 0.3576 * g + 0.1805 * b;
+        let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        let z = 0.0193 * r + 0.1192 * g + 0.9505 * b;
+        let sum = x + y + z;
+        if sum <= 0.0 {
+            return colour;
+        }
+        let point = [x / sum, y / sum];
+        if self.contains(point) {
+            return colour;
+        }
+
+        let [cx, cy] = [
+            (self.red, self.green),
+            (self.green, self.blue),
+            (self.blue, self.red),
+        ]
+        .into_iter()
+        .map(|(from, to)| closest_on_segment(point, from, to))
+        .min_by(|a, b| distance(point, *a).total_cmp(&distance(point, *b)))
+        .expect("a triangle has edges");
+        let x = cx / cy * y;
+        let z = (1.0 - cx - cy) / cy * y;
+        let rgb = [
+            3.2406 * x - 1.5372 * y - 0.4986 * z,
+            -0.9689 * x + 1.8758 * y + 0.0415 * z,
+            0.0557 * x - 0.2040 * y + 1.0570 * z,
+        ]
+        .map(|channel| channel.max(0.0));
+        let max = rgb.iter().copied().fold(1.0, f64::max);
+        rgb.map(|channel| linear_to_srgb(channel / max))
+    }
+
+    fn contains(&self, point: [f64; 2]) -> bool {
+        let sides = [
+            cross(self.red, self.green, point),
+            cross(self.green, self.blue, point),
+            cross(self.blue, self.red, point),
+        ];
+        sides.iter().all(|side| *side >= 0.0)
+            || sides.iter().all(|side| *side <= 0.0)
+    }
+}
+
+fn srgb_to_linear(channel: u8) -> f64 {
+    let channel = f64::from(channel) / 255.0;
+    if channel <= 0.04045 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(channel: f64) -> u8 {
+    let channel = if channel <= 0.0031308 {
+        12.92 * channel
+    } else {
+        1.055 * channel.powf(1.0 / 2.4) - 0.055
+    };
+    (channel.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Which side of the line through `a` and `b` the point is on.
+fn cross(a: [f64; 2], b: [f64; 2], point: [f64; 2]) -> f64 {
+    (b[0] - a[0]) * (point[1] - a[1]) - (b[1] - a[1]) * (point[0] - a[0])
+}
+
+fn closest_on_segment(point: [f64; 2], a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    let ab = [b[0] - a[0], b[1] - a[1]];
+    let t = ((point[0] - a[0]) * ab[0] + (point[1] - a[1]) * ab[1])
+        / (ab[0] * ab[0] + ab[1] * ab[1]);
+    let t = t.clamp(0.0, 1.0);
+    [a[0] + t * ab[0], a[1] + t * ab[1]]
+}
+
+fn distance(a: [f64; 2], b: [f64; 2]) -> f64 {
+    (a[0] - b[0]).hypot(a[1] - b[1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The primaries of sRGB itself, so every sRGB colour is inside.
+    const SRGB: Gamut = Gamut {
+        red: [0.64, 0.33],
+        green: [0.30, 0.60],
+        blue: [0.15, 0.06],
+    };
+
+    /// A small triangle around white.
+    const NARROW: Gamut = Gamut {
+        red: [0.40, 0.35],
+        green: [0.30, 0.40],
+        blue: [0.25, 0.25],
+    };
+
+    fn chromaticity(colour: [u8; 3]) -> [f64; 2] {
+        let [r, g, b] = colour.map(srgb_to_linear);
+        let x = 0.4124 * r + 0.3576 * g + 0.1805 * b;
+        let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        let z = 0.0193 * r + 0.1192 * g + 0.9505 * b;
+        [x / (x + y + z), y / (x + y + z)]
+    }
+
+    fn distance_to(gamut: &Gamut, point: [f64; 2]) -> f64 {
+        if gamut.contains(point) {
+            return 0.0;
+        }
+        [
+            (gamut.red, gamut.green),
+            (gamut.green, gamut.blue),
+            (gamut.blue, gamut.red),
+        ]
+        .into_iter()
+        .map(|(from, to)| distance(point, closest_on_segment(point, from, to)))
+        .fold(f64::INFINITY, f64::min)
+    }
+
+    fn light_profile(capabilities: Vec<Capability>) -> DeviceProfile {
+        DeviceProfile {
+            id: "test".to_string(),
+            manufacturer: "Test".to_string(),
+            model: "Bulb".to_string(),
+            kind: DeviceKind::Light,
+            capabilities,
+            gamut: Some(NARROW),
+            min_brightness: None,
+            wattage: None,
+        }
+    }
+
+    #[test]
+    fn keeps_colours_inside_the_gamut() {
+        for colour in [[200, 100, 50], [255, 0, 0], [10, 20, 250]] {
+            assert_eq!(SRGB.clamp(colour), colour);
+        }
+    }
+
+    #[test]
+    fn moves_colours_outside_the_gamut_onto_its_edge() {
+        for colour in [[255, 0, 0], [0, 255, 0], [0, 0, 255]] {
+            let clamped = NARROW.clamp(colour);
+
+            assert_ne!(clamped, colour);
+            assert!(distance_to(&NARROW, chromaticity(clamped)) < 0.01);
+        }
+    }
+
+    #[test]
+    fn leaves_black_alone() {
+        assert_eq!(NARROW.clamp([0, 0, 0]), [0, 0, 0]);
+    }
+
+    #[test]
+    fn fits_colours_of_colour_lights() {
+        let profile =
+            light_profile(vec![Capability::OnOff, Capability::Colour]);
+        let state = LightState {
+            on: true,
+            colour: Some([255, 0, 0]),
+        };
+
+        let fitted = profile.fit_light_state(state).unwrap();
+
+        assert!(fitted.on);
+        assert_eq!(fitted.colour, Some(NARROW.clamp([255, 0, 0])));
+    }
+
+    #[test]
+    fn rejects_colours_for_lights_without_colour() {
+        let profile = light_profile(vec![Capability::OnOff]);
+        let state = LightState {
+            on: true,
+            colour: Some([255, 0, 0]),
+        };
+
+        assert!(matches!(
+            profile.fit_light_state(state),
+            Err(Error::UnsupportedCapabilityError(_))
+        ));
+    }
+
+    #[test]
+    fn accepts_the_bundled_gamuts() {
+        assert!(parse(BUNDLED_PROFILES).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_gamuts() {
+        let zero_y = Gamut {
+            blue: [0.15, 0.0],
+            ..SRGB
+        };
+        let on_one_line = Gamut {
+            red: [0.2, 0.2],
+            green: [0.3, 0.3],
+            blue: [0.4, 0.4],
+        };
+        let not_a_number = Gamut {
+            red: [f64::NAN, 0.33],
+            ..SRGB
+        };
+
+        for gamut in [zero_y, on_one_line, not_a_number] {
+            assert!(gamut.validate().is_err());
+        }
+        assert!(SRGB.validate().is_ok());
+    }
+}
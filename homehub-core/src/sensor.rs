@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use chrono::{Duration, NaiveDateTime, Utc};
+pub use homehub_db::sensor_reading::Metric;
+use homehub_db::DatabaseConnection;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::device::DeviceState;
+use crate::error::Error;
+
+/// How far back a range reaches when the client does not say.
+const DEFAULT_RANGE: Duration = Duration::days(1);
+const DEFAULT_READINGS_LIMIT: u64 = 1000;
+const MAX_READINGS_LIMIT: u64 = 10000;
+/// The most buckets a single range may be split into.
+const MAX_BUCKETS: i64 = 10000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+pub struct ReadingDto {
+    pub metric: Metric,
+    pub value: f64,
+    pub recorded_at: NaiveDateTime,
+}
+
+impl From<homehub_db::sensor_reading::Model> for ReadingDto {
+    fn from(value: homehub_db::sensor_reading::Model) -> Self {
+        ReadingDto {
+            metric: value.metric,
+            value: value.value,
+            recorded_at: value.recorded_at,
+        }
+    }
+}
+
+/// The readings of a metric within one interval, starting at `start`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BucketDto {
+    pub start: NaiveDateTime,
+    pub min: f64,
+    pub max: f64,
+    pub avg: f64,
+    pub count: i64,
+}
+
+impl From<homehub_db::queries::sensor_reading::Bucket> for BucketDto {
+    fn from(value: homehub_db::queries::sensor_reading::Bucket) -> Self {
+        BucketDto {
+            start: value.start,
+            min: value.min,
+            max: value.max,
+            avg: value.avg,
+            count: value.count,
+        }
+    }
+}
+
+/// Remembers the most recent reading of every metric of every sensor, so
+/// that asking for current values and deciding whether a reading is newer
+/// than what the device shows does not have to scan the readings table.
+/// Devices are loaded from the database the first time they are needed.
+#[derive(Default)]
+pub struct LatestReadings {
+    readings: Mutex<HashMap<uuid::Uuid, HashMap<Metric, ReadingDto>>>,
+}
+
+impl LatestReadings {
+    async fn get(
+        &self,
+        device_id: &uuid::Uuid,
+        db: &DatabaseConnection,
+    ) -> Result<Vec<ReadingDto>, Error> {
+        if let Some(readings) = self.readings.lock().unwrap().get(device_id) {
+            return Ok(sorted(readings));
+        }
+        let loaded: HashMap<_, _> =
+            homehub_db::queries::sensor_reading::get_latest_readings(
+                device_id, db,
+            )
+            .await
+            .map_err(Error::DbError)?
+            .into_iter()
+            .map(|reading| (reading.metric, ReadingDto::from(reading)))
+            .collect();
+        // Someone else may have loaded the device in the meantime, and
+        // theirs is at least as recent.
+        let mut readings = self.readings.lock().unwrap();
+        Ok(sorted(readings.entry(*device_id).or_insert(loaded)))
+    }
+
+    /// Keeps `reading` if it is the newest of its metric, and returns
+    /// whether it was. The device must have been loaded with `get` first.
+    fn offer(&self, device_id: &uuid::Uuid, reading: ReadingDto) -> bool {
+        let mut readings = self.readings.lock().unwrap();
+        let latest = readings
+            .entry(*device_id)
+            .or_default()
+            .entry(reading.metric)
+            .or_insert(reading);
+        if latest.recorded_at > reading.recorded_at {
+            return false;
+        }
+        *latest = reading;
+        true
+    }
+
+    fn forget(&self, device_id: &uuid::Uuid) {
+        self.readings.lock().unwrap().remove(device_id);
+    }
+}
+
+fn sorted(readings: &HashMap<Metric, ReadingDto>) -> Vec<ReadingDto> {
+    let mut readings: Vec<_> = readings.values().copied().collect();
+    readings.sort_by_key(|reading| reading.metric);
+    readings
+}
+
+/// Fails unless the device exists and reports `metric`.
+async fn ensure_reports(
+    device_id: &uuid::Uuid,
+    metric: Metric,
+    db: &DatabaseConnection,
+) -> Result<(), Error> {
+    let device = crate::device::get_device(device_id, db).await?;
+    if !device.capabilities.contains(&metric.capability()) {
+        return Err(Error::UnsupportedCapabilityError(
+            metric.capability().name().to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+/// Stores readings of a sensor, whether they came in over the API or from a
+/// driver. The device's state is moved on to the newest value of each
+/// metric, unless it already shows a newer one. Returns the latest reading
+/// of every metric of the device.
+pub async fn record_readings(
+    device_id: &uuid::Uuid,
+    readings: &[ReadingDto],
+    db: &DatabaseConnection,
+    latest: &LatestReadings,
+) -> Result<Vec<ReadingDto>, Error> {
+    let device = crate::device::get_device(device_id, db).await?;
+    for reading in readings {
+        if !device.capabilities.contains(&reading.metric.capability()) {
+            return Err(Error::UnsupportedCapabilityError(
+                reading.metric.capability().name().to_owned(),
+            ));
+        }
+        if !reading.value.is_finite() {
+            return Err(Error::InvalidReadingError);
+        }
+    }
+    let rows: Vec<_> = readings
+        .iter()
+        .map(|reading| (reading.metric, reading.recorded_at, reading.value))
+        .collect();
+    homehub_db::queries::sensor_reading::insert_readings(device_id, &rows, db)
+        .await
+        .map_err(Error::DbError)?;
+
+    latest.get(device_id, db).await?;
+    let mut newest: HashMap<Metric, ReadingDto> = HashMap::new();
+    for reading in readings {
+        let entry = newest.entry(reading.metric).or_insert(*reading);
+        if reading.recorded_at > entry.recorded_at {
+            *entry = *reading;
+        }
+    }
+    let mut state = DeviceState::default();
+    for reading in newest.into_values() {
+        if latest.offer(device_id, reading) {
+            reading.metric.set(&mut state, reading.value);
+        }
+    }
+    if state != DeviceState::default() {
+        let updated = homehub_db::queries::device::set_device_state(
+            device_id, &state, None, db,
+        )
+        .await
+        .map_err(Error::DbError)?;
+        if updated.is_none() {
+            // Deleted while the readings were being stored.
+            latest.forget(device_id);
+            return Err(Error::DeviceNotFoundError);
+        }
+    }
+    latest.get(device_id, db).await
+}
+
+/// The most recent reading of every metric the sensor has reported.
+pub async fn get_latest_readings(
+    device_id: &uuid::Uuid,
+    db: &DatabaseConnection,
+    latest: &LatestReadings,
+) -> Result<Vec<ReadingDto>, Error> {
+    crate::device::get_device(device_id, db).await?;
+    latest.get(device_id, db).await
+}
+
+/// Fills in a missing end with now and a missing start with a day before the
+/// end, and checks that the range is not backwards.
+fn resolve_range(
+    from: Option<NaiveDateTime>,
+    to: Option<NaiveDateTime>,
+) -> Result<(NaiveDateTime, NaiveDateTime), Error> {
+    let to = to.unwrap_or_else(|| Utc::now().naive_utc());
+    let from = from.unwrap_or(to - DEFAULT_RANGE);
+    if from > to {
+        return Err(Error::InvalidReadingRangeError);
+    }
+    Ok((from, to))
+}
+
+/// The readings of a metric recorded from `from` up to but excluding `to`,
+/// oldest first. `limit` defaults to 1000 and is at most 10000; to page
+/// through a longer range, ask again from just after the last reading.
+pub async fn get_readings(
+    device_id: &uuid::Uuid,
+    metric: Metric,
+    from: Option<NaiveDateTime>,
+    to: Option<NaiveDateTime>,
+    limit: Option<u64>,
+    db: &DatabaseConnection,
+) -> Result<Vec<ReadingDto>, Error> {
+    let (from, to) = resolve_range(from, to)?;
+    ensure_reports(device_id, metric, db).await?;
+    let limit = limit
+        .unwrap_or(DEFAULT_READINGS_LIMIT)
+        .clamp(1, MAX_READINGS_LIMIT);
+    homehub_db::queries::sensor_reading::get_readings(
+        device_id, metric, from, to, limit, db,
+    )
+    .await
+    .map(|readings| readings.into_iter().map(Into::into).collect())
+    .map_err(Error::DbError)
+}
+
+/// The minimum, maximum and average of a metric per `bucket` of time, over
+/// the same range as [`get_readings`]. Buckets are aligned to the Unix
+/// epoch, so that a bucket of an hour starts on the hour.
+pub async fn get_reading_buckets(
+    device_id: &uuid::Uuid,
+    metric: Metric,
+    from: Option<NaiveDateTime>,
+    to: Option<NaiveDateTime>,
+    bucket: Duration,
+    db: &DatabaseConnection,
+) -> Result<Vec<BucketDto>, Error> {
+    let (from, to) = resolve_range(from, to)?;
+    let bucket_seconds = bucket.num_seconds();
+    if bucket_seconds < 1
+        || (to - from).num_seconds() / bucket_seconds > MAX_BUCKETS
+    {
+        return Err(Error::InvalidReadingRangeError);
+    }
+    ensure_reports(device_id, metric, db).await?;
+    homehub_db::queries::sensor_reading::get_buckets(
+        device_id,
+        metric,
+        from,
+        to,
+        bucket_seconds,
+        db,
+    )
+    .await
+    .map(|buckets| buckets.into_iter().map(Into::into).collect())
+    .map_err(Error::DbError)
+}
@@ -1,12 +1,22 @@
-use base64::{engine::general_purpose, Engine as _};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::keys::{KeySet, SigningKey};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TokenDetails {
     pub token: Option<String>,
     pub user_id: Uuid,
     pub expires_in: Option<i64>,
+    pub grant: Option<ClientGrant>,
+}
+
+/// Present on tokens issued to third-party OAuth clients, which only have
+/// access to the granted scopes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientGrant {
+    pub client_id: Uuid,
+    pub scope: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -15,22 +25,37 @@ pub struct TokenClaims {
     pub exp: i64,
     pub iat: i64,
     pub nbf: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
 }
 
 pub fn generate_jwt_token(
     user_id: uuid::Uuid,
     ttl: i64,
-    private_key: String,
-) -> Result<TokenDetails, jsonwebtoken::errors::Error> {
-    let bytes_private_key =
-        general_purpose::STANDARD.decode(private_key).unwrap();
-    let decoded_private_key = String::from_utf8(bytes_private_key).unwrap();
+    keys: &KeySet,
+) -> anyhow::Result<TokenDetails> {
+    generate_granted_jwt_token(user_id, None, ttl, keys)
+}
+
+pub fn generate_granted_jwt_token(
+    user_id: uuid::Uuid,
+    grant: Option<ClientGrant>,
+    ttl: i64,
+    keys: &KeySet,
+) -> anyhow::Result<TokenDetails> {
+    let signing_key = keys.active();
+    let encoding_key = signing_key
+        .encoding_key()
+        .ok_or_else(|| anyhow::anyhow!("Active key has no private key"))?;
 
     let now = chrono::Utc::now();
     let mut token_details = TokenDetails {
         user_id,
         expires_in: Some((now + chrono::Duration::minutes(ttl)).timestamp()),
         token: None,
+        grant,
     };
 
     let claims = TokenClaims {
@@ -38,41 +63,61 @@ pub fn generate_jwt_token(
         exp: token_details.expires_in.unwrap(),
         iat: now.timestamp(),
         nbf: now.timestamp(),
+        client_id: token_details
+            .grant
+            .as_ref()
+            .map(|grant| grant.client_id.to_string()),
+        scope: token_details
+            .grant
+            .as_ref()
+            .map(|grant| grant.scope.clone()),
     };
 
-    let header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256);
-    let token = jsonwebtoken::encode(
-        &header,
-        &claims,
-        &jsonwebtoken::EncodingKey::from_rsa_pem(
-            decoded_private_key.as_bytes(),
-        )?,
-    )?;
+    let mut header = jsonwebtoken::Header::new(signing_key.algorithm);
+    header.kid = Some(signing_key.kid.clone());
+    let token = jsonwebtoken::encode(&header, &claims, encoding_key)?;
     token_details.token = Some(token);
     Ok(token_details)
 }
 
 pub fn verify_jwt_token(
-    public_key: String,
+    keys: &KeySet,
     token: &str,
 ) -> anyhow::Result<TokenDetails> {
-    let bytes_public_key =
-        general_purpose::STANDARD.decode(public_key).unwrap();
-    let decoded_public_key = String::from_utf8(bytes_public_key).unwrap();
+    let header = jsonwebtoken::decode_header(token)?;
 
-    let validation =
-        jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::RS256);
+    // Tokens issued before key ids were introduced have no kid, so they are
+    // checked against every key we still accept.
+    let candidate_keys: Vec<&SigningKey> = match &header.kid {
+        Some(kid) => keys.find(kid).into_iter().collect(),
+        None => keys.iter().collect(),
+    };
+
+    let decoded = candidate_keys
+        .into_iter()
+        .find_map(|key| {
+            let validation = jsonwebtoken::Validation::new(key.algorithm);
+            jsonwebtoken::decode::<TokenClaims>(
+                token,
+                key.decoding_key(),
+                &validation,
+            )
+            .ok()
+        })
+        .ok_or_else(|| anyhow::anyhow!("Token signature is not valid"))?;
 
-    let decoded = jsonwebtoken::decode::<TokenClaims>(
-        token,
-        &jsonwebtoken::DecodingKey::from_rsa_pem(
-            decoded_public_key.as_bytes(),
-        )?,
-        &validation,
-    )?;
+    let grant = match (decoded.claims.client_id, decoded.claims.scope) {
+        (Some(client_id), Some(scope)) => Some(ClientGrant {
+            client_id: client_id.parse()?,
+            scope,
+        }),
+        (None, None) => None,
+        _ => anyhow::bail!("Token has an incomplete client grant"),
+    };
     Ok(TokenDetails {
         token: None,
         user_id: decoded.claims.sub.parse()?,
         expires_in: None,
+        grant,
     })
 }
@@ -1,67 +1,112 @@
 use homehub_db::{queries::app_user::FilteredAppUserModel, DatabaseConnection};
 
 use argon2::{
-    password_hash::SaltString, Argon2, PasswordHash, PasswordHasher,
-    PasswordVerifier,
+    password_hash::SaltString, Argon2, PasswordHash, PasswordVerifier,
 };
 use rand_core::OsRng;
-use thiserror::Error;
 
-use crate::config;
+use std::net::IpAddr;
+use std::sync::OnceLock;
 
-#[derive(Debug, Error)]
-pub enum RegisterUserError {
-    #[error("User with email already exists")]
-    UserAlreadyExists { email: String },
-    #[error("Failed to insert user")]
-    DbError(anyhow::Error),
-    #[error("Could not hash password")]
-    CouldNotHashError,
+use crate::config::{self, RegistrationMode};
+use crate::error::Error;
+use crate::i18n::Locale;
+use crate::login_throttle::LoginThrottle;
+use crate::mail::Mailer;
+use crate::password::{hash_password, needs_rehash, validate_password};
+
+const EMAIL_VERIFICATION_TTL_HOURS: i64 = 24;
+
+/// Whether the configured registration mode makes the first user an
+/// administrator and whether it asks everyone else for an invite, as
+/// `(first_user_admin, invite_required)`. Fails if nobody may register.
+pub(crate) fn registration_policy(
+    config: &config::Config,
+) -> Result<(bool, bool), Error> {
+    match config.registration_mode {
+        RegistrationMode::Disabled => Err(Error::RegistrationDisabledError),
+        RegistrationMode::FirstUserAdmin => Ok((true, true)),
+        RegistrationMode::InviteOnly => Ok((false, true)),
+        RegistrationMode::Open => Ok((false, false)),
+    }
 }
+
+/// Registers a user according to the configured registration mode.
+/// `invite_token` is only looked at when the mode requires an invite.
 pub async fn register_user(
     db: &DatabaseConnection,
     name: &str,
     email: &str,
     password: &str,
-) -> Result<FilteredAppUserModel, RegisterUserError> {
-    let salt = SaltString::generate(&mut OsRng);
-    let password_hash = Argon2::default()
-        .hash_password(password.as_bytes(), salt.as_salt())
-        .map(|hash| hash.to_string())
-        .map_err(|_| RegisterUserError::CouldNotHashError)?;
+    invite_token: Option<&str>,
+    config: &config::Config,
+) -> Result<FilteredAppUserModel, Error> {
+    let (first_user_admin, invite_required) = registration_policy(config)?;
+
+    let violations = validate_password(password, &config.password_policy);
+    if !violations.is_empty() {
+        return Err(Error::PasswordPolicyError(violations));
+    }
+
+    let password_hash = hash_password(password, &config.argon2)
+        .map_err(|_| Error::CouldNotHashError)?;
 
     if let Ok(Some(_)) =
         homehub_db::queries::app_user::find_user_by_email(email, db).await
     {
-        return Err(RegisterUserError::UserAlreadyExists {
+        return Err(Error::UserAlreadyExistsError {
             email: email.to_string(),
         });
     };
 
-    let user = homehub_db::queries::app_user::create_user(
+    // Whether this is the first user, redeeming the invite and creating the
+    // user all happen in one transaction, so that concurrent registrations
+    // cannot both become administrator and a failure does not use up the
+    // invite.
+    let invite_token_hash = invite_token.map(crate::oauth::hash_token);
+    let user = homehub_db::queries::app_user::register_user(
         name,
         email,
         &password_hash,
-        None,
+        first_user_admin,
+        invite_required,
+        invite_token_hash.as_deref(),
+        chrono::Utc::now().naive_utc(),
         db,
     )
     .await
-    .map_err(RegisterUserError::DbError)?;
+    .map_err(Error::DbError)?
+    .ok_or(Error::InvalidInviteError)?;
     Ok(user.into())
 }
 
-#[derive(Debug, Error)]
-pub enum LoginUserError {
-    #[error("User not found")]
-    UserNotFoundError(String),
-    #[error("Invalid credentials")]
-    InvalidCredentialError,
-    #[error("Failed to query database")]
-    DbError(anyhow::Error),
-    #[error("Could not hash password")]
-    CouldNotHashError,
-    #[error("Token generation failed")]
-    TokenGenerationError,
+/// Creates the configured bootstrap administrator if the database has no
+/// users yet, so that a fresh install can be managed without open
+/// registration.
+pub async fn bootstrap_admin(
+    db: &DatabaseConnection,
+    config: &config::Config,
+) -> anyhow::Result<Option<FilteredAppUserModel>> {
+    let Some(admin) = &config.bootstrap_admin else {
+        return Ok(None);
+    };
+    if homehub_db::queries::app_user::count_users(db).await? > 0 {
+        return Ok(None);
+    }
+
+    let password_hash = hash_password(&admin.password, &config.argon2)
+        .map_err(|_| anyhow::anyhow!("Could not hash password"))?;
+
+    // Checked again under a lock, in case someone registered in the
+    // meantime.
+    let user = homehub_db::queries::app_user::create_first_user(
+        &admin.name,
+        &admin.email,
+        &password_hash,
+        db,
+    )
+    .await?;
+    Ok(user.map(Into::into))
 }
 
 pub struct Tokens {
@@ -72,51 +117,176 @@ pub async fn login_user(
     db: &DatabaseConnection,
     email: &str,
     password: &str,
+    client_ip: IpAddr,
+    throttle: &LoginThrottle,
     config: &config::Config,
-) -> Result<Tokens, LoginUserError> {
+) -> Result<Tokens, Error> {
+    if let Some(retry_after) = throttle.check(email, client_ip) {
+        return Err(Error::TooManyLoginAttemptsError { retry_after });
+    }
+
     let user = homehub_db::queries::app_user::find_user_by_email(email, db)
         .await
-        .map_err(LoginUserError::DbError)?
-        .ok_or_else(|| LoginUserError::UserNotFoundError(email.to_string()))?;
+        .map_err(Error::DbError)?;
+
+    let Some(user) = user else {
+        // Spend as long as a real verification would, so response times do
+        // not reveal which emails have accounts.
+        let _ = Argon2::default().verify_password(
+            password.as_bytes(),
+            &dummy_password_hash(&config.argon2),
+        );
+        throttle.record_failure(email, client_ip);
+        // Unknown users get the same error as wrong passwords so that the
+        // endpoint cannot be used to find out which emails have accounts.
+        return Err(Error::InvalidCredentialError);
+    };
+
+    let Ok(hash) = PasswordHash::new(&user.password_hash) else {
+        return Err(Error::CouldNotHashError);
+    };
 
-    let matches = match PasswordHash::new(&user.password_hash) {
-        Ok(hash) => {
-            Argon2::default().verify_password(password.as_bytes(), &hash)
+    match Argon2::default().verify_password(password.as_bytes(), &hash) {
+        Ok(_) => {
+            throttle.record_success(email);
+            check_can_sign_in(&user)?;
+            if needs_rehash(&hash, &config.argon2) {
+                rehash_password(user.id, password, db, config).await;
+            }
+            generate_tokens(user.id, config)
         }
-        Err(_) => return Err(LoginUserError::CouldNotHashError),
+        Err(_) => {
+            throttle.record_failure(email, client_ip);
+            Err(Error::InvalidCredentialError)
+        }
+    }
+}
+
+/// Upgrades a hash made with outdated parameters now that the password is
+/// known. Failing to do so is not worth failing the login over.
+async fn rehash_password(
+    user_id: uuid::Uuid,
+    password: &str,
+    db: &DatabaseConnection,
+    config: &config::Config,
+) {
+    let result = match hash_password(password, &config.argon2) {
+        Ok(password_hash) => {
+            homehub_db::queries::app_user::update_password_hash(
+                user_id,
+                &password_hash,
+                db,
+            )
+            .await
+            .map(|_| ())
+        }
+        Err(e) => Err(anyhow::anyhow!(e)),
     };
+    if let Err(e) = result {
+        tracing::warn!("Could not rehash password for {}: {}", user_id, e);
+    }
+}
+
+fn dummy_password_hash(params: &argon2::Params) -> PasswordHash<'static> {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    let hash = DUMMY_HASH.get_or_init(|| {
+        let secret = SaltString::generate(&mut OsRng);
+        hash_password(secret.as_str(), params)
+            .expect("hashing a random password cannot fail")
+    });
+    PasswordHash::new(hash).expect("dummy hash is a valid PHC string")
+}
+
+/// Sets a new password using the one-time token from an administrator's
+/// forced password reset.
+pub async fn reset_password(
+    db: &DatabaseConnection,
+    reset_token: &str,
+    password: &str,
+    config: &config::Config,
+) -> Result<FilteredAppUserModel, Error> {
+    let user =
+        homehub_db::queries::app_user::find_by_password_reset_token_hash(
+            &crate::oauth::hash_token(reset_token),
+            db,
+        )
+        .await
+        .map_err(Error::DbError)?
+        .filter(|user| {
+            user.password_reset_expires_at.is_some_and(|expires_at| {
+                expires_at > chrono::Utc::now().naive_utc()
+            })
+        })
+        .ok_or(Error::InvalidResetTokenError)?;
+
+    let violations = validate_password(password, &config.password_policy);
+    if !violations.is_empty() {
+        return Err(Error::PasswordPolicyError(violations));
+    }
 
-    match matches {
-        Ok(_) => generate_tokens(user.id, config),
-        Err(_) => Err(LoginUserError::InvalidCredentialError),
+    let password_hash = hash_password(password, &config.argon2)
+        .map_err(|_| Error::CouldNotHashError)?;
+
+    let user = homehub_db::queries::app_user::update_password(
+        user.id,
+        &password_hash,
+        db,
+    )
+    .await
+    .map_err(Error::DbError)?;
+    Ok(user.into())
+}
+
+/// Rejects users who were disabled or told to reset their password, even
+/// though they hold valid credentials.
+pub fn check_can_sign_in(
+    user: &homehub_db::app_user::Model,
+) -> Result<(), Error> {
+    if user.disabled_at.is_some() {
+        return Err(Error::AccountDisabledError);
     }
+    if user.password_reset_required {
+        return Err(Error::PasswordResetRequiredError);
+    }
+    Ok(())
 }
 
 pub async fn refresh_access_token(
+    db: &DatabaseConnection,
     refresh_token: &str,
     config: &config::Config,
-) -> Result<Tokens, LoginUserError> {
-    crate::token::verify_jwt_token(
-        config.refresh_token_public_key.clone(),
+) -> Result<Tokens, Error> {
+    let user_id = crate::token::verify_jwt_token(
+        &config.refresh_token_keys,
         refresh_token,
     )
-    .map_err(|_| LoginUserError::InvalidCredentialError)
-    .map(|token_detail| generate_tokens(token_detail.user_id, config))?
+    .ok()
+    .filter(|token_detail| token_detail.grant.is_none())
+    .ok_or(Error::InvalidCredentialError)?
+    .user_id;
+
+    let user = homehub_db::queries::app_user::find_by_id(user_id, db)
+        .await
+        .map_err(Error::DbError)?
+        .ok_or(Error::InvalidCredentialError)?;
+    check_can_sign_in(&user)?;
+
+    generate_tokens(user.id, config)
 }
 
-fn generate_tokens(
+pub(crate) fn generate_tokens(
     user_id: uuid::Uuid,
     config: &config::Config,
-) -> Result<Tokens, LoginUserError> {
+) -> Result<Tokens, Error> {
     let access_token = crate::token::generate_jwt_token(
         user_id,
         config.access_token_max_age,
-        config.access_token_private_key.clone(),
+        &config.access_token_keys,
     );
     let refresh_token = crate::token::generate_jwt_token(
         user_id,
         config.refresh_token_max_age,
-        config.refresh_token_private_key.clone(),
+        &config.refresh_token_keys,
     );
     let tokens = vec![access_token, refresh_token]
         .into_iter()
@@ -126,7 +296,7 @@ fn generate_tokens(
             })
         })
         .collect::<Result<Vec<String>, _>>()
-        .map_err(|_| LoginUserError::TokenGenerationError);
+        .map_err(|_| Error::TokenGenerationError);
 
     match tokens {
         Ok(tokens) => Ok(Tokens {
@@ -137,9 +307,236 @@ fn generate_tokens(
     }
 }
 
+/// Updates the user's profile. A new email only takes effect once the user
+/// follows the verification email sent to it.
+pub async fn update_user(
+    db: &DatabaseConnection,
+    user_id: uuid::Uuid,
+    name: Option<&str>,
+    email: Option<&str>,
+    locale: Option<Option<&str>>,
+    mailer: &Mailer,
+) -> Result<FilteredAppUserModel, Error> {
+    let user = homehub_db::queries::app_user::find_by_id(user_id, db)
+        .await
+        .map_err(Error::DbError)?
+        .ok_or(Error::UserNotFoundError)?;
+
+    // Everything is checked before anything is written, so that a rejected
+    // email does not leave the rest of the update applied.
+    let email = email.filter(|email| *email != user.email);
+    if let Some(email) = email {
+        if homehub_db::queries::app_user::find_user_by_email(email, db)
+            .await
+            .map_err(Error::DbError)?
+            .is_some()
+        {
+            return Err(Error::UserAlreadyExistsError {
+                email: email.to_string(),
+            });
+        }
+    }
+
+    let verification_token = email.map(|_| crate::oauth::random_token());
+    let token_hash =
+        verification_token.as_deref().map(crate::oauth::hash_token);
+    let pending_email =
+        email.zip(token_hash.as_deref()).map(|(email, token_hash)| {
+            homehub_db::queries::app_user::PendingEmail {
+                email,
+                token_hash,
+                expires_at: chrono::Utc::now().naive_utc()
+                    + chrono::Duration::hours(EMAIL_VERIFICATION_TTL_HOURS),
+            }
+        });
+    let user = homehub_db::queries::app_user::update_profile(
+        user_id,
+        name,
+        locale,
+        pending_email,
+        db,
+    )
+    .await
+    .map_err(Error::DbError)?;
+
+    let (Some(email), Some(verification_token)) = (email, verification_token)
+    else {
+        return Ok(user.into());
+    };
+    let locale = Locale::negotiate(user.locale.as_deref());
+    mailer
+        .send(
+            email,
+            &locale.message("email-verification-subject"),
+            &locale.message_with(
+                "email-verification-body",
+                &[
+                    ("email", email.into()),
+                    ("code", verification_token.as_str().into()),
+                    ("hours", EMAIL_VERIFICATION_TTL_HOURS.into()),
+                ],
+            ),
+        )
+        .await
+        .map_err(Error::MailError)?;
+
+    Ok(user.into())
+}
+
+pub async fn verify_email(
+    db: &DatabaseConnection,
+    verification_token: &str,
+) -> Result<FilteredAppUserModel, Error> {
+    let user =
+        homehub_db::queries::app_user::find_by_email_verification_token_hash(
+            &crate::oauth::hash_token(verification_token),
+            db,
+        )
+        .await
+        .map_err(Error::DbError)?
+        .filter(|user| {
+            user.email_verification_expires_at
+                .is_some_and(|expires_at| {
+                    expires_at > chrono::Utc::now().naive_utc()
+                })
+        })
+        .ok_or(Error::InvalidVerificationTokenError)?;
+    // A token without an address to confirm has nothing left to verify.
+    let email = user
+        .pending_email
+        .as_deref()
+        .ok_or(Error::InvalidVerificationTokenError)?;
+
+    // Someone else may have registered the address in the meantime.
+    if homehub_db::queries::app_user::find_user_by_email(email, db)
+        .await
+        .map_err(Error::DbError)?
+        .is_some()
+    {
+        return Err(Error::UserAlreadyExistsError {
+            email: email.to_string(),
+        });
+    }
+
+    let user =
+        homehub_db::queries::app_user::confirm_pending_email(user.id, db)
+            .await
+            .map_err(Error::DbError)?;
+    Ok(user.into())
+}
+
+pub async fn change_password(
+    db: &DatabaseConnection,
+    user_id: uuid::Uuid,
+    current_password: &str,
+    new_password: &str,
+    config: &config::Config,
+) -> Result<(), Error> {
+    let user = homehub_db::queries::app_user::find_by_id(user_id, db)
+        .await
+        .map_err(Error::DbError)?
+        .ok_or(Error::UserNotFoundError)?;
+
+    let hash = PasswordHash::new(&user.password_hash)
+        .map_err(|_| Error::CouldNotHashError)?;
+    Argon2::default()
+        .verify_password(current_password.as_bytes(), &hash)
+        .map_err(|_| Error::CurrentPasswordIncorrectError)?;
+
+    let violations = validate_password(new_password, &config.password_policy);
+    if !violations.is_empty() {
+        return Err(Error::PasswordPolicyError(violations));
+    }
+
+    let password_hash = hash_password(new_password, &config.argon2)
+        .map_err(|_| Error::CouldNotHashError)?;
+    homehub_db::queries::app_user::update_password(user_id, &password_hash, db)
+        .await
+        .map_err(Error::DbError)?;
+    Ok(())
+}
+
+/// Deletes the user's account along with everything that belongs to it.
+pub async fn delete_user(
+    db: &DatabaseConnection,
+    user_id: uuid::Uuid,
+) -> Result<(), Error> {
+    homehub_db::queries::app_user::delete_user(user_id, db)
+        .await
+        .map_err(Error::DbError)?;
+    Ok(())
+}
+
 pub async fn find_by_id(
     id: uuid::Uuid,
     db: &DatabaseConnection,
 ) -> anyhow::Result<Option<homehub_db::app_user::Model>> {
     homehub_db::queries::app_user::find_by_id(id, db).await
 }
+
+#[cfg(test)]
+mod tests {
+    use homehub_db::app_user;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    use super::*;
+
+    fn user_verifying(pending_email: Option<&str>) -> app_user::Model {
+        app_user::Model {
+            id: uuid::Uuid::from_u128(1),
+            name: "Ada".to_string(),
+            email: "ada@example.com".to_string(),
+            password_hash: String::new(),
+            created_at: None,
+            updated_at: None,
+            locale: None,
+            is_admin: false,
+            disabled_at: None,
+            password_reset_required: false,
+            password_reset_token_hash: None,
+            password_reset_expires_at: None,
+            pending_email: pending_email.map(str::to_string),
+            email_verification_token_hash: Some(crate::oauth::hash_token(
+                "token",
+            )),
+            email_verification_expires_at: Some(
+                chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
+            ),
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_tokens_without_a_pending_email() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![user_verifying(None)]])
+            .into_connection();
+
+        let result = verify_email(&db, "token").await;
+
+        assert!(matches!(result, Err(Error::InvalidVerificationTokenError)));
+        assert_eq!(db.into_transaction_log().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn confirms_the_pending_email() {
+        let user = user_verifying(Some("grace@example.com"));
+        let confirmed = app_user::Model {
+            email: "grace@example.com".to_string(),
+            pending_email: None,
+            email_verification_token_hash: None,
+            email_verification_expires_at: None,
+            ..user.clone()
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![user.clone()]])
+            .append_query_results([Vec::<app_user::Model>::new()])
+            .append_query_results([vec![user]])
+            .append_query_results([vec![confirmed]])
+            .into_connection();
+
+        let user = verify_email(&db, "token").await.unwrap();
+
+        assert_eq!(user.email, "grace@example.com");
+        assert_eq!(user.pending_email, None);
+    }
+}
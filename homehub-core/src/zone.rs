@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+pub use homehub_db::queries::zone::ZoneSort;
+use homehub_db::DatabaseConnection;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::error::Error;
+use crate::light::{AggregateState, LightEvents, LightSelector, LightState};
+use crate::pagination::{Page, PageRequest};
+
+/// A group of lights, such as "Downstairs" or "Christmas lights". Unlike
+/// rooms, a light can be in any number of zones.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ZoneDto {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub light_ids: Vec<uuid::Uuid>,
+    /// The combined state of the zone's lights.
+    pub state: AggregateState,
+    pub updated_at: NaiveDateTime,
+    pub version: i32,
+}
+
+async fn with_lights(
+    zones: Vec<homehub_db::zone::Model>,
+    db: &DatabaseConnection,
+) -> Result<Vec<ZoneDto>, Error> {
+    let ids: Vec<_> = zones.iter().map(|zone| zone.id).collect();
+    let mut lights: HashMap<_, Vec<_>> = HashMap::new();
+    for (zone_id, light_id, state) in
+        homehub_db::queries::zone::get_lights(&ids, db)
+            .await
+            .map_err(Error::DbError)?
+    {
+        lights.entry(zone_id).or_default().push((light_id, state));
+    }
+    Ok(zones
+        .into_iter()
+        .map(|zone| {
+            let lights = lights.remove(&zone.id).unwrap_or_default();
+            ZoneDto {
+                id: zone.id,
+                name: zone.name,
+                light_ids: lights.iter().map(|(id, _)| *id).collect(),
+                state: AggregateState::of(
+                    lights.iter().map(|(_, state)| state),
+                ),
+                updated_at: zone.updated_at,
+                version: zone.version,
+            }
+        })
+        .collect())
+}
+
+async fn with_lights_one(
+    zone: homehub_db::zone::Model,
+    db: &DatabaseConnection,
+) -> Result<ZoneDto, Error> {
+    with_lights(vec![zone], db)
+        .await?
+        .pop()
+        .ok_or(Error::ZoneNotFoundError)
+}
+
+/// Lights are referenced by id in requests, so make sure they all exist
+/// before adding them to a zone.
+async fn ensure_lights_exist(
+    light_ids: &[uuid::Uuid],
+    db: &DatabaseConnection,
+) -> Result<(), Error> {
+    match homehub_db::queries::light::lights_exist(light_ids, db).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(Error::LightNotFoundError),
+        Err(e) => Err(Error::DbError(e)),
+    }
+}
+
+/// Explains why a conditional update changed nothing: either the zone is
+/// gone, or it is no longer at the version the client expected.
+async fn not_updated_error(
+    id: &uuid::Uuid,
+    if_match: Option<&[i32]>,
+    db: &DatabaseConnection,
+) -> Error {
+    match homehub_db::queries::zone::get_zone(id, db).await {
+        Ok(Some(_)) if if_match.is_some() => Error::PreconditionFailedError,
+        Ok(_) => Error::ZoneNotFoundError,
+        Err(e) => Error::DbError(e),
+    }
+}
+
+/// Lists all zones, or only those a light is in.
+pub async fn get_zones(
+    light_id: Option<&uuid::Uuid>,
+    page: &PageRequest<ZoneSort>,
+    db: &DatabaseConnection,
+) -> Result<Page<ZoneDto>, Error> {
+    let zones = homehub_db::queries::zone::get_zones(light_id, page, db)
+        .await
+        .map_err(Error::DbError)?;
+    let items = with_lights(zones.items, db).await?;
+    Ok(Page {
+        items,
+        next_cursor: zones.next_cursor,
+        limit: zones.limit,
+    })
+}
+
+pub async fn get_zone(
+    id: &uuid::Uuid,
+    db: &DatabaseConnection,
+) -> Result<ZoneDto, Error> {
+    let zone = homehub_db::queries::zone::get_zone(id, db)
+        .await
+        .map_err(Error::DbError)?
+        .ok_or(Error::ZoneNotFoundError)?;
+    with_lights_one(zone, db).await
+}
+
+pub async fn create_zone(
+    name: &str,
+    light_ids: &[uuid::Uuid],
+    db: &DatabaseConnection,
+) -> Result<ZoneDto, Error> {
+    ensure_lights_exist(light_ids, db).await?;
+    let zone = homehub_db::queries::zone::create_zone(name, light_ids, db)
+        .await
+        .map_err(Error::DbError)?;
+    with_lights_one(zone, db).await
+}
+
+/// `light_ids` replaces the zone's lights when set. `if_match` lists the
+/// versions the client expects the zone to be at, as sent in an `If-Match`
+/// header. `None` updates whatever version is current.
+pub async fn update_zone(
+    id: &uuid::Uuid,
+    name: Option<&str>,
+    light_ids: Option<&[uuid::Uuid]>,
+    if_match: Option<&[i32]>,
+    db: &DatabaseConnection,
+) -> Result<ZoneDto, Error> {
+    if let Some(light_ids) = light_ids {
+        ensure_lights_exist(light_ids, db).await?;
+    }
+    match homehub_db::queries::zone::update_zone(
+        id, name, light_ids, if_match, db,
+    )
+    .await
+    .map_err(Error::DbError)?
+    {
+        Some(zone) => with_lights_one(zone, db).await,
+        None => Err(not_updated_error(id, if_match, db).await),
+    }
+}
+
+/// Adds a light to a zone. Adding a light that is already in the zone
+/// changes nothing but its version.
+pub async fn add_light(
+    id: &uuid::Uuid,
+    light_id: &uuid::Uuid,
+    db: &DatabaseConnection,
+) -> Result<ZoneDto, Error> {
+    ensure_lights_exist(std::slice::from_ref(light_id), db).await?;
+    let zone = homehub_db::queries::zone::set_member(id, light_id, true, db)
+        .await
+        .map_err(Error::DbError)?
+        .ok_or(Error::ZoneNotFoundError)?;
+    with_lights_one(zone, db).await
+}
+
+pub async fn remove_light(
+    id: &uuid::Uuid,
+    light_id: &uuid::Uuid,
+    db: &DatabaseConnection,
+) -> Result<ZoneDto, Error> {
+    let zone = homehub_db::queries::zone::set_member(id, light_id, false, db)
+        .await
+        .map_err(Error::DbError)?
+        .ok_or(Error::ZoneNotFoundError)?;
+    with_lights_one(zone, db).await
+}
+
+pub async fn delete_zone(
+    id: &uuid::Uuid,
+    db: &DatabaseConnection,
+) -> Result<(), Error> {
+    match homehub_db::queries::zone::delete_zone(id, db).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(Error::ZoneNotFoundError),
+        Err(e) => Err(Error::DbError(e)),
+    }
+}
+
+/// Switches every light in a zone at once.
+pub async fn set_zone_state(
+    id: &uuid::Uuid,
+    state: LightState,
+    db: &DatabaseConnection,
+    events: &LightEvents,
+) -> Result<ZoneDto, Error> {
+    crate::light::set_light_states(
+        &LightSelector::Zone(*id),
+        state,
+        db,
+        events,
+    )
+    .await?;
+    get_zone(id, db).await
+}
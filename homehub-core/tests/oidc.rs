@@ -0,0 +1,446 @@
+//! Signs in through a mock OpenID Connect provider served on localhost, with
+//! the database mocked so that the queries run can be checked.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use base64::{engine::general_purpose, Engine as _};
+use homehub_core::config::{
+    Config, LoginThrottleConfig, OidcConfig, PasswordPolicyConfig,
+    RateLimitConfig, RegistrationMode,
+};
+use homehub_core::error::Error;
+use homehub_core::keys::KeySet;
+use homehub_core::oidc::OidcProvider;
+use homehub_core::user::Tokens;
+use homehub_db::{app_user, user_identity, DatabaseConnection};
+use jsonwebtoken::{Algorithm, Header};
+use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+const CLIENT_ID: &str = "homehub";
+
+struct MockProvider {
+    issuer: String,
+    keys: KeySet,
+    /// The claims of the next ID token the token endpoint hands out.
+    claims: Mutex<Value>,
+}
+
+fn fixture(name: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    general_purpose::STANDARD.encode(std::fs::read(path).unwrap())
+}
+
+fn signing_keys() -> KeySet {
+    KeySet::from_base64_pems(
+        &fixture("signing-key.pem"),
+        &fixture("signing-key.pub.pem"),
+        &[],
+        Some(Algorithm::RS256),
+    )
+    .unwrap()
+}
+
+async fn discovery(State(provider): State<Arc<MockProvider>>) -> Json<Value> {
+    let issuer = &provider.issuer;
+    Json(json!({
+        "issuer": issuer,
+        "authorization_endpoint": format!("{issuer}/authorize"),
+        "token_endpoint": format!("{issuer}/token"),
+        "jwks_uri": format!("{issuer}/jwks"),
+        "response_types_supported": ["code"],
+        "subject_types_supported": ["public"],
+        "id_token_signing_alg_values_supported": ["RS256"],
+    }))
+}
+
+async fn jwks(State(provider): State<Arc<MockProvider>>) -> Json<Value> {
+    Json(json!(provider.keys.jwks()))
+}
+
+async fn token(State(provider): State<Arc<MockProvider>>) -> Json<Value> {
+    let key = provider.keys.active();
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(key.kid.clone());
+    let claims = provider.claims.lock().unwrap().clone();
+    let id_token =
+        jsonwebtoken::encode(&header, &claims, key.encoding_key().unwrap())
+            .unwrap();
+    Json(json!({
+        "access_token": "provider-access-token",
+        "token_type": "Bearer",
+        "expires_in": 300,
+        "id_token": id_token,
+    }))
+}
+
+async fn start_provider() -> Arc<MockProvider> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let provider = Arc::new(MockProvider {
+        issuer: format!("http://{}", listener.local_addr().unwrap()),
+        keys: signing_keys(),
+        claims: Mutex::new(Value::Null),
+    });
+    let app = Router::new()
+        .route("/.well-known/openid-configuration", get(discovery))
+        .route("/jwks", get(jwks))
+        .route("/token", post(token))
+        .with_state(provider.clone());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    provider
+}
+
+fn config(issuer: &str) -> Config {
+    Config {
+        database_url: String::new(),
+        access_token_keys: signing_keys(),
+        refresh_token_keys: signing_keys(),
+        access_token_max_age: 15,
+        refresh_token_max_age: 60,
+        oidc: Some(OidcConfig {
+            issuer_url: issuer.to_string(),
+            client_id: CLIENT_ID.to_string(),
+            client_secret: Some("secret".to_string()),
+            redirect_url: "http://localhost/auth/oidc/callback".to_string(),
+        }),
+        login_throttle: LoginThrottleConfig {
+            max_account_attempts: 5,
+            max_ip_attempts: 20,
+            base_lockout: Duration::from_secs(30),
+            max_lockout: Duration::from_secs(60 * 60),
+        },
+        trust_proxy_headers: false,
+        rate_limit: RateLimitConfig {
+            burst: 60,
+            per_second: 10.0,
+        },
+        registration_mode: RegistrationMode::Open,
+        bootstrap_admin: None,
+        password_policy: PasswordPolicyConfig {
+            min_length: 10,
+            min_entropy_bits: 40.0,
+            breached_passwords_path: None,
+        },
+        // Cheap to hash, as the account created here never signs in with it
+        argon2: argon2::Params::new(8, 1, 1, None).unwrap(),
+        mail: None,
+        idempotency_key_ttl: Duration::from_secs(24 * 60 * 60),
+        idempotency_key_lease: Duration::from_secs(60),
+        device_profiles_path: None,
+        grpc_address: "127.0.0.1:0".parse().unwrap(),
+    }
+}
+
+fn user(id: u128, email: &str) -> app_user::Model {
+    app_user::Model {
+        id: Uuid::from_u128(id),
+        name: "Ada".to_string(),
+        email: email.to_string(),
+        password_hash: String::new(),
+        created_at: None,
+        updated_at: None,
+        locale: None,
+        is_admin: false,
+        disabled_at: None,
+        password_reset_required: false,
+        password_reset_token_hash: None,
+        password_reset_expires_at: None,
+        pending_email: None,
+        email_verification_token_hash: None,
+        email_verification_expires_at: None,
+    }
+}
+
+fn identity(user_id: Uuid, issuer: &str) -> user_identity::Model {
+    user_identity::Model {
+        id: Uuid::from_u128(100),
+        user_id,
+        issuer: issuer.to_string(),
+        subject: "subject".to_string(),
+        created_at: None,
+    }
+}
+
+/// Goes through the whole flow, with the provider vouching for `email` as
+/// `email_verified` says.
+async fn sign_in(
+    provider: &MockProvider,
+    db: &DatabaseConnection,
+    email: &str,
+    email_verified: bool,
+    registration_mode: RegistrationMode,
+) -> Result<Tokens, Error> {
+    let config = Config {
+        registration_mode,
+        ..config(&provider.issuer)
+    };
+    let oidc = OidcProvider::discover(config.oidc.as_ref().unwrap())
+        .await
+        .unwrap();
+
+    let auth_url = url::Url::parse(&oidc.begin_login()).unwrap();
+    let query_param = |name: &str| {
+        auth_url
+            .query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+            .unwrap()
+    };
+
+    let now = chrono::Utc::now().timestamp();
+    *provider.claims.lock().unwrap() = json!({
+        "iss": provider.issuer,
+        "aud": CLIENT_ID,
+        "sub": "subject",
+        "iat": now,
+        "exp": now + 300,
+        "nonce": query_param("nonce"),
+        "email": email,
+        "email_verified": email_verified,
+        "name": "Ada",
+    });
+
+    oidc.complete_login(db, "code", &query_param("state"), &config)
+        .await
+}
+
+/// The outcome of taking the registration lock.
+fn lock_taken() -> MockExecResult {
+    MockExecResult {
+        last_insert_id: 0,
+        rows_affected: 1,
+    }
+}
+
+fn user_count(count: i64) -> BTreeMap<String, sea_orm::Value> {
+    BTreeMap::from([("num_items".to_string(), count.into())])
+}
+
+fn executed_sql(db: DatabaseConnection) -> String {
+    format!("{:?}", db.into_transaction_log())
+}
+
+#[tokio::test]
+async fn rejects_unknown_state() {
+    let provider = start_provider().await;
+    let db = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let config = config(&provider.issuer);
+    let oidc = OidcProvider::discover(config.oidc.as_ref().unwrap())
+        .await
+        .unwrap();
+    oidc.begin_login();
+
+    let result = oidc
+        .complete_login(&db, "code", "not-the-state", &config)
+        .await;
+
+    assert!(matches!(result, Err(Error::OidcUnknownStateError)));
+    assert_eq!(db.into_transaction_log().len(), 0);
+}
+
+#[tokio::test]
+async fn does_not_link_unverified_email_to_existing_account() {
+    let provider = start_provider().await;
+    let existing_user = user(1, "ada@example.com");
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([Vec::<user_identity::Model>::new()])
+        .append_query_results([vec![existing_user]])
+        .into_connection();
+
+    let result = sign_in(
+        &provider,
+        &db,
+        "ada@example.com",
+        false,
+        RegistrationMode::Open,
+    )
+    .await;
+
+    assert!(matches!(
+        result,
+        Err(Error::OidcUnverifiedEmailError(email)) if email == "ada@example.com"
+    ));
+    assert!(!executed_sql(db).contains("INSERT"));
+}
+
+#[tokio::test]
+async fn links_verified_email_to_existing_account() {
+    let provider = start_provider().await;
+    let existing_user = user(1, "ada@example.com");
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([Vec::<user_identity::Model>::new()])
+        .append_query_results([vec![existing_user.clone()]])
+        .append_query_results([vec![identity(
+            existing_user.id,
+            &provider.issuer,
+        )]])
+        .append_query_results([vec![existing_user.clone()]])
+        .into_connection();
+
+    let tokens = sign_in(
+        &provider,
+        &db,
+        "ada@example.com",
+        true,
+        RegistrationMode::Open,
+    )
+    .await
+    .unwrap();
+
+    let access_token = homehub_core::token::verify_jwt_token(
+        &config(&provider.issuer).access_token_keys,
+        &tokens.access_token,
+    )
+    .unwrap();
+    assert_eq!(access_token.user_id, existing_user.id);
+    let sql = executed_sql(db);
+    assert!(sql.contains(r#"INSERT INTO \"user_identity\""#));
+    assert!(!sql.contains(r#"INSERT INTO \"app_user\""#));
+}
+
+#[tokio::test]
+async fn creates_account_with_identity_in_one_transaction() {
+    let provider = start_provider().await;
+    let new_user = user(2, "grace@example.com");
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([Vec::<user_identity::Model>::new()])
+        .append_query_results([Vec::<app_user::Model>::new()])
+        .append_exec_results([lock_taken()])
+        .append_query_results([vec![new_user.clone()]])
+        .append_query_results([vec![identity(new_user.id, &provider.issuer)]])
+        .append_query_results([vec![new_user.clone()]])
+        .into_connection();
+
+    let tokens = sign_in(
+        &provider,
+        &db,
+        "grace@example.com",
+        false,
+        RegistrationMode::Open,
+    )
+    .await
+    .unwrap();
+
+    let access_token = homehub_core::token::verify_jwt_token(
+        &config(&provider.issuer).access_token_keys,
+        &tokens.access_token,
+    )
+    .unwrap();
+    assert_eq!(access_token.user_id, new_user.id);
+    let log = db.into_transaction_log();
+    let transaction = log
+        .iter()
+        .map(|transaction| format!("{:?}", transaction))
+        .find(|transaction| transaction.contains("INSERT"))
+        .unwrap();
+    assert!(transaction.contains("pg_advisory_xact_lock"));
+    assert!(transaction.contains(r#"INSERT INTO \"app_user\""#));
+    assert!(transaction.contains(r#"INSERT INTO \"user_identity\""#));
+}
+
+#[tokio::test]
+async fn invite_only_rejects_unknown_subject() {
+    let provider = start_provider().await;
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([Vec::<user_identity::Model>::new()])
+        .append_query_results([Vec::<app_user::Model>::new()])
+        .into_connection();
+
+    let result = sign_in(
+        &provider,
+        &db,
+        "grace@example.com",
+        true,
+        RegistrationMode::InviteOnly,
+    )
+    .await;
+
+    assert!(matches!(result, Err(Error::InvalidInviteError)));
+    assert!(!executed_sql(db).contains("INSERT"));
+}
+
+#[tokio::test]
+async fn disabled_registration_rejects_unknown_subject() {
+    let provider = start_provider().await;
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([Vec::<user_identity::Model>::new()])
+        .append_query_results([Vec::<app_user::Model>::new()])
+        .into_connection();
+
+    let result = sign_in(
+        &provider,
+        &db,
+        "grace@example.com",
+        true,
+        RegistrationMode::Disabled,
+    )
+    .await;
+
+    assert!(matches!(result, Err(Error::RegistrationDisabledError)));
+    assert!(!executed_sql(db).contains("INSERT"));
+}
+
+#[tokio::test]
+async fn makes_the_first_user_an_administrator() {
+    let provider = start_provider().await;
+    let new_user = app_user::Model {
+        is_admin: true,
+        ..user(2, "grace@example.com")
+    };
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([Vec::<user_identity::Model>::new()])
+        .append_query_results([Vec::<app_user::Model>::new()])
+        .append_exec_results([lock_taken()])
+        .append_query_results([vec![user_count(0)]])
+        .append_query_results([vec![new_user.clone()]])
+        .append_query_results([vec![identity(new_user.id, &provider.issuer)]])
+        .append_query_results([vec![new_user.clone()]])
+        .into_connection();
+
+    sign_in(
+        &provider,
+        &db,
+        "grace@example.com",
+        true,
+        RegistrationMode::FirstUserAdmin,
+    )
+    .await
+    .unwrap();
+
+    let sql = executed_sql(db);
+    assert!(sql.contains("pg_advisory_xact_lock"));
+    assert!(sql.contains("COUNT(*)"));
+    assert!(sql.contains("Bool(Some(true))"));
+}
+
+#[tokio::test]
+async fn first_user_admin_rejects_later_users_without_an_invite() {
+    let provider = start_provider().await;
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([Vec::<user_identity::Model>::new()])
+        .append_query_results([Vec::<app_user::Model>::new()])
+        .append_exec_results([lock_taken()])
+        .append_query_results([vec![user_count(1)]])
+        .into_connection();
+
+    let result = sign_in(
+        &provider,
+        &db,
+        "grace@example.com",
+        true,
+        RegistrationMode::FirstUserAdmin,
+    )
+    .await;
+
+    assert!(matches!(result, Err(Error::InvalidInviteError)));
+    assert!(!executed_sql(db).contains("INSERT"));
+}
@@ -3,6 +3,18 @@ pub use sea_orm_migration::prelude::*;
 mod m20240317_190601_create_base_schema;
 mod m20240330_012419_add_light;
 mod m20240331_095824_change_light_state;
+mod m20240406_113012_add_user_identity;
+mod m20240413_094511_add_oauth;
+mod m20240420_160245_add_user_admin;
+mod m20240427_102214_add_registration_invite;
+mod m20240504_141907_add_email_verification;
+mod m20240511_093015_add_light_timestamps;
+mod m20240518_101544_add_versions;
+mod m20240525_083127_add_idempotency_key;
+mod m20240601_150322_add_zone;
+mod m20240608_094417_add_device;
+mod m20240615_081254_add_sensor_reading;
+mod m20240622_140538_add_device_profile;
 
 pub struct Migrator;
 
@@ -13,6 +25,18 @@ impl MigratorTrait for Migrator {
             Box::new(m20240317_190601_create_base_schema::Migration),
             Box::new(m20240330_012419_add_light::Migration),
             Box::new(m20240331_095824_change_light_state::Migration),
+            Box::new(m20240406_113012_add_user_identity::Migration),
+            Box::new(m20240413_094511_add_oauth::Migration),
+            Box::new(m20240420_160245_add_user_admin::Migration),
+            Box::new(m20240427_102214_add_registration_invite::Migration),
+            Box::new(m20240504_141907_add_email_verification::Migration),
+            Box::new(m20240511_093015_add_light_timestamps::Migration),
+            Box::new(m20240518_101544_add_versions::Migration),
+            Box::new(m20240525_083127_add_idempotency_key::Migration),
+            Box::new(m20240601_150322_add_zone::Migration),
+            Box::new(m20240608_094417_add_device::Migration),
+            Box::new(m20240615_081254_add_sensor_reading::Migration),
+            Box::new(m20240622_140538_add_device_profile::Migration),
         ]
     }
 }
@@ -129,7 +129,7 @@ impl MigrationTrait for Migration {
 }
 
 #[derive(DeriveIden)]
-enum AppUser {
+pub(crate) enum AppUser {
     Table,
     Id,
     Name,
@@ -141,7 +141,7 @@ enum AppUser {
 }
 
 #[derive(DeriveIden)]
-enum Location {
+pub(crate) enum Location {
     Table,
     Id,
     Name,
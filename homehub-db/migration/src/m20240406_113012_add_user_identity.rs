@@ -0,0 +1,76 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20240317_190601_create_base_schema::{AppUser, GenerateUuid};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserIdentity::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(UserIdentity::Id)
+                            .uuid()
+                            .not_null()
+                            .default(SimpleExpr::FunctionCall(Func::cust(
+                                GenerateUuid,
+                            )))
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(UserIdentity::UserId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(UserIdentity::Issuer)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UserIdentity::Subject)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UserIdentity::CreatedAt)
+                            .timestamp()
+                            .default(SimpleExpr::Keyword(
+                                Keyword::CurrentTimestamp,
+                            )),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("user_identity_user_id_fk")
+                            .from(UserIdentity::Table, UserIdentity::UserId)
+                            .to(AppUser::Table, AppUser::Id),
+                    )
+                    .index(
+                        Index::create()
+                            .name("user_identity_issuer_subject_idx")
+                            .col(UserIdentity::Issuer)
+                            .col(UserIdentity::Subject)
+                            .unique(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(UserIdentity::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UserIdentity {
+    Table,
+    Id,
+    UserId,
+    Issuer,
+    Subject,
+    CreatedAt,
+}
@@ -0,0 +1,207 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20240317_190601_create_base_schema::{AppUser, GenerateUuid};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(OauthClient::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(OauthClient::Id)
+                            .uuid()
+                            .not_null()
+                            .default(SimpleExpr::FunctionCall(Func::cust(
+                                GenerateUuid,
+                            )))
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(OauthClient::Name).string().not_null())
+                    .col(ColumnDef::new(OauthClient::SecretHash).string())
+                    .col(
+                        ColumnDef::new(OauthClient::RedirectUris)
+                            .json()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(OauthClient::Scope).string().not_null())
+                    .col(ColumnDef::new(OauthClient::OwnerId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(OauthClient::CreatedAt)
+                            .timestamp()
+                            .default(SimpleExpr::Keyword(
+                                Keyword::CurrentTimestamp,
+                            )),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("oauth_client_owner_id_fk")
+                            .from(OauthClient::Table, OauthClient::OwnerId)
+                            .to(AppUser::Table, AppUser::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(OauthAuthorizationCode::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(OauthAuthorizationCode::CodeHash)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(OauthAuthorizationCode::ClientId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(OauthAuthorizationCode::UserId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(OauthAuthorizationCode::RedirectUri)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(OauthAuthorizationCode::Scope)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(OauthAuthorizationCode::CodeChallenge)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(OauthAuthorizationCode::ExpiresAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("oauth_authorization_code_client_id_fk")
+                            .from(
+                                OauthAuthorizationCode::Table,
+                                OauthAuthorizationCode::ClientId,
+                            )
+                            .to(OauthClient::Table, OauthClient::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("oauth_authorization_code_user_id_fk")
+                            .from(
+                                OauthAuthorizationCode::Table,
+                                OauthAuthorizationCode::UserId,
+                            )
+                            .to(AppUser::Table, AppUser::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(OauthConsent::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(OauthConsent::UserId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(OauthConsent::ClientId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(OauthConsent::Scope).string().not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(OauthConsent::CreatedAt)
+                            .timestamp()
+                            .default(SimpleExpr::Keyword(
+                                Keyword::CurrentTimestamp,
+                            )),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(OauthConsent::UserId)
+                            .col(OauthConsent::ClientId),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("oauth_consent_user_id_fk")
+                            .from(OauthConsent::Table, OauthConsent::UserId)
+                            .to(AppUser::Table, AppUser::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("oauth_consent_client_id_fk")
+                            .from(OauthConsent::Table, OauthConsent::ClientId)
+                            .to(OauthClient::Table, OauthClient::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(OauthConsent::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(OauthAuthorizationCode::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(OauthClient::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum OauthClient {
+    Table,
+    Id,
+    Name,
+    SecretHash,
+    RedirectUris,
+    Scope,
+    OwnerId,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum OauthAuthorizationCode {
+    Table,
+    CodeHash,
+    ClientId,
+    UserId,
+    RedirectUri,
+    Scope,
+    CodeChallenge,
+    ExpiresAt,
+}
+
+#[derive(DeriveIden)]
+enum OauthConsent {
+    Table,
+    UserId,
+    ClientId,
+    Scope,
+    CreatedAt,
+}
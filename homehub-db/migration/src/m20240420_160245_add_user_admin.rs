@@ -0,0 +1,66 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20240317_190601_create_base_schema::AppUser;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(AppUser::Table)
+                    .add_column(
+                        ColumnDef::new(UserAdmin::IsAdmin)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .add_column(
+                        ColumnDef::new(UserAdmin::DisabledAt).timestamp(),
+                    )
+                    .add_column(
+                        ColumnDef::new(UserAdmin::PasswordResetRequired)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .add_column(
+                        ColumnDef::new(UserAdmin::PasswordResetTokenHash)
+                            .string(),
+                    )
+                    .add_column(
+                        ColumnDef::new(UserAdmin::PasswordResetExpiresAt)
+                            .timestamp(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(AppUser::Table)
+                    .drop_column(UserAdmin::IsAdmin)
+                    .drop_column(UserAdmin::DisabledAt)
+                    .drop_column(UserAdmin::PasswordResetRequired)
+                    .drop_column(UserAdmin::PasswordResetTokenHash)
+                    .drop_column(UserAdmin::PasswordResetExpiresAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UserAdmin {
+    IsAdmin,
+    DisabledAt,
+    PasswordResetRequired,
+    PasswordResetTokenHash,
+    PasswordResetExpiresAt,
+}
@@ -0,0 +1,69 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20240317_190601_create_base_schema::AppUser;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RegistrationInvite::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RegistrationInvite::TokenHash)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(RegistrationInvite::CreatedBy)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RegistrationInvite::ExpiresAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RegistrationInvite::CreatedAt)
+                            .timestamp()
+                            .default(SimpleExpr::Keyword(
+                                Keyword::CurrentTimestamp,
+                            )),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("registration_invite_created_by_fk")
+                            .from(
+                                RegistrationInvite::Table,
+                                RegistrationInvite::CreatedBy,
+                            )
+                            .to(AppUser::Table, AppUser::Id),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop().table(RegistrationInvite::Table).to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum RegistrationInvite {
+    Table,
+    TokenHash,
+    CreatedBy,
+    ExpiresAt,
+    CreatedAt,
+}
@@ -0,0 +1,50 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20240317_190601_create_base_schema::AppUser;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(AppUser::Table)
+                    .add_column(
+                        ColumnDef::new(UserEmail::PendingEmail).string(),
+                    )
+                    .add_column(
+                        ColumnDef::new(UserEmail::EmailVerificationTokenHash)
+                            .string(),
+                    )
+                    .add_column(
+                        ColumnDef::new(UserEmail::EmailVerificationExpiresAt)
+                            .timestamp(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(AppUser::Table)
+                    .drop_column(UserEmail::PendingEmail)
+                    .drop_column(UserEmail::EmailVerificationTokenHash)
+                    .drop_column(UserEmail::EmailVerificationExpiresAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UserEmail {
+    PendingEmail,
+    EmailVerificationTokenHash,
+    EmailVerificationExpiresAt,
+}
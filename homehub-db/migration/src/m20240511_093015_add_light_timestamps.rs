@@ -0,0 +1,89 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20240330_012419_add_light::Light;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Light::Table)
+                    .add_column(
+                        ColumnDef::new(LightTimestamps::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(SimpleExpr::Keyword(
+                                Keyword::CurrentTimestamp,
+                            )),
+                    )
+                    .add_column(
+                        ColumnDef::new(LightTimestamps::UpdatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(SimpleExpr::Keyword(
+                                Keyword::CurrentTimestamp,
+                            )),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("light_updated_at_idx")
+                    .table(Light::Table)
+                    .col(LightTimestamps::UpdatedAt)
+                    .col(Light::Id)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("light_name_idx")
+                    .table(Light::Table)
+                    .col(Light::Name)
+                    .col(Light::Id)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("light_name_idx")
+                    .table(Light::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("light_updated_at_idx")
+                    .table(Light::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Light::Table)
+                    .drop_column(LightTimestamps::CreatedAt)
+                    .drop_column(LightTimestamps::UpdatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum LightTimestamps {
+    CreatedAt,
+    UpdatedAt,
+}
@@ -0,0 +1,85 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20240317_190601_create_base_schema::AppUser;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(IdempotencyKey::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(IdempotencyKey::UserId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(IdempotencyKey::Key).string().not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(IdempotencyKey::Fingerprint)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(IdempotencyKey::ResponseStatus)
+                            .integer(),
+                    )
+                    .col(ColumnDef::new(IdempotencyKey::ResponseHeaders).json())
+                    .col(ColumnDef::new(IdempotencyKey::ResponseBody).binary())
+                    .col(
+                        ColumnDef::new(IdempotencyKey::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(SimpleExpr::Keyword(
+                                Keyword::CurrentTimestamp,
+                            )),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(IdempotencyKey::UserId)
+                            .col(IdempotencyKey::Key),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("idempotency_key_user_id_fk")
+                            .from(IdempotencyKey::Table, IdempotencyKey::UserId)
+                            .to(AppUser::Table, AppUser::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idempotency_key_created_at_idx")
+                    .table(IdempotencyKey::Table)
+                    .col(IdempotencyKey::CreatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(IdempotencyKey::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum IdempotencyKey {
+    Table,
+    UserId,
+    Key,
+    Fingerprint,
+    ResponseStatus,
+    ResponseHeaders,
+    ResponseBody,
+    CreatedAt,
+}
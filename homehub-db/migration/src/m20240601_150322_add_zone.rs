@@ -0,0 +1,126 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20240317_190601_create_base_schema::GenerateUuid;
+use crate::m20240330_012419_add_light::Light;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Zone::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Zone::Id)
+                            .uuid()
+                            .not_null()
+                            .default(SimpleExpr::FunctionCall(Func::cust(
+                                GenerateUuid,
+                            )))
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Zone::Name).string().not_null())
+                    .col(
+                        ColumnDef::new(Zone::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(SimpleExpr::Keyword(
+                                Keyword::CurrentTimestamp,
+                            )),
+                    )
+                    .col(
+                        ColumnDef::new(Zone::UpdatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(SimpleExpr::Keyword(
+                                Keyword::CurrentTimestamp,
+                            )),
+                    )
+                    .col(
+                        ColumnDef::new(Zone::Version)
+                            .integer()
+                            .not_null()
+                            .default(1),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("zone_name_idx")
+                    .table(Zone::Table)
+                    .col(Zone::Name)
+                    .col(Zone::Id)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ZoneLight::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(ZoneLight::ZoneId).uuid().not_null())
+                    .col(ColumnDef::new(ZoneLight::LightId).uuid().not_null())
+                    .primary_key(
+                        Index::create()
+                            .col(ZoneLight::ZoneId)
+                            .col(ZoneLight::LightId),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("zone_light_zone_id_fk")
+                            .from(ZoneLight::Table, ZoneLight::ZoneId)
+                            .to(Zone::Table, Zone::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("zone_light_light_id_fk")
+                            .from(ZoneLight::Table, ZoneLight::LightId)
+                            .to(Light::Table, Light::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("zone_light_light_id_idx")
+                    .table(ZoneLight::Table)
+                    .col(ZoneLight::LightId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ZoneLight::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Zone::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Zone {
+    Table,
+    Id,
+    Name,
+    CreatedAt,
+    UpdatedAt,
+    Version,
+}
+
+#[derive(DeriveIden)]
+enum ZoneLight {
+    Table,
+    ZoneId,
+    LightId,
+}
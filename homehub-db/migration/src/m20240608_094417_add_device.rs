@@ -0,0 +1,75 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// The capabilities every light had before devices existed.
+const LIGHT_CAPABILITIES: &str = r#"'["on_off","colour"]'::json"#;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Lights become devices of kind `light`. Rooms and zones keep
+        // pointing at the same rows, so their memberships carry over.
+        db.execute_unprepared("ALTER TABLE light RENAME TO device")
+            .await?;
+        db.execute_unprepared(&format!(
+            "ALTER TABLE device
+                ADD COLUMN kind varchar NOT NULL DEFAULT 'light',
+                ADD COLUMN capabilities json NOT NULL
+                    DEFAULT {LIGHT_CAPABILITIES}"
+        ))
+        .await?;
+        db.execute_unprepared(
+            "ALTER TABLE device
+                ALTER COLUMN kind DROP DEFAULT,
+                ALTER COLUMN capabilities DROP DEFAULT",
+        )
+        .await?;
+        db.execute_unprepared("CREATE INDEX device_kind_idx ON device (kind)")
+            .await?;
+
+        // The light API keeps working on a view of the lights, which
+        // Postgres can update and insert into like the old table.
+        db.execute_unprepared(
+            "CREATE VIEW light AS
+                SELECT id, name, state, created_at, updated_at, version,
+                    kind, capabilities
+                FROM device
+                WHERE kind = 'light'
+                WITH CHECK OPTION",
+        )
+        .await?;
+        db.execute_unprepared(&format!(
+            "ALTER VIEW light ALTER COLUMN kind SET DEFAULT 'light';
+            ALTER VIEW light ALTER COLUMN capabilities
+                SET DEFAULT {LIGHT_CAPABILITIES}"
+        ))
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared("DROP VIEW light").await?;
+        for table in ["room_light", "zone_light"] {
+            db.execute_unprepared(&format!(
+                "DELETE FROM {table} WHERE light_id IN
+                    (SELECT id FROM device WHERE kind <> 'light')"
+            ))
+            .await?;
+        }
+        db.execute_unprepared("DELETE FROM device WHERE kind <> 'light'")
+            .await?;
+        db.execute_unprepared(
+            "ALTER TABLE device DROP COLUMN kind, DROP COLUMN capabilities",
+        )
+        .await?;
+        db.execute_unprepared("ALTER TABLE device RENAME TO light")
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Readings are only ever appended, and read back by device, metric
+        // and time range, which is exactly the order of the primary key.
+        manager
+            .create_table(
+                Table::create()
+                    .table(SensorReading::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(SensorReading::DeviceId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(SensorReading::Metric)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(SensorReading::RecordedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(SensorReading::Value)
+                            .double()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(SensorReading::DeviceId)
+                            .col(SensorReading::Metric)
+                            .col(SensorReading::RecordedAt),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("sensor_reading_device_id_fk")
+                            .from(SensorReading::Table, SensorReading::DeviceId)
+                            .to(Device::Table, Device::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        // Rows arrive roughly in time order, so a BRIN index stays tiny while
+        // still making it cheap to find old readings across all devices.
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "CREATE INDEX sensor_reading_recorded_at_idx
+                    ON sensor_reading USING brin (recorded_at)",
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SensorReading::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Device {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum SensorReading {
+    Table,
+    DeviceId,
+    Metric,
+    RecordedAt,
+    Value,
+}
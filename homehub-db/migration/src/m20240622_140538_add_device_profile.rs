@@ -0,0 +1,55 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Profiles live in a catalogue shipped with the server rather than
+        // in the database, so the column is a plain id without a foreign
+        // key.
+        db.execute_unprepared(
+            "ALTER TABLE device ADD COLUMN profile_id varchar NULL",
+        )
+        .await?;
+        db.execute_unprepared(
+            "CREATE OR REPLACE VIEW light AS
+                SELECT id, name, state, created_at, updated_at, version,
+                    kind, capabilities, profile_id
+                FROM device
+                WHERE kind = 'light'
+                WITH CHECK OPTION",
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // A view can only lose columns by being recreated, which also loses
+        // its defaults.
+        db.execute_unprepared("DROP VIEW light").await?;
+        db.execute_unprepared(
+            "CREATE VIEW light AS
+                SELECT id, name, state, created_at, updated_at, version,
+                    kind, capabilities
+                FROM device
+                WHERE kind = 'light'
+                WITH CHECK OPTION",
+        )
+        .await?;
+        db.execute_unprepared(
+            r#"ALTER VIEW light ALTER COLUMN kind SET DEFAULT 'light';
+            ALTER VIEW light ALTER COLUMN capabilities
+                SET DEFAULT '["on_off","colour"]'::json"#,
+        )
+        .await?;
+        db.execute_unprepared("ALTER TABLE device DROP COLUMN profile_id")
+            .await?;
+        Ok(())
+    }
+}
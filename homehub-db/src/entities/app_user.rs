@@ -16,9 +16,66 @@ pub struct Model {
     pub created_at: Option<DateTime>,
     pub updated_at: Option<DateTime>,
     pub locale: Option<String>,
+    pub is_admin: bool,
+    pub disabled_at: Option<DateTime>,
+    pub password_reset_required: bool,
+    pub password_reset_token_hash: Option<String>,
+    pub password_reset_expires_at: Option<DateTime>,
+    pub pending_email: Option<String>,
+    pub email_verification_token_hash: Option<String>,
+    pub email_verification_expires_at: Option<DateTime>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(has_many = "super::idempotency_key::Entity")]
+    IdempotencyKey,
+    #[sea_orm(has_many = "super::oauth_authorization_code::Entity")]
+    OauthAuthorizationCode,
+    #[sea_orm(has_many = "super::oauth_client::Entity")]
+    OauthClient,
+    #[sea_orm(has_many = "super::oauth_consent::Entity")]
+    OauthConsent,
+    #[sea_orm(has_many = "super::registration_invite::Entity")]
+    RegistrationInvite,
+    #[sea_orm(has_many = "super::user_identity::Entity")]
+    UserIdentity,
+}
+
+impl Related<super::idempotency_key::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::IdempotencyKey.def()
+    }
+}
+
+impl Related<super::oauth_authorization_code::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OauthAuthorizationCode.def()
+    }
+}
+
+impl Related<super::oauth_client::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OauthClient.def()
+    }
+}
+
+impl Related<super::oauth_consent::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OauthConsent.def()
+    }
+}
+
+impl Related<super::registration_invite::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RegistrationInvite.def()
+    }
+}
+
+impl Related<super::user_identity::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserIdentity.def()
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}
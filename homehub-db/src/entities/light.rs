@@ -3,6 +3,7 @@
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
+pub use crate::extra_models::device::Capabilities;
 pub use crate::extra_models::light::LightState;
 
 #[derive(
@@ -14,12 +15,19 @@ pub struct Model {
     pub id: Uuid,
     pub name: String,
     pub state: LightState,
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+    pub version: i32,
+    pub capabilities: Capabilities,
+    pub profile_id: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
     #[sea_orm(has_many = "super::room_light::Entity")]
     RoomLight,
+    #[sea_orm(has_many = "super::zone_light::Entity")]
+    ZoneLight,
 }
 
 impl Related<super::room_light::Entity> for Entity {
@@ -37,4 +45,19 @@ impl Related<super::room::Entity> for Entity {
     }
 }
 
+impl Related<super::zone_light::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ZoneLight.def()
+    }
+}
+
+impl Related<super::zone::Entity> for Entity {
+    fn to() -> RelationDef {
+        super::zone_light::Relation::Zone.def()
+    }
+    fn via() -> Option<RelationDef> {
+        Some(super::zone_light::Relation::Light.def().rev())
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -13,6 +13,7 @@ pub struct Model {
     pub name: String,
     pub created_at: Option<DateTime>,
     pub updated_at: Option<DateTime>,
+    pub version: i32,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
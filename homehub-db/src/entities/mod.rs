@@ -3,7 +3,17 @@
 pub mod prelude;
 
 pub mod app_user;
+pub mod device;
+pub mod idempotency_key;
 pub mod light;
 pub mod location;
+pub mod oauth_authorization_code;
+pub mod oauth_client;
+pub mod oauth_consent;
+pub mod registration_invite;
 pub mod room;
 pub mod room_light;
+pub mod sensor_reading;
+pub mod user_identity;
+pub mod zone;
+pub mod zone_light;
@@ -1,7 +1,17 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.15
 
 pub use super::app_user::Entity as AppUser;
+pub use super::device::Entity as Device;
+pub use super::idempotency_key::Entity as IdempotencyKey;
 pub use super::light::Entity as Light;
 pub use super::location::Entity as Location;
+pub use super::oauth_authorization_code::Entity as OauthAuthorizationCode;
+pub use super::oauth_client::Entity as OauthClient;
+pub use super::oauth_consent::Entity as OauthConsent;
+pub use super::registration_invite::Entity as RegistrationInvite;
 pub use super::room::Entity as Room;
 pub use super::room_light::Entity as RoomLight;
+pub use super::sensor_reading::Entity as SensorReading;
+pub use super::user_identity::Entity as UserIdentity;
+pub use super::zone::Entity as Zone;
+pub use super::zone_light::Entity as ZoneLight;
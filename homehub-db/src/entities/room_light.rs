@@ -16,6 +16,14 @@ pub struct Model {
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::device::Entity",
+        from = "Column::LightId",
+        to = "super::device::Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    Device,
     #[sea_orm(
         belongs_to = "super::light::Entity",
         from = "Column::LightId",
@@ -34,6 +42,12 @@ pub enum Relation {
     Room,
 }
 
+impl Related<super::device::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Device.def()
+    }
+}
+
 impl Related<super::light::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Light.def()
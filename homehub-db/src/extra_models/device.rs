@@ -0,0 +1,188 @@
+use sea_orm::{DeriveActiveEnum, EnumIter, FromJsonQueryResult};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    EnumIter,
+    DeriveActiveEnum,
+    Serialize,
+    Deserialize,
+    ToSchema,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(None)")]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceKind {
+    #[sea_orm(string_value = "light")]
+    Light,
+    #[sea_orm(string_value = "switch")]
+    Switch,
+    #[sea_orm(string_value = "plug")]
+    Plug,
+    #[sea_orm(string_value = "sensor")]
+    Sensor,
+    #[sea_orm(string_value = "thermostat")]
+    Thermostat,
+}
+
+/// Something a device can do or report. Each capability owns one field of
+/// the device's state.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// `on`
+    OnOff,
+    /// `brightness`, in percent.
+    Dimmable,
+    /// `colour`, as RGB.
+    Colour,
+    /// `temperature`, in degrees Celsius.
+    TemperatureReading,
+    /// `humidity`, the relative humidity in percent.
+    HumidityReading,
+    /// `illuminance`, in lux.
+    IlluminanceReading,
+    /// `power`, the current draw in watts.
+    PowerReading,
+    /// `setpoint`, the target temperature in degrees Celsius.
+    Setpoint,
+    /// `battery`, in percent.
+    Battery,
+    /// `open`, whether a door or window contact is open.
+    Contact,
+    /// `motion`, whether motion is currently detected.
+    Motion,
+}
+
+impl Capability {
+    /// The name used for the capability in JSON.
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::OnOff => "on_off",
+            Capability::Dimmable => "dimmable",
+            Capability::Colour => "colour",
+            Capability::TemperatureReading => "temperature_reading",
+            Capability::HumidityReading => "humidity_reading",
+            Capability::IlluminanceReading => "illuminance_reading",
+            Capability::PowerReading => "power_reading",
+            Capability::Setpoint => "setpoint",
+            Capability::Battery => "battery",
+            Capability::Contact => "contact",
+            Capability::Motion => "motion",
+        }
+    }
+}
+
+#[derive(
+    Clone, Debug, PartialEq, Eq, Serialize, Deserialize, FromJsonQueryResult,
+)]
+pub struct Capabilities(pub Vec<Capability>);
+
+/// The state of a device. Only the fields of the device's capabilities are
+/// ever set.
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    FromJsonQueryResult,
+    ToSchema,
+)]
+pub struct DeviceState {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brightness: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub colour: Option<[u8; 3]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub humidity: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub illuminance: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub power: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub setpoint: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub battery: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub motion: Option<bool>,
+}
+
+/// A quantity a sensor measures over time. Each metric is reported by the
+/// capability of the same name, which owns the latest value in the state.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    EnumIter,
+    DeriveActiveEnum,
+    Serialize,
+    Deserialize,
+    ToSchema,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(None)")]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    /// In degrees Celsius.
+    #[sea_orm(string_value = "temperature")]
+    Temperature,
+    /// Relative humidity in percent.
+    #[sea_orm(string_value = "humidity")]
+    Humidity,
+    /// In lux.
+    #[sea_orm(string_value = "illuminance")]
+    Illuminance,
+    /// In watts.
+    #[sea_orm(string_value = "power")]
+    Power,
+}
+
+impl Metric {
+    pub fn capability(self) -> Capability {
+        match self {
+            Metric::Temperature => Capability::TemperatureReading,
+            Metric::Humidity => Capability::HumidityReading,
+            Metric::Illuminance => Capability::IlluminanceReading,
+            Metric::Power => Capability::PowerReading,
+        }
+    }
+
+    /// Sets the field of the state this metric owns.
+    pub fn set(self, state: &mut DeviceState, value: f64) {
+        *match self {
+            Metric::Temperature => &mut state.temperature,
+            Metric::Humidity => &mut state.humidity,
+            Metric::Illuminance => &mut state.illuminance,
+            Metric::Power => &mut state.power,
+        } = Some(value);
+    }
+}
@@ -1 +1,3 @@
+pub mod device;
 pub mod light;
+pub mod oauth;
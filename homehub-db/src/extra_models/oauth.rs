@@ -0,0 +1,7 @@
+use sea_orm::FromJsonQueryResult;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    Clone, Debug, PartialEq, Eq, Serialize, Deserialize, FromJsonQueryResult,
+)]
+pub struct RedirectUris(pub Vec<String>);
@@ -1,5 +1,6 @@
 mod entities;
 pub mod extra_models;
+pub mod pagination;
 pub mod queries;
 pub use entities::prelude::*;
 pub use entities::*;
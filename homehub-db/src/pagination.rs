@@ -0,0 +1,366 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use sea_orm::{
+    prelude::{DateTime, Uuid},
+    sea_query::Condition,
+    ColumnTrait, DatabaseConnection, EntityTrait, Order, QueryFilter,
+    QueryOrder, QuerySelect, Select, Value,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use utoipa::ToSchema;
+
+pub const DEFAULT_PAGE_LIMIT: u64 = 50;
+pub const MAX_PAGE_LIMIT: u64 = 200;
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// A field a collection can be sorted by. Items with the same value are
+/// ordered by id, so that every item has a distinct position to resume from.
+pub trait SortKey:
+    Copy + PartialEq + Serialize + DeserializeOwned + Send + Sync
+{
+    type Entity: EntityTrait;
+
+    fn column(self) -> <Self::Entity as EntityTrait>::Column;
+
+    fn value(self, model: &<Self::Entity as EntityTrait>::Model) -> SortValue;
+
+    fn id_column() -> <Self::Entity as EntityTrait>::Column;
+
+    fn id(model: &<Self::Entity as EntityTrait>::Model) -> Uuid;
+}
+
+/// The value of a sort key, as remembered by a cursor.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SortValue {
+    Text(String),
+    Time(DateTime),
+}
+
+impl From<SortValue> for Value {
+    fn from(value: SortValue) -> Self {
+        match value {
+            SortValue::Text(text) => text.into(),
+            SortValue::Time(time) => time.into(),
+        }
+    }
+}
+
+/// The position of the last item of a page. Clients get it as an opaque
+/// string and send it back for the next page.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Cursor<S> {
+    sort: S,
+    order: SortOrder,
+    value: SortValue,
+    id: Uuid,
+}
+
+impl<S: SortKey> Cursor<S> {
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    fn decode(cursor: &str) -> Option<Self> {
+        let json = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+}
+
+/// Which page of a collection to fetch, and in what order.
+#[derive(Clone, Debug)]
+pub struct PageRequest<S> {
+    pub sort: S,
+    pub order: SortOrder,
+    pub after: Option<Cursor<S>>,
+    pub limit: u64,
+}
+
+impl<S: SortKey> PageRequest<S> {
+    /// Returns `None` when the cursor is malformed or was issued for a
+    /// different sort. The limit is clamped to `MAX_PAGE_LIMIT`.
+    pub fn new(
+        sort: S,
+        order: SortOrder,
+        after: Option<&str>,
+        limit: Option<u64>,
+    ) -> Option<Self> {
+        let after = match after {
+            Some(after) => Some(Cursor::decode(after).filter(|cursor| {
+                cursor.sort == sort && cursor.order == order
+            })?),
+            None => None,
+        };
+        Some(PageRequest {
+            sort,
+            order,
+            after,
+            limit: limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT),
+        })
+    }
+}
+
+/// One page of a collection. `next_cursor` is `None` on the last page.
+#[derive(Clone, Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub limit: u64,
+}
+
+impl<T> Page<T> {
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            next_cursor: self.next_cursor,
+            limit: self.limit,
+        }
+    }
+}
+
+/// Fetches one page of `select`, which should be filtered but not ordered.
+pub async fn paginate<S: SortKey>(
+    mut select: Select<S::Entity>,
+    page: &PageRequest<S>,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Page<<S::Entity as EntityTrait>::Model>> {
+    let column = page.sort.column();
+    let id_column = S::id_column();
+    if let Some(after) = &page.after {
+        let value: Value = after.value.clone().into();
+        let condition = match page.order {
+            SortOrder::Asc => Condition::any()
+                .add(column.gt(value.clone()))
+                .add(column.eq(value).and(id_column.gt(after.id))),
+            SortOrder::Desc => Condition::any()
+                .add(column.lt(value.clone()))
+                .add(column.eq(value).and(id_column.lt(after.id))),
+        };
+        select = select.filter(condition);
+    }
+    let order = match page.order {
+        SortOrder::Asc => Order::Asc,
+        SortOrder::Desc => Order::Desc,
+    };
+
+    // Fetch one more than asked for, to know whether there is a next page.
+    let mut items = select
+        .order_by(column, order.clone())
+        .order_by(id_column, order)
+        .limit(page.limit + 1)
+        .all(db)
+        .await?;
+    let next_cursor = match items.len() as u64 > page.limit {
+        true => {
+            items.truncate(page.limit as usize);
+            items.last().map(|last| {
+                Cursor {
+                    sort: page.sort,
+                    order: page.order,
+                    value: page.sort.value(last),
+                    id: S::id(last),
+                }
+                .encode()
+            })
+        }
+        false => None,
+    };
+
+    Ok(Page {
+        items,
+        next_cursor,
+        limit: page.limit,
+    })
+}
+
+/// A case-insensitive `LIKE` pattern matching values that contain `query`.
+pub(crate) fn contains_pattern(query: &str) -> String {
+    format!(
+        "%{}%",
+        query
+            .to_lowercase()
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    use super::*;
+    use crate::entities::room;
+    use crate::queries::room::RoomSort;
+
+    fn room(id: u128, name: &str) -> room::Model {
+        room::Model {
+            id: Uuid::from_u128(id),
+            name: name.to_string(),
+            created_at: None,
+            updated_at: None,
+            location_id: Uuid::nil(),
+            version: 1,
+        }
+    }
+
+    fn cursor(name: &str, id: u128, order: SortOrder) -> String {
+        Cursor {
+            sort: RoomSort::Name,
+            order,
+            value: SortValue::Text(name.to_string()),
+            id: Uuid::from_u128(id),
+        }
+        .encode()
+    }
+
+    /// Fetches a page from a database that answers with `rows`, returning
+    /// the page and the SQL that was run.
+    async fn fetch(
+        page: &PageRequest<RoomSort>,
+        rows: Vec<room::Model>,
+    ) -> (Page<room::Model>, String) {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([rows])
+            .into_connection();
+        let page = paginate(room::Entity::find(), page, &db).await.unwrap();
+        let sql = format!("{:?}", db.into_transaction_log());
+        (page, sql)
+    }
+
+    #[test]
+    fn cursors_round_trip() {
+        let encoded = cursor("Kitchen", 7, SortOrder::Desc);
+
+        let page = PageRequest::new(
+            RoomSort::Name,
+            SortOrder::Desc,
+            Some(&encoded),
+            None,
+        )
+        .unwrap();
+
+        let after = page.after.unwrap();
+        assert_eq!(after.value, SortValue::Text("Kitchen".to_string()));
+        assert_eq!(after.id, Uuid::from_u128(7));
+    }
+
+    #[test]
+    fn rejects_malformed_cursors() {
+        for cursor in ["not base64!", "bm90IGpzb24"] {
+            assert!(PageRequest::new(
+                RoomSort::Name,
+                SortOrder::Asc,
+                Some(cursor),
+                None
+            )
+            .is_none());
+        }
+    }
+
+    #[test]
+    fn rejects_cursors_of_another_order() {
+        let encoded = cursor("Kitchen", 7, SortOrder::Desc);
+
+        assert!(PageRequest::new(
+            RoomSort::Name,
+            SortOrder::Asc,
+            Some(&encoded),
+            None
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn clamps_the_limit() {
+        let page = |limit| {
+            PageRequest::new(RoomSort::Name, SortOrder::Asc, None, limit)
+                .unwrap()
+                .limit
+        };
+
+        assert_eq!(page(None), DEFAULT_PAGE_LIMIT);
+        assert_eq!(page(Some(0)), 1);
+        assert_eq!(page(Some(MAX_PAGE_LIMIT + 1)), MAX_PAGE_LIMIT);
+    }
+
+    #[tokio::test]
+    async fn resumes_after_the_cursor_breaking_ties_by_id() {
+        let encoded = cursor("Kitchen", 7, SortOrder::Asc);
+        let page = PageRequest::new(
+            RoomSort::Name,
+            SortOrder::Asc,
+            Some(&encoded),
+            None,
+        )
+        .unwrap();
+
+        let (_, sql) = fetch(&page, vec![]).await;
+
+        assert!(sql.contains(
+            r#"WHERE \"room\".\"name\" > $1 OR (\"room\".\"name\" = $2 AND \"room\".\"id\" > $3)"#
+        ));
+        assert!(sql.contains(
+            r#"ORDER BY \"room\".\"name\" ASC, \"room\".\"id\" ASC"#
+        ));
+    }
+
+    #[tokio::test]
+    async fn resumes_before_the_cursor_when_descending() {
+        let encoded = cursor("Kitchen", 7, SortOrder::Desc);
+        let page = PageRequest::new(
+            RoomSort::Name,
+            SortOrder::Desc,
+            Some(&encoded),
+            None,
+        )
+        .unwrap();
+
+        let (_, sql) = fetch(&page, vec![]).await;
+
+        assert!(sql.contains(
+            r#"WHERE \"room\".\"name\" < $1 OR (\"room\".\"name\" = $2 AND \"room\".\"id\" < $3)"#
+        ));
+        assert!(sql.contains(
+            r#"ORDER BY \"room\".\"name\" DESC, \"room\".\"id\" DESC"#
+        ));
+    }
+
+    #[tokio::test]
+    async fn points_the_next_cursor_at_the_last_item_of_a_tie() {
+        let page =
+            PageRequest::new(RoomSort::Name, SortOrder::Asc, None, Some(2))
+                .unwrap();
+        // One more row than the limit, all with the same name.
+        let rows = vec![room(1, "Hall"), room(2, "Hall"), room(3, "Hall")];
+
+        let (page, sql) = fetch(&page, rows).await;
+
+        assert!(sql.contains("LIMIT $1"));
+        assert_eq!(page.items.len(), 2);
+        let next_cursor =
+            Cursor::<RoomSort>::decode(&page.next_cursor.unwrap()).unwrap();
+        assert_eq!(next_cursor.value, SortValue::Text("Hall".to_string()));
+        assert_eq!(next_cursor.id, Uuid::from_u128(2));
+    }
+
+    #[tokio::test]
+    async fn has_no_next_cursor_on_the_last_page() {
+        let page =
+            PageRequest::new(RoomSort::Name, SortOrder::Asc, None, Some(2))
+                .unwrap();
+
+        let (page, _) =
+            fetch(&page, vec![room(1, "Hall"), room(2, "Hall")]).await;
+
+        assert_eq!(page.items.len(), 2);
+        assert!(page.next_cursor.is_none());
+    }
+}
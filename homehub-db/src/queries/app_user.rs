@@ -2,19 +2,28 @@ use crate::{
     app_user::{ActiveModel, Column, Model},
     AppUser as Entity,
 };
+use sea_orm::sea_query::{Condition, Expr, Func, LikeExpr};
 use sea_orm::ActiveModelTrait;
 use sea_orm::ColumnTrait;
 use sea_orm::EntityTrait;
 use sea_orm::QueryFilter;
+use sea_orm::{prelude::DateTime, PaginatorTrait, TransactionTrait};
 use sea_orm::{prelude::Uuid, ActiveValue, DatabaseConnection};
+use sea_orm::{ConnectionTrait, DatabaseTransaction, DbBackend, Statement};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+use crate::pagination::{
+    contains_pattern, paginate, Page, PageRequest, SortKey, SortValue,
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct FilteredAppUserModel {
     pub id: Uuid,
     pub name: String,
     pub email: String,
     pub locale: Option<String>,
+    pub pending_email: Option<String>,
 }
 
 impl From<Model> for FilteredAppUserModel {
@@ -24,6 +33,7 @@ impl From<Model> for FilteredAppUserModel {
             name: val.name.clone(),
             email: val.email.clone(),
             locale: val.locale.clone(),
+            pending_email: val.pending_email.clone(),
         }
     }
 }
@@ -32,19 +42,123 @@ pub async fn create_user(
     email: &str,
     password_hash: &str,
     locale: Option<&str>,
-    db: &DatabaseConnection,
+    is_admin: bool,
+    db: &impl ConnectionTrait,
 ) -> anyhow::Result<Model> {
     let user = ActiveModel {
         name: ActiveValue::Set(name.to_owned()),
         email: ActiveValue::Set(email.to_owned()),
         password_hash: ActiveValue::Set(password_hash.to_owned()),
         locale: ActiveValue::Set(locale.map(|s| s.to_owned())),
+        is_admin: ActiveValue::Set(is_admin),
         ..Default::default()
     };
 
     Ok(user.insert(db).await?)
 }
 
+/// An arbitrary key for the advisory lock taken while registering, which
+/// only has to be the same for every registration.
+const REGISTRATION_LOCK: i64 = 0x0068_6f6d_6568_7562;
+
+/// Holds every other registration back until the transaction ends, so that
+/// counting the users and adding one cannot interleave with another
+/// registration doing the same.
+async fn lock_registrations(txn: &DatabaseTransaction) -> anyhow::Result<()> {
+    txn.execute(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        "SELECT pg_advisory_xact_lock($1)",
+        [REGISTRATION_LOCK.into()],
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Registers a user in one transaction. With `first_user_admin`, the first
+/// user becomes an administrator without needing an invite. Anyone else
+/// has to redeem the invite with `invite_token_hash` when
+/// `invite_required`. Returns `None`, changing nothing, when that invite is
+/// missing, unknown or expired at `now`.
+#[allow(clippy::too_many_arguments)]
+pub async fn register_user(
+    name: &str,
+    email: &str,
+    password_hash: &str,
+    first_user_admin: bool,
+    invite_required: bool,
+    invite_token_hash: Option<&str>,
+    now: DateTime,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<Model>> {
+    let txn = db.begin().await?;
+    let user = register_user_in(
+        name,
+        email,
+        password_hash,
+        first_user_admin,
+        invite_required,
+        invite_token_hash.map(|hash| (hash, now)),
+        &txn,
+    )
+    .await?;
+    if user.is_some() {
+        txn.commit().await?;
+    }
+    Ok(user)
+}
+
+/// Does the work of [`register_user`] in a transaction of the caller's, so
+/// that more can be added to the account before it is committed. `invite`
+/// is the hash of the invite token and the time to check its expiry at.
+pub(crate) async fn register_user_in(
+    name: &str,
+    email: &str,
+    password_hash: &str,
+    first_user_admin: bool,
+    invite_required: bool,
+    invite: Option<(&str, DateTime)>,
+    txn: &DatabaseTransaction,
+) -> anyhow::Result<Option<Model>> {
+    lock_registrations(txn).await?;
+    let is_first_user =
+        first_user_admin && Entity::find().count(txn).await? == 0;
+    if invite_required && !is_first_user {
+        let Some((invite_token_hash, now)) = invite else {
+            return Ok(None);
+        };
+        let invite = crate::queries::registration_invite::take_invite(
+            invite_token_hash,
+            txn,
+        )
+        .await?;
+        if invite.is_none_or(|invite| invite.expires_at <= now) {
+            return Ok(None);
+        }
+    }
+    let user =
+        create_user(name, email, password_hash, None, is_first_user, txn)
+            .await?;
+    Ok(Some(user))
+}
+
+/// Creates an administrator, but only if there are no users at all yet.
+pub async fn create_first_user(
+    name: &str,
+    email: &str,
+    password_hash: &str,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<Model>> {
+    let txn = db.begin().await?;
+    lock_registrations(&txn).await?;
+    if Entity::find().count(&txn).await? > 0 {
+        return Ok(None);
+    }
+    let user =
+        create_user(name, email, password_hash, None, true, &txn).await?;
+    txn.commit().await?;
+    Ok(Some(user))
+}
+
 pub async fn find_user_by_email(
     email: &str,
     db: &DatabaseConnection,
@@ -63,3 +177,274 @@ pub async fn find_by_id(
     let user = Entity::find_by_id(id).one(db).await?;
     Ok(user)
 }
+
+/// A new email address waiting to be confirmed with the token whose hash
+/// is `token_hash`.
+pub struct PendingEmail<'a> {
+    pub email: &'a str,
+    pub token_hash: &'a str,
+    pub expires_at: DateTime,
+}
+
+/// Changes the given parts of a profile, all in one statement.
+pub async fn update_profile(
+    id: Uuid,
+    name: Option<&str>,
+    locale: Option<Option<&str>>,
+    pending_email: Option<PendingEmail<'_>>,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Model> {
+    let mut user = ActiveModel {
+        id: ActiveValue::Unchanged(id),
+        ..Default::default()
+    };
+    if let Some(name) = name {
+        user.name = ActiveValue::Set(name.to_owned());
+    }
+    if let Some(locale) = locale {
+        user.locale = ActiveValue::Set(locale.map(|s| s.to_owned()));
+    }
+    if let Some(pending_email) = pending_email {
+        user.pending_email =
+            ActiveValue::Set(Some(pending_email.email.to_owned()));
+        user.email_verification_token_hash =
+            ActiveValue::Set(Some(pending_email.token_hash.to_owned()));
+        user.email_verification_expires_at =
+            ActiveValue::Set(Some(pending_email.expires_at));
+    }
+    if !user.is_changed() {
+        return find_by_id(id, db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("User not found"));
+    }
+    Ok(user.update(db).await?)
+}
+
+pub async fn find_by_email_verification_token_hash(
+    token_hash: &str,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<Model>> {
+    let user = Entity::find()
+        .filter(Column::EmailVerificationTokenHash.eq(token_hash))
+        .one(db)
+        .await?;
+    Ok(user)
+}
+
+/// Replaces the user's email with their verified pending email.
+pub async fn confirm_pending_email(
+    id: Uuid,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Model> {
+    let user = find_by_id(id, db)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("User not found"))?;
+    let pending_email = user
+        .pending_email
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("No pending email"))?;
+    let mut user: ActiveModel = user.into();
+    user.email = ActiveValue::Set(pending_email);
+    user.pending_email = ActiveValue::Set(None);
+    user.email_verification_token_hash = ActiveValue::Set(None);
+    user.email_verification_expires_at = ActiveValue::Set(None);
+    Ok(user.update(db).await?)
+}
+
+pub async fn count_users(db: &DatabaseConnection) -> anyhow::Result<u64> {
+    Ok(Entity::find().count(db).await?)
+}
+
+/// The orders users can be listed in.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum UserSort {
+    #[default]
+    Email,
+    Name,
+}
+
+impl SortKey for UserSort {
+    type Entity = Entity;
+
+    fn column(self) -> Column {
+        match self {
+            UserSort::Email => Column::Email,
+            UserSort::Name => Column::Name,
+        }
+    }
+
+    fn value(self, model: &Model) -> SortValue {
+        match self {
+            UserSort::Email => SortValue::Text(model.email.clone()),
+            UserSort::Name => SortValue::Text(model.name.clone()),
+        }
+    }
+
+    fn id_column() -> Column {
+        Column::Id
+    }
+
+    fn id(model: &Model) -> Uuid {
+        model.id
+    }
+}
+
+/// Lists users whose name or email contains `query`, ignoring case.
+pub async fn search_users(
+    query: Option<&str>,
+    page: &PageRequest<UserSort>,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Page<Model>> {
+    let mut select = Entity::find();
+    if let Some(query) = query {
+        let pattern = contains_pattern(query);
+        select = select.filter(
+            Condition::any()
+                .add(
+                    Expr::expr(Func::lower(Expr::col(Column::Name)))
+                        .like(LikeExpr::new(&pattern).escape('\\')),
+                )
+                .add(
+                    Expr::expr(Func::lower(Expr::col(Column::Email)))
+                        .like(LikeExpr::new(&pattern).escape('\\')),
+                ),
+        );
+    }
+    paginate(select, page, db).await
+}
+
+pub async fn set_disabled_at(
+    id: Uuid,
+    disabled_at: Option<DateTime>,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<Model>> {
+    let Some(user) = find_by_id(id, db).await? else {
+        return Ok(None);
+    };
+    let mut user: ActiveModel = user.into();
+    user.disabled_at = ActiveValue::Set(disabled_at);
+    Ok(Some(user.update(db).await?))
+}
+
+pub async fn require_password_reset(
+    id: Uuid,
+    token_hash: &str,
+    expires_at: DateTime,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<Model>> {
+    let Some(user) = find_by_id(id, db).await? else {
+        return Ok(None);
+    };
+    let mut user: ActiveModel = user.into();
+    user.password_reset_required = ActiveValue::Set(true);
+    user.password_reset_token_hash =
+        ActiveValue::Set(Some(token_hash.to_owned()));
+    user.password_reset_expires_at = ActiveValue::Set(Some(expires_at));
+    Ok(Some(user.update(db).await?))
+}
+
+pub async fn find_by_password_reset_token_hash(
+    token_hash: &str,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<Model>> {
+    let user = Entity::find()
+        .filter(Column::PasswordResetTokenHash.eq(token_hash))
+        .one(db)
+        .await?;
+    Ok(user)
+}
+
+pub async fn update_password_hash(
+    id: Uuid,
+    password_hash: &str,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Model> {
+    let user = ActiveModel {
+        id: ActiveValue::Unchanged(id),
+        password_hash: ActiveValue::Set(password_hash.to_owned()),
+        ..Default::default()
+    };
+    Ok(user.update(db).await?)
+}
+
+/// Sets a new password and clears any pending password reset.
+pub async fn update_password(
+    id: Uuid,
+    password_hash: &str,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Model> {
+    let user = find_by_id(id, db)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("User not found"))?;
+    let mut user: ActiveModel = user.into();
+    user.password_hash = ActiveValue::Set(password_hash.to_owned());
+    user.password_reset_required = ActiveValue::Set(false);
+    user.password_reset_token_hash = ActiveValue::Set(None);
+    user.password_reset_expires_at = ActiveValue::Set(None);
+    Ok(user.update(db).await?)
+}
+
+/// Deletes a user along with their linked identities, OAuth clients,
+/// grants and outstanding invites, returning whether the user existed.
+pub async fn delete_user(
+    id: Uuid,
+    db: &DatabaseConnection,
+) -> anyhow::Result<bool> {
+    use crate::entities::{
+        idempotency_key, oauth_authorization_code, oauth_client, oauth_consent,
+        registration_invite, user_identity,
+    };
+
+    let txn = db.begin().await?;
+
+    let client_ids: Vec<Uuid> = oauth_client::Entity::find()
+        .filter(oauth_client::Column::OwnerId.eq(id))
+        .all(&txn)
+        .await?
+        .into_iter()
+        .map(|client| client.id)
+        .collect();
+
+    oauth_authorization_code::Entity::delete_many()
+        .filter(
+            Condition::any()
+                .add(oauth_authorization_code::Column::UserId.eq(id))
+                .add(
+                    oauth_authorization_code::Column::ClientId
+                        .is_in(client_ids.clone()),
+                ),
+        )
+        .exec(&txn)
+        .await?;
+    oauth_consent::Entity::delete_many()
+        .filter(
+            Condition::any()
+                .add(oauth_consent::Column::UserId.eq(id))
+                .add(oauth_consent::Column::ClientId.is_in(client_ids)),
+        )
+        .exec(&txn)
+        .await?;
+    oauth_client::Entity::delete_many()
+        .filter(oauth_client::Column::OwnerId.eq(id))
+        .exec(&txn)
+        .await?;
+    user_identity::Entity::delete_many()
+        .filter(user_identity::Column::UserId.eq(id))
+        .exec(&txn)
+        .await?;
+    registration_invite::Entity::delete_many()
+        .filter(registration_invite::Column::CreatedBy.eq(id))
+        .exec(&txn)
+        .await?;
+    idempotency_key::Entity::delete_many()
+        .filter(idempotency_key::Column::UserId.eq(id))
+        .exec(&txn)
+        .await?;
+    let deleted = Entity::delete_by_id(id).exec(&txn).await?;
+
+    txn.commit().await?;
+    Ok(deleted.rows_affected > 0)
+}
@@ -0,0 +1,303 @@
+use chrono::Utc;
+use sea_orm::sea_query::{Expr, Func, LikeExpr, Query};
+use sea_orm::{
+    prelude::Uuid, ActiveModelTrait, ActiveValue, ColumnTrait,
+    DatabaseConnection, EntityTrait, LoaderTrait, QueryFilter,
+    TransactionTrait, UpdateMany,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::extra_models::device::{
+    Capabilities, Capability, DeviceKind, DeviceState,
+};
+use crate::pagination::{
+    contains_pattern, paginate, Page, PageRequest, SortKey, SortValue,
+};
+use crate::queries::light::merged_state;
+
+type DeviceWithRoom = (
+    crate::entities::device::Model,
+    Option<crate::entities::room::Model>,
+);
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceSort {
+    #[default]
+    Name,
+    UpdatedAt,
+}
+
+impl SortKey for DeviceSort {
+    type Entity = crate::entities::device::Entity;
+
+    fn column(self) -> crate::entities::device::Column {
+        match self {
+            DeviceSort::Name => crate::entities::device::Column::Name,
+            DeviceSort::UpdatedAt => crate::entities::device::Column::UpdatedAt,
+        }
+    }
+
+    fn value(self, model: &crate::entities::device::Model) -> SortValue {
+        match self {
+            DeviceSort::Name => SortValue::Text(model.name.clone()),
+            DeviceSort::UpdatedAt => SortValue::Time(model.updated_at),
+        }
+    }
+
+    fn id_column() -> crate::entities::device::Column {
+        crate::entities::device::Column::Id
+    }
+
+    fn id(model: &crate::entities::device::Model) -> Uuid {
+        model.id
+    }
+}
+
+/// A device model profile to link a device to, and the capabilities the
+/// profile gives it.
+#[derive(Clone, Copy, Debug)]
+pub struct ProfileLink<'a> {
+    pub id: &'a str,
+    pub capabilities: &'a [Capability],
+}
+
+/// Narrows down a list of devices. Unset fields match every device.
+#[derive(Clone, Debug, Default)]
+pub struct DeviceFilter {
+    /// Matches devices whose name contains this, ignoring case.
+    pub name: Option<String>,
+    pub kind: Option<DeviceKind>,
+    pub capability: Option<Capability>,
+    pub room_id: Option<Uuid>,
+}
+
+pub async fn create_device(
+    name: &str,
+    kind: DeviceKind,
+    capabilities: Vec<Capability>,
+    state: DeviceState,
+    room_id: Option<Uuid>,
+    profile_id: Option<&str>,
+    db: &DatabaseConnection,
+) -> anyhow::Result<DeviceWithRoom> {
+    let txn = db.begin().await?;
+    let device = crate::entities::device::ActiveModel {
+        name: ActiveValue::Set(name.to_owned()),
+        kind: ActiveValue::Set(kind),
+        capabilities: ActiveValue::Set(Capabilities(capabilities)),
+        state: ActiveValue::Set(state),
+        profile_id: ActiveValue::Set(profile_id.map(str::to_owned)),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await?;
+    if let Some(room_id) = room_id {
+        crate::entities::room_light::ActiveModel {
+            room_id: ActiveValue::Set(room_id),
+            light_id: ActiveValue::Set(device.id),
+        }
+        .insert(&txn)
+        .await?;
+    }
+    txn.commit().await?;
+
+    let room = match room_id {
+        Some(room_id) => crate::queries::room::get_room(&room_id, db).await?,
+        None => None,
+    };
+    Ok((device, room))
+}
+
+pub async fn get_device(
+    id: &Uuid,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<DeviceWithRoom>> {
+    let device = crate::entities::device::Entity::find_by_id(*id)
+        .find_with_related(crate::entities::room::Entity)
+        .all(db)
+        .await?;
+
+    Ok(device
+        .into_iter()
+        .next()
+        .map(|(device, rooms)| (device, rooms.into_iter().next())))
+}
+
+pub async fn get_devices(
+    filter: &DeviceFilter,
+    page: &PageRequest<DeviceSort>,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Page<DeviceWithRoom>> {
+    let mut select = crate::entities::device::Entity::find();
+    if let Some(name) = &filter.name {
+        select = select.filter(
+            Expr::expr(Func::lower(Expr::col(
+                crate::entities::device::Column::Name,
+            )))
+            .like(LikeExpr::new(contains_pattern(name)).escape('\\')),
+        );
+    }
+    if let Some(kind) = filter.kind {
+        select = select.filter(crate::entities::device::Column::Kind.eq(kind));
+    }
+    if let Some(capability) = filter.capability {
+        select = select.filter(Expr::cust_with_values(
+            "device.capabilities::jsonb @> $1::jsonb",
+            [serde_json::to_value([capability])?],
+        ));
+    }
+    if let Some(room_id) = filter.room_id {
+        select = select.filter(
+            crate::entities::device::Column::Id.in_subquery(
+                Query::select()
+                    .column(crate::entities::room_light::Column::LightId)
+                    .from(crate::entities::room_light::Entity)
+                    .and_where(
+                        crate::entities::room_light::Column::RoomId.eq(room_id),
+                    )
+                    .to_owned(),
+            ),
+        );
+    }
+
+    let page = paginate(select, page, db).await?;
+    let mut rooms = page
+        .items
+        .load_many_to_many(
+            crate::entities::room::Entity,
+            crate::entities::room_light::Entity,
+            db,
+        )
+        .await?
+        .into_iter();
+    Ok(page.map(|device| {
+        (
+            device,
+            rooms.next().and_then(|rooms| rooms.into_iter().next()),
+        )
+    }))
+}
+
+/// Starts an update of a device that bumps its version, and only applies to
+/// one of the given versions, if any are given.
+fn versioned_update(
+    id: &Uuid,
+    versions: Option<&[i32]>,
+) -> UpdateMany<crate::entities::device::Entity> {
+    let mut update = crate::entities::device::Entity::update_many()
+        .col_expr(
+            crate::entities::device::Column::Version,
+            Expr::col(crate::entities::device::Column::Version).add(1),
+        )
+        .col_expr(
+            crate::entities::device::Column::UpdatedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(crate::entities::device::Column::Id.eq(*id));
+    if let Some(versions) = versions {
+        update = update.filter(
+            crate::entities::device::Column::Version
+                .is_in(versions.iter().copied()),
+        );
+    }
+    update
+}
+
+/// Linking a profile also gives the device the profile's capabilities;
+/// unlinking one leaves them as they are. Returns `None` when there is no
+/// device with that id, or it is not at one of the given versions.
+pub async fn update_device(
+    id: &Uuid,
+    name: Option<&str>,
+    room_id: Option<Option<Uuid>>,
+    profile: Option<Option<ProfileLink<'_>>>,
+    versions: Option<&[i32]>,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<DeviceWithRoom>> {
+    let txn = db.begin().await?;
+    let mut update = versioned_update(id, versions);
+    if let Some(name) = name {
+        update = update
+            .col_expr(crate::entities::device::Column::Name, Expr::value(name));
+    }
+    if let Some(profile) = profile {
+        update = update.col_expr(
+            crate::entities::device::Column::ProfileId,
+            Expr::value(profile.map(|profile| profile.id)),
+        );
+    }
+    if let Some(Some(profile)) = profile {
+        update = update.col_expr(
+            crate::entities::device::Column::Capabilities,
+            Expr::value(Capabilities(profile.capabilities.to_vec())),
+        );
+    }
+    if update.exec(&txn).await?.rows_affected == 0 {
+        return Ok(None);
+    }
+    if let Some(room_id) = room_id {
+        crate::entities::room_light::Entity::delete_many()
+            .filter(crate::entities::room_light::Column::LightId.eq(*id))
+            .exec(&txn)
+            .await?;
+        if let Some(room_id) = room_id {
+            crate::entities::room_light::ActiveModel {
+                room_id: ActiveValue::Set(room_id),
+                light_id: ActiveValue::Set(*id),
+            }
+            .insert(&txn)
+            .await?;
+        }
+    }
+    txn.commit().await?;
+    get_device(id, db).await
+}
+
+/// Sets the fields of the state that are given, leaving the others as they
+/// are. Returns `None` when there is no device with that id, or it is not at
+/// one of the given versions.
+pub async fn set_device_state(
+    id: &Uuid,
+    state: &DeviceState,
+    versions: Option<&[i32]>,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<DeviceWithRoom>> {
+    let result = versioned_update(id, versions)
+        .col_expr(crate::entities::device::Column::State, merged_state(state)?)
+        .exec(db)
+        .await?;
+    if result.rows_affected == 0 {
+        return Ok(None);
+    }
+    get_device(id, db).await
+}
+
+/// Deletes a device, its room and zone memberships and its readings. Returns
+/// whether the device existed.
+pub async fn delete_device(
+    id: &Uuid,
+    db: &DatabaseConnection,
+) -> anyhow::Result<bool> {
+    let txn = db.begin().await?;
+    crate::entities::room_light::Entity::delete_many()
+        .filter(crate::entities::room_light::Column::LightId.eq(*id))
+        .exec(&txn)
+        .await?;
+    crate::entities::zone_light::Entity::delete_many()
+        .filter(crate::entities::zone_light::Column::LightId.eq(*id))
+        .exec(&txn)
+        .await?;
+    crate::entities::sensor_reading::Entity::delete_many()
+        .filter(crate::entities::sensor_reading::Column::DeviceId.eq(*id))
+        .exec(&txn)
+        .await?;
+    let result = crate::entities::device::Entity::delete_by_id(*id)
+        .exec(&txn)
+        .await?;
+    txn.commit().await?;
+    Ok(result.rows_affected > 0)
+}
@@ -0,0 +1,130 @@
+use crate::{
+    idempotency_key::{ActiveModel, Column, Model},
+    IdempotencyKey as Entity,
+};
+use sea_orm::{
+    prelude::{DateTime, Json, Uuid},
+    sea_query::{Expr, OnConflict},
+    ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+};
+
+/// Claims a key for a new request. Returns `false` when the user already
+/// used the key, in which case the stored request should be looked up.
+pub async fn insert_key(
+    user_id: Uuid,
+    key: &str,
+    fingerprint: &str,
+    db: &DatabaseConnection,
+) -> anyhow::Result<bool> {
+    let model = ActiveModel {
+        user_id: ActiveValue::Set(user_id),
+        key: ActiveValue::Set(key.to_owned()),
+        fingerprint: ActiveValue::Set(fingerprint.to_owned()),
+        ..Default::default()
+    };
+    let inserted = Entity::insert(model)
+        .on_conflict(
+            OnConflict::columns([Column::UserId, Column::Key])
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?;
+    Ok(inserted > 0)
+}
+
+pub async fn get_key(
+    user_id: Uuid,
+    key: &str,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<Model>> {
+    Ok(Entity::find_by_id((user_id, key.to_owned()))
+        .one(db)
+        .await?)
+}
+
+/// Takes over a key whose request was claimed before `stale_before` and
+/// never completed, as if it had just been claimed. Returns whether it did.
+pub async fn reclaim_key(
+    user_id: Uuid,
+    key: &str,
+    stale_before: DateTime,
+    db: &DatabaseConnection,
+) -> anyhow::Result<bool> {
+    let reclaimed = Entity::update_many()
+        .col_expr(
+            Column::CreatedAt,
+            Expr::value(chrono::Utc::now().naive_utc()),
+        )
+        .filter(Column::UserId.eq(user_id))
+        .filter(Column::Key.eq(key))
+        .filter(Column::ResponseStatus.is_null())
+        .filter(Column::CreatedAt.lt(stale_before))
+        .exec(db)
+        .await?;
+    Ok(reclaimed.rows_affected > 0)
+}
+
+/// Extends the lease of a request that is still running, so that the key is
+/// not taken over while it is. Returns whether the key was still held.
+pub async fn renew_key(
+    user_id: Uuid,
+    key: &str,
+    db: &DatabaseConnection,
+) -> anyhow::Result<bool> {
+    let renewed = Entity::update_many()
+        .col_expr(
+            Column::CreatedAt,
+            Expr::value(chrono::Utc::now().naive_utc()),
+        )
+        .filter(Column::UserId.eq(user_id))
+        .filter(Column::Key.eq(key))
+        .filter(Column::ResponseStatus.is_null())
+        .exec(db)
+        .await?;
+    Ok(renewed.rows_affected > 0)
+}
+
+/// Stores the response to replay for later requests with the key.
+pub async fn complete_key(
+    user_id: Uuid,
+    key: &str,
+    status: i32,
+    headers: Json,
+    body: Vec<u8>,
+    db: &DatabaseConnection,
+) -> anyhow::Result<()> {
+    let model = ActiveModel {
+        user_id: ActiveValue::Unchanged(user_id),
+        key: ActiveValue::Unchanged(key.to_owned()),
+        response_status: ActiveValue::Set(Some(status)),
+        response_headers: ActiveValue::Set(Some(headers)),
+        response_body: ActiveValue::Set(Some(body)),
+        ..Default::default()
+    };
+    Entity::update(model).exec(db).await?;
+    Ok(())
+}
+
+pub async fn delete_key(
+    user_id: Uuid,
+    key: &str,
+    db: &DatabaseConnection,
+) -> anyhow::Result<()> {
+    Entity::delete_by_id((user_id, key.to_owned()))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// Forgets every key first used before `before`, so that it can be reused.
+pub async fn delete_expired_keys(
+    before: DateTime,
+    db: &DatabaseConnection,
+) -> anyhow::Result<u64> {
+    let deleted = Entity::delete_many()
+        .filter(Column::CreatedAt.lt(before))
+        .exec(db)
+        .await?;
+    Ok(deleted.rows_affected)
+}
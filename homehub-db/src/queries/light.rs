@@ -1,20 +1,38 @@
+use std::collections::HashSet;
+
+use chrono::Utc;
+use sea_orm::sea_query::extension::postgres::PgExpr;
+use sea_orm::sea_query::{Alias, Expr, Func, LikeExpr, Query, SimpleExpr};
 use sea_orm::{prelude::Uuid, ActiveValue, DatabaseConnection};
-use sea_orm::{ActiveModelTrait, QueryFilter};
+use sea_orm::{
+    ActiveModelTrait, LoaderTrait, PaginatorTrait, QueryFilter, QuerySelect,
+    TransactionTrait, UpdateMany,
+};
 use sea_orm::{ColumnTrait, EntityTrait};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
+use crate::extra_models::device::Capabilities;
 use crate::extra_models::light::LightState;
+use crate::pagination::{
+    contains_pattern, paginate, Page, PageRequest, SortKey, SortValue,
+};
+use crate::queries::device::ProfileLink;
 
-type LightResult = anyhow::Result<(
+type LightWithRoom = (
     crate::entities::light::Model,
     Option<crate::entities::room::Model>,
-)>;
+);
 
+/// Without a profile, the light gets the capabilities every light had
+/// before profiles existed.
 pub async fn create_light(
     name: &str,
     room_id: Option<Uuid>,
+    profile: Option<ProfileLink<'_>>,
     db: &DatabaseConnection,
-) -> LightResult {
-    let light = crate::entities::light::ActiveModel {
+) -> anyhow::Result<LightWithRoom> {
+    let mut light = crate::entities::light::ActiveModel {
         name: ActiveValue::Set(name.to_owned()),
         state: ActiveValue::Set(LightState {
             on: false,
@@ -22,6 +40,11 @@ pub async fn create_light(
         }),
         ..Default::default()
     };
+    if let Some(profile) = profile {
+        light.profile_id = ActiveValue::Set(Some(profile.id.to_owned()));
+        light.capabilities =
+            ActiveValue::Set(Capabilities(profile.capabilities.to_vec()));
+    }
 
     let light_model = light.insert(db).await?;
 
@@ -43,58 +66,425 @@ pub async fn create_light(
     Ok((light_model, None))
 }
 
-pub async fn get_light(id: &Uuid, db: &DatabaseConnection) -> LightResult {
+pub async fn get_light(
+    id: &Uuid,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<LightWithRoom>> {
     let light = crate::entities::light::Entity::find_by_id(*id)
         .find_with_related(crate::entities::room::Entity)
         .all(db)
         .await?;
 
-    if let Some(light) = light.into_iter().next() {
-        return Ok((light.0, light.1.into_iter().next()));
+    Ok(light
+        .into_iter()
+        .next()
+        .map(|(light, rooms)| (light, rooms.into_iter().next())))
+}
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum LightSort {
+    #[default]
+    Name,
+    UpdatedAt,
+}
+
+impl SortKey for LightSort {
+    type Entity = crate::entities::light::Entity;
+
+    fn column(self) -> crate::entities::light::Column {
+        match self {
+            LightSort::Name => crate::entities::light::Column::Name,
+            LightSort::UpdatedAt => crate::entities::light::Column::UpdatedAt,
+        }
+    }
+
+    fn value(self, model: &crate::entities::light::Model) -> SortValue {
+        match self {
+            LightSort::Name => SortValue::Text(model.name.clone()),
+            LightSort::UpdatedAt => SortValue::Time(model.updated_at),
+        }
+    }
+
+    fn id_column() -> crate::entities::light::Column {
+        crate::entities::light::Column::Id
+    }
+
+    fn id(model: &crate::entities::light::Model) -> Uuid {
+        model.id
     }
-    Err(anyhow::anyhow!("No lights found"))
 }
 
+/// Narrows down a list of lights. Unset fields match every light.
+#[derive(Clone, Debug, Default)]
+pub struct LightFilter {
+    /// Matches lights whose name contains this, ignoring case.
+    pub name: Option<String>,
+    pub room_id: Option<Uuid>,
+    pub zone_id: Option<Uuid>,
+    pub on: Option<bool>,
+}
+
+/// Matches the lights in a zone.
+fn in_zone(zone_id: Uuid) -> SimpleExpr {
+    crate::entities::light::Column::Id.in_subquery(
+        Query::select()
+            .column(crate::entities::zone_light::Column::LightId)
+            .from(crate::entities::zone_light::Entity)
+            .and_where(crate::entities::zone_light::Column::ZoneId.eq(zone_id))
+            .to_owned(),
+    )
+}
+
+pub async fn get_lights(
+    filter: &LightFilter,
+    page: &PageRequest<LightSort>,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Page<LightWithRoom>> {
+    let mut select = crate::entities::light::Entity::find();
+    if let Some(name) = &filter.name {
+        select = select.filter(
+            Expr::expr(Func::lower(Expr::col(
+                crate::entities::light::Column::Name,
+            )))
+            .like(LikeExpr::new(contains_pattern(name)).escape('\\')),
+        );
+    }
+    if let Some(room_id) = filter.room_id {
+        select = select.filter(
+            crate::entities::light::Column::Id.in_subquery(
+                Query::select()
+                    .column(crate::entities::room_light::Column::LightId)
+                    .from(crate::entities::room_light::Entity)
+                    .and_where(
+                        crate::entities::room_light::Column::RoomId.eq(room_id),
+                    )
+                    .to_owned(),
+            ),
+        );
+    }
+    if let Some(zone_id) = filter.zone_id {
+        select = select.filter(in_zone(zone_id));
+    }
+    if let Some(on) = filter.on {
+        select = select.filter(
+            Expr::expr(
+                Expr::col((
+                    crate::entities::light::Entity,
+                    crate::entities::light::Column::State,
+                ))
+                .cast_json_field("on"),
+            )
+            .cast_as(Alias::new("boolean"))
+            .eq(on),
+        );
+    }
+
+    let page = paginate(select, page, db).await?;
+    let mut rooms = page
+        .items
+        .load_many_to_many(
+            crate::entities::room::Entity,
+            crate::entities::room_light::Entity,
+            db,
+        )
+        .await?
+        .into_iter();
+    Ok(page.map(|light| {
+        (
+            light,
+            rooms.next().and_then(|rooms| rooms.into_iter().next()),
+        )
+    }))
+}
+
+/// Sets the fields of a light state without touching the rest of the
+/// device state, such as the brightness of dimmable lights.
+pub(crate) fn merged_state(
+    state: &impl Serialize,
+) -> anyhow::Result<SimpleExpr> {
+    Ok(Expr::cust_with_values(
+        "(state::jsonb || $1::jsonb)::json",
+        [serde_json::to_value(state)?],
+    ))
+}
+
+/// Starts an update of lights that bumps their versions.
+fn bumping_update() -> UpdateMany<crate::entities::light::Entity> {
+    crate::entities::light::Entity::update_many()
+        .col_expr(
+            crate::entities::light::Column::Version,
+            Expr::col(crate::entities::light::Column::Version).add(1),
+        )
+        .col_expr(
+            crate::entities::light::Column::UpdatedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+}
+
+/// Starts an update of a light that bumps its version, and only applies to
+/// one of the given versions, if any are given.
+fn versioned_update(
+    id: &Uuid,
+    versions: Option<&[i32]>,
+) -> UpdateMany<crate::entities::light::Entity> {
+    let mut update =
+        bumping_update().filter(crate::entities::light::Column::Id.eq(*id));
+    if let Some(versions) = versions {
+        update = update.filter(
+            crate::entities::light::Column::Version
+                .is_in(versions.iter().copied()),
+        );
+    }
+    update
+}
+
+/// Returns `None` when there is no light with that id, or it is not at one
+/// of the given versions.
 pub async fn update_light(
     id: &Uuid,
     name: Option<&str>,
     room_id: Option<Option<Uuid>>,
+    profile: Option<Option<ProfileLink<'_>>>,
+    versions: Option<&[i32]>,
     db: &DatabaseConnection,
-) -> LightResult {
-    let (light, room) = get_light(id, db).await?;
-    let mut light: crate::entities::light::ActiveModel = light.into();
+) -> anyhow::Result<Option<LightWithRoom>> {
+    let txn = db.begin().await?;
+    let mut update = versioned_update(id, versions);
     if let Some(name) = name {
-        light.name = ActiveValue::Set(name.to_owned());
+        update = update
+            .col_expr(crate::entities::light::Column::Name, Expr::value(name));
+    }
+    if let Some(profile) = profile {
+        update = update.col_expr(
+            crate::entities::light::Column::ProfileId,
+            Expr::value(profile.map(|profile| profile.id)),
+        );
     }
-    if let Some(Some(room_id)) = room_id {
-        let existing_room_light = crate::entities::room_light::Entity::find()
+    if let Some(Some(profile)) = profile {
+        update = update.col_expr(
+            crate::entities::light::Column::Capabilities,
+            Expr::value(Capabilities(profile.capabilities.to_vec())),
+        );
+    }
+    if update.exec(&txn).await?.rows_affected == 0 {
+        return Ok(None);
+    }
+    if let Some(room_id) = room_id {
+        crate::entities::room_light::Entity::delete_many()
             .filter(crate::entities::room_light::Column::LightId.eq(*id))
-            .one(db)
+            .exec(&txn)
             .await?;
-        if let Some(existing_room_light) = existing_room_light {
-            let existing_room_light: crate::entities::room_light::ActiveModel =
-                existing_room_light.into();
-            existing_room_light.delete(db).await?;
+        if let Some(room_id) = room_id {
+            let room_light = crate::entities::room_light::ActiveModel {
+                room_id: ActiveValue::Set(room_id),
+                light_id: ActiveValue::Set(id.to_owned()),
+            };
+            room_light.insert(&txn).await?;
         }
-        let room_light = crate::entities::room_light::ActiveModel {
-            room_id: ActiveValue::Set(room_id),
-            light_id: ActiveValue::Set(id.to_owned()),
-        };
-
-        room_light.insert(db).await?;
     }
-    let light = light.update(db).await?;
-    Ok((light, room))
+    txn.commit().await?;
+    get_light(id, db).await
 }
 
+/// Returns `None` when there is no light with that id, or it is not at one
+/// of the given versions.
 pub async fn set_light_state(
     id: &Uuid,
     state: LightState,
+    versions: Option<&[i32]>,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<LightWithRoom>> {
+    let result = versioned_update(id, versions)
+        .col_expr(crate::entities::light::Column::State, merged_state(&state)?)
+        .exec(db)
+        .await?;
+    if result.rows_affected == 0 {
+        return Ok(None);
+    }
+    get_light(id, db).await
+}
+
+/// Picks out the lights a bulk operation applies to.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LightSelector {
+    /// The lights with these ids.
+    Ids(Vec<Uuid>),
+    /// Every light in this room.
+    #[serde(rename = "room_id")]
+    Room(Uuid),
+    /// Every light in a room at this location.
+    #[serde(rename = "location_id")]
+    Location(Uuid),
+    /// Every light in this zone.
+    #[serde(rename = "zone_id")]
+    Zone(Uuid),
+}
+
+/// Sets the state of every selected light in one transaction, returning the
+/// lights that were updated. `state` gives the state for lights of each
+/// profile, so that it can be adapted to what they can do.
+pub async fn set_light_states(
+    selector: &LightSelector,
+    state: impl Fn(Option<&str>) -> LightState,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<LightWithRoom>> {
+    let in_rooms = |rooms: SimpleExpr| {
+        crate::entities::light::Column::Id.in_subquery(
+            Query::select()
+                .column(crate::entities::room_light::Column::LightId)
+                .from(crate::entities::room_light::Entity)
+                .and_where(rooms)
+                .to_owned(),
+        )
+    };
+    let condition = match selector {
+        LightSelector::Ids(ids) => {
+            crate::entities::light::Column::Id.is_in(ids.iter().copied())
+        }
+        LightSelector::Room(room_id) => {
+            in_rooms(crate::entities::room_light::Column::RoomId.eq(*room_id))
+        }
+        LightSelector::Location(location_id) => in_rooms(
+            crate::entities::room_light::Column::RoomId.in_subquery(
+                Query::select()
+                    .column(crate::entities::room::Column::Id)
+                    .from(crate::entities::room::Entity)
+                    .and_where(
+                        crate::entities::room::Column::LocationId
+                            .eq(*location_id),
+                    )
+                    .to_owned(),
+            ),
+        ),
+        LightSelector::Zone(zone_id) => in_zone(*zone_id),
+    };
+
+    let txn = db.begin().await?;
+    let profile_ids: Vec<Option<String>> =
+        crate::entities::light::Entity::find()
+            .select_only()
+            .column(crate::entities::light::Column::ProfileId)
+            .distinct()
+            .filter(condition.clone())
+            .into_tuple()
+            .all(&txn)
+            .await?;
+    let mut lights = Vec::new();
+    for profile_id in profile_ids {
+        let same_profile = match &profile_id {
+            Some(profile_id) => {
+                crate::entities::light::Column::ProfileId.eq(profile_id)
+            }
+            None => crate::entities::light::Column::ProfileId.is_null(),
+        };
+        lights.extend(
+            bumping_update()
+                .col_expr(
+                    crate::entities::light::Column::State,
+                    merged_state(&state(profile_id.as_deref()))?,
+                )
+                .filter(condition.clone())
+                .filter(same_profile)
+                .exec_with_returning(&txn)
+                .await?,
+        );
+    }
+    let rooms = lights
+        .load_many_to_many(
+            crate::entities::room::Entity,
+            crate::entities::room_light::Entity,
+            &txn,
+        )
+        .await?;
+    txn.commit().await?;
+
+    Ok(lights
+        .into_iter()
+        .zip(rooms)
+        .map(|(light, rooms)| (light, rooms.into_iter().next()))
+        .collect())
+}
+
+/// Whether every one of the given lights exists.
+pub async fn lights_exist(
+    ids: &[Uuid],
     db: &DatabaseConnection,
-) -> LightResult {
-    let (light, room) = get_light(id, db).await?;
-    let mut light: crate::entities::light::ActiveModel = light.into();
-    light.state = ActiveValue::Set(state);
-    let light = light.update(db).await?;
-    Ok((light, room))
+) -> anyhow::Result<bool> {
+    let ids: HashSet<_> = ids.iter().copied().collect();
+    let count = crate::entities::light::Entity::find()
+        .filter(crate::entities::light::Column::Id.is_in(ids.iter().copied()))
+        .count(db)
+        .await?;
+    Ok(count == ids.len() as u64)
+}
+
+/// Deletes a light, its room and zone memberships and its readings. Returns
+/// whether the light existed; devices of other kinds are left alone.
+pub async fn delete_light(
+    id: &Uuid,
+    db: &DatabaseConnection,
+) -> anyhow::Result<bool> {
+    let txn = db.begin().await?;
+    if crate::entities::light::Entity::find_by_id(*id)
+        .one(&txn)
+        .await?
+        .is_none()
+    {
+        return Ok(false);
+    }
+    crate::entities::room_light::Entity::delete_many()
+        .filter(crate::entities::room_light::Column::LightId.eq(*id))
+        .exec(&txn)
+        .await?;
+    crate::entities::zone_light::Entity::delete_many()
+        .filter(crate::entities::zone_light::Column::LightId.eq(*id))
+        .exec(&txn)
+        .await?;
+    crate::entities::sensor_reading::Entity::delete_many()
+        .filter(crate::entities::sensor_reading::Column::DeviceId.eq(*id))
+        .exec(&txn)
+        .await?;
+    let result = crate::entities::light::Entity::delete_by_id(*id)
+        .exec(&txn)
+        .await?;
+    txn.commit().await?;
+    Ok(result.rows_affected > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    use super::*;
+    use crate::pagination::SortOrder;
+
+    #[tokio::test]
+    async fn filters_on_the_state_column() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<crate::entities::light::Model>::new()])
+            .into_connection();
+        let filter = LightFilter {
+            on: Some(true),
+            ..Default::default()
+        };
+        let page =
+            PageRequest::new(LightSort::Name, SortOrder::Asc, None, None)
+                .unwrap();
+
+        let lights = get_lights(&filter, &page, &db).await.unwrap();
+        let sql = format!("{:?}", db.into_transaction_log());
+
+        assert!(lights.items.is_empty());
+        assert!(
+            sql.contains(
+                r#"CAST((\"light\".\"state\" ->> $1) AS boolean) = $2"#
+            ),
+            "{}",
+            sql
+        );
+    }
 }
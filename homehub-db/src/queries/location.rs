@@ -0,0 +1,151 @@
+use std::collections::HashSet;
+
+use chrono::Utc;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    prelude::Uuid, ColumnTrait, DatabaseConnection, EntityTrait, JoinType,
+    QueryFilter, QuerySelect, RelationTrait,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::extra_models::light::LightState;
+use crate::pagination::{paginate, Page, PageRequest, SortKey, SortValue};
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum LocationSort {
+    #[default]
+    Name,
+}
+
+impl SortKey for LocationSort {
+    type Entity = crate::entities::location::Entity;
+
+    fn column(self) -> crate::entities::location::Column {
+        match self {
+            LocationSort::Name => crate::entities::location::Column::Name,
+        }
+    }
+
+    fn value(self, model: &crate::entities::location::Model) -> SortValue {
+        match self {
+            LocationSort::Name => SortValue::Text(model.name.clone()),
+        }
+    }
+
+    fn id_column() -> crate::entities::location::Column {
+        crate::entities::location::Column::Id
+    }
+
+    fn id(model: &crate::entities::location::Model) -> Uuid {
+        model.id
+    }
+}
+
+pub async fn get_locations(
+    page: &PageRequest<LocationSort>,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Page<crate::entities::location::Model>> {
+    paginate(crate::entities::location::Entity::find(), page, db).await
+}
+
+pub async fn get_location(
+    id: &Uuid,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<crate::entities::location::Model>> {
+    let location = crate::entities::location::Entity::find_by_id(*id)
+        .one(db)
+        .await?;
+    Ok(location)
+}
+
+/// Looks up many locations at once. Unknown ids are left out.
+pub async fn get_locations_by_ids(
+    ids: &[Uuid],
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<crate::entities::location::Model>> {
+    let locations = crate::entities::location::Entity::find()
+        .filter(
+            crate::entities::location::Column::Id.is_in(ids.iter().copied()),
+        )
+        .all(db)
+        .await?;
+    Ok(locations)
+}
+
+/// Renames a location and bumps its version. Returns `None` when there is no
+/// location with that id, or it is not at one of the given versions.
+pub async fn update_location(
+    id: &Uuid,
+    name: Option<&str>,
+    versions: Option<&[i32]>,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<crate::entities::location::Model>> {
+    let mut update = crate::entities::location::Entity::update_many()
+        .col_expr(
+            crate::entities::location::Column::Version,
+            Expr::col(crate::entities::location::Column::Version).add(1),
+        )
+        .col_expr(
+            crate::entities::location::Column::UpdatedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(crate::entities::location::Column::Id.eq(*id));
+    if let Some(name) = name {
+        update = update.col_expr(
+            crate::entities::location::Column::Name,
+            Expr::value(name),
+        );
+    }
+    if let Some(versions) = versions {
+        update = update.filter(
+            crate::entities::location::Column::Version
+                .is_in(versions.iter().copied()),
+        );
+    }
+    if update.exec(db).await?.rows_affected == 0 {
+        return Ok(None);
+    }
+    get_location(id, db).await
+}
+
+/// The states of the lights in the rooms of each of the given locations, as
+/// pairs of location id and light state. A light is only counted once per
+/// location.
+pub async fn get_light_states(
+    location_ids: &[Uuid],
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<(Uuid, LightState)>> {
+    let lights: Vec<(Uuid, Uuid, LightState)> =
+        crate::entities::room_light::Entity::find()
+            .select_only()
+            .column(crate::entities::room::Column::LocationId)
+            .column(crate::entities::light::Column::Id)
+            .column(crate::entities::light::Column::State)
+            .join(
+                JoinType::InnerJoin,
+                crate::entities::room_light::Relation::Light.def(),
+            )
+            .join(
+                JoinType::InnerJoin,
+                crate::entities::room_light::Relation::Room.def(),
+            )
+            .filter(
+                crate::entities::room::Column::LocationId
+                    .is_in(location_ids.iter().copied()),
+            )
+            .into_tuple()
+            .all(db)
+            .await?;
+    let mut seen = HashSet::new();
+    Ok(lights
+        .into_iter()
+        .filter(|(location_id, light_id, _)| {
+            seen.insert((*location_id, *light_id))
+        })
+        .map(|(location_id, _, state)| (location_id, state))
+        .collect())
+}
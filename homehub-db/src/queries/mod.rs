@@ -1,2 +1,11 @@
 pub mod app_user;
+pub mod device;
+pub mod idempotency_key;
 pub mod light;
+pub mod location;
+pub mod oauth;
+pub mod registration_invite;
+pub mod room;
+pub mod sensor_reading;
+pub mod user_identity;
+pub mod zone;
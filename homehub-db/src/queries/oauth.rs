@@ -0,0 +1,148 @@
+use sea_orm::{prelude::Uuid, ActiveValue, DatabaseConnection};
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter};
+
+use crate::entities::{oauth_authorization_code, oauth_client, oauth_consent};
+use crate::extra_models::oauth::RedirectUris;
+
+pub async fn create_client(
+    name: &str,
+    secret_hash: Option<&str>,
+    redirect_uris: Vec<String>,
+    scope: &str,
+    owner_id: Uuid,
+    db: &DatabaseConnection,
+) -> anyhow::Result<oauth_client::Model> {
+    let client = oauth_client::ActiveModel {
+        name: ActiveValue::Set(name.to_owned()),
+        secret_hash: ActiveValue::Set(secret_hash.map(|s| s.to_owned())),
+        redirect_uris: ActiveValue::Set(RedirectUris(redirect_uris)),
+        scope: ActiveValue::Set(scope.to_owned()),
+        owner_id: ActiveValue::Set(owner_id),
+        ..Default::default()
+    };
+
+    Ok(client.insert(db).await?)
+}
+
+pub async fn find_client(
+    id: Uuid,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<oauth_client::Model>> {
+    let client = oauth_client::Entity::find_by_id(id).one(db).await?;
+    Ok(client)
+}
+
+pub async fn find_clients_by_owner(
+    owner_id: Uuid,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<oauth_client::Model>> {
+    let clients = oauth_client::Entity::find()
+        .filter(oauth_client::Column::OwnerId.eq(owner_id))
+        .all(db)
+        .await?;
+    Ok(clients)
+}
+
+pub async fn delete_client(
+    id: Uuid,
+    db: &DatabaseConnection,
+) -> anyhow::Result<()> {
+    oauth_authorization_code::Entity::delete_many()
+        .filter(oauth_authorization_code::Column::ClientId.eq(id))
+        .exec(db)
+        .await?;
+    oauth_consent::Entity::delete_many()
+        .filter(oauth_consent::Column::ClientId.eq(id))
+        .exec(db)
+        .await?;
+    oauth_client::Entity::delete_by_id(id).exec(db).await?;
+    Ok(())
+}
+
+pub async fn create_authorization_code(
+    code: oauth_authorization_code::Model,
+    db: &DatabaseConnection,
+) -> anyhow::Result<oauth_authorization_code::Model> {
+    let code: oauth_authorization_code::ActiveModel = code.into();
+    Ok(code.insert(db).await?)
+}
+
+/// Removes and returns an authorization code so that it can only ever be
+/// redeemed once, even by concurrent requests.
+pub async fn take_authorization_code(
+    code_hash: &str,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<oauth_authorization_code::Model>> {
+    let code = oauth_authorization_code::Entity::find_by_id(code_hash)
+        .one(db)
+        .await?;
+
+    let Some(code) = code else {
+        return Ok(None);
+    };
+
+    let deleted = oauth_authorization_code::Entity::delete_by_id(code_hash)
+        .exec(db)
+        .await?;
+
+    if deleted.rows_affected == 0 {
+        return Ok(None);
+    }
+    Ok(Some(code))
+}
+
+pub async fn find_consent(
+    user_id: Uuid,
+    client_id: Uuid,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<oauth_consent::Model>> {
+    let consent = oauth_consent::Entity::find_by_id((user_id, client_id))
+        .one(db)
+        .await?;
+    Ok(consent)
+}
+
+pub async fn find_consents_by_user(
+    user_id: Uuid,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<(oauth_consent::Model, Option<oauth_client::Model>)>> {
+    let consents = oauth_consent::Entity::find()
+        .filter(oauth_consent::Column::UserId.eq(user_id))
+        .find_also_related(oauth_client::Entity)
+        .all(db)
+        .await?;
+    Ok(consents)
+}
+
+pub async fn save_consent(
+    user_id: Uuid,
+    client_id: Uuid,
+    scope: &str,
+    db: &DatabaseConnection,
+) -> anyhow::Result<oauth_consent::Model> {
+    if let Some(consent) = find_consent(user_id, client_id, db).await? {
+        let mut consent: oauth_consent::ActiveModel = consent.into();
+        consent.scope = ActiveValue::Set(scope.to_owned());
+        return Ok(consent.update(db).await?);
+    }
+
+    let consent = oauth_consent::ActiveModel {
+        user_id: ActiveValue::Set(user_id),
+        client_id: ActiveValue::Set(client_id),
+        scope: ActiveValue::Set(scope.to_owned()),
+        ..Default::default()
+    };
+
+    Ok(consent.insert(db).await?)
+}
+
+pub async fn delete_consent(
+    user_id: Uuid,
+    client_id: Uuid,
+    db: &DatabaseConnection,
+) -> anyhow::Result<bool> {
+    let deleted = oauth_consent::Entity::delete_by_id((user_id, client_id))
+        .exec(db)
+        .await?;
+    Ok(deleted.rows_affected > 0)
+}
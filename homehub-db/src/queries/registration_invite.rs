@@ -0,0 +1,43 @@
+use crate::{
+    registration_invite::{ActiveModel, Model},
+    RegistrationInvite as Entity,
+};
+use sea_orm::ActiveModelTrait;
+use sea_orm::EntityTrait;
+use sea_orm::{
+    prelude::{DateTime, Uuid},
+    ActiveValue, ConnectionTrait, DatabaseConnection,
+};
+
+pub async fn create_invite(
+    token_hash: &str,
+    created_by: Uuid,
+    expires_at: DateTime,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Model> {
+    let invite = ActiveModel {
+        token_hash: ActiveValue::Set(token_hash.to_owned()),
+        created_by: ActiveValue::Set(created_by),
+        expires_at: ActiveValue::Set(expires_at),
+        ..Default::default()
+    };
+
+    Ok(invite.insert(db).await?)
+}
+
+/// Removes and returns an invite so that it can only ever be redeemed once,
+/// even by concurrent registrations.
+pub async fn take_invite(
+    token_hash: &str,
+    db: &impl ConnectionTrait,
+) -> anyhow::Result<Option<Model>> {
+    let Some(invite) = Entity::find_by_id(token_hash).one(db).await? else {
+        return Ok(None);
+    };
+
+    let deleted = Entity::delete_by_id(token_hash).exec(db).await?;
+    if deleted.rows_affected == 0 {
+        return Ok(None);
+    }
+    Ok(Some(invite))
+}
@@ -0,0 +1,138 @@
+use chrono::Utc;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    prelude::Uuid, ColumnTrait, DatabaseConnection, EntityTrait, JoinType,
+    QueryFilter, QuerySelect, RelationTrait,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::extra_models::light::LightState;
+use crate::pagination::{paginate, Page, PageRequest, SortKey, SortValue};
+
+pub async fn get_room(
+    id: &Uuid,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<crate::entities::room::Model>> {
+    let room = crate::entities::room::Entity::find_by_id(*id)
+        .one(db)
+        .await?;
+    Ok(room)
+}
+
+/// Looks up many rooms at once. Unknown ids are left out.
+pub async fn get_rooms_by_ids(
+    ids: &[Uuid],
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<crate::entities::room::Model>> {
+    let rooms = crate::entities::room::Entity::find()
+        .filter(crate::entities::room::Column::Id.is_in(ids.iter().copied()))
+        .all(db)
+        .await?;
+    Ok(rooms)
+}
+
+/// Renames a room and bumps its version. Returns `None` when there is no
+/// room with that id, or it is not at one of the given versions.
+pub async fn update_room(
+    id: &Uuid,
+    name: Option<&str>,
+    versions: Option<&[i32]>,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<crate::entities::room::Model>> {
+    let mut update = crate::entities::room::Entity::update_many()
+        .col_expr(
+            crate::entities::room::Column::Version,
+            Expr::col(crate::entities::room::Column::Version).add(1),
+        )
+        .col_expr(
+            crate::entities::room::Column::UpdatedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(crate::entities::room::Column::Id.eq(*id));
+    if let Some(name) = name {
+        update = update
+            .col_expr(crate::entities::room::Column::Name, Expr::value(name));
+    }
+    if let Some(versions) = versions {
+        update = update.filter(
+            crate::entities::room::Column::Version
+                .is_in(versions.iter().copied()),
+        );
+    }
+    if update.exec(db).await?.rows_affected == 0 {
+        return Ok(None);
+    }
+    get_room(id, db).await
+}
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum RoomSort {
+    #[default]
+    Name,
+}
+
+impl SortKey for RoomSort {
+    type Entity = crate::entities::room::Entity;
+
+    fn column(self) -> crate::entities::room::Column {
+        match self {
+            RoomSort::Name => crate::entities::room::Column::Name,
+        }
+    }
+
+    fn value(self, model: &crate::entities::room::Model) -> SortValue {
+        match self {
+            RoomSort::Name => SortValue::Text(model.name.clone()),
+        }
+    }
+
+    fn id_column() -> crate::entities::room::Column {
+        crate::entities::room::Column::Id
+    }
+
+    fn id(model: &crate::entities::room::Model) -> Uuid {
+        model.id
+    }
+}
+
+/// Lists all rooms, or only those of one location.
+pub async fn get_rooms(
+    location_id: Option<&Uuid>,
+    page: &PageRequest<RoomSort>,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Page<crate::entities::room::Model>> {
+    let mut select = crate::entities::room::Entity::find();
+    if let Some(location_id) = location_id {
+        select = select
+            .filter(crate::entities::room::Column::LocationId.eq(*location_id));
+    }
+    paginate(select, page, db).await
+}
+
+/// The states of the lights in each of the given rooms, as pairs of room id
+/// and light state.
+pub async fn get_light_states(
+    room_ids: &[Uuid],
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<(Uuid, LightState)>> {
+    let states = crate::entities::room_light::Entity::find()
+        .select_only()
+        .column(crate::entities::room_light::Column::RoomId)
+        .column(crate::entities::light::Column::State)
+        .join(
+            JoinType::InnerJoin,
+            crate::entities::room_light::Relation::Light.def(),
+        )
+        .filter(
+            crate::entities::room_light::Column::RoomId
+                .is_in(room_ids.iter().copied()),
+        )
+        .into_tuple()
+        .all(db)
+        .await?;
+    Ok(states)
+}
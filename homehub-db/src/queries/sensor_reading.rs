@@ -0,0 +1,145 @@
+use chrono::NaiveDateTime;
+use sea_orm::sea_query::{Expr, Func, OnConflict, SimpleExpr};
+use sea_orm::{
+    prelude::Uuid, ColumnTrait, DatabaseConnection, EntityTrait, Order,
+    QueryFilter, QueryOrder, QuerySelect,
+};
+
+use crate::extra_models::device::Metric;
+
+/// The readings of one metric within one bucket of time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bucket {
+    pub start: NaiveDateTime,
+    pub min: f64,
+    pub max: f64,
+    pub avg: f64,
+    pub count: i64,
+}
+
+/// Stores readings of a device as `(metric, recorded_at, value)`. A reading
+/// of a metric at a time that already has one is ignored, so sending the
+/// same readings twice is harmless.
+pub async fn insert_readings(
+    device_id: &Uuid,
+    readings: &[(Metric, NaiveDateTime, f64)],
+    db: &DatabaseConnection,
+) -> anyhow::Result<()> {
+    if readings.is_empty() {
+        return Ok(());
+    }
+    crate::entities::sensor_reading::Entity::insert_many(readings.iter().map(
+        |(metric, recorded_at, value)| {
+            crate::entities::sensor_reading::ActiveModel {
+                device_id: sea_orm::ActiveValue::Set(*device_id),
+                metric: sea_orm::ActiveValue::Set(*metric),
+                recorded_at: sea_orm::ActiveValue::Set(*recorded_at),
+                value: sea_orm::ActiveValue::Set(*value),
+            }
+        },
+    ))
+    .on_conflict(
+        OnConflict::columns([
+            crate::entities::sensor_reading::Column::DeviceId,
+            crate::entities::sensor_reading::Column::Metric,
+            crate::entities::sensor_reading::Column::RecordedAt,
+        ])
+        .do_nothing()
+        .to_owned(),
+    )
+    .do_nothing()
+    .exec(db)
+    .await?;
+    Ok(())
+}
+
+/// The most recent reading of each metric of a device.
+pub async fn get_latest_readings(
+    device_id: &Uuid,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<crate::entities::sensor_reading::Model>> {
+    Ok(crate::entities::sensor_reading::Entity::find()
+        .distinct_on([crate::entities::sensor_reading::Column::Metric])
+        .filter(
+            crate::entities::sensor_reading::Column::DeviceId.eq(*device_id),
+        )
+        .order_by_asc(crate::entities::sensor_reading::Column::Metric)
+        .order_by_desc(crate::entities::sensor_reading::Column::RecordedAt)
+        .all(db)
+        .await?)
+}
+
+/// The readings of a metric recorded in `from..to`, oldest first, up to
+/// `limit` of them.
+pub async fn get_readings(
+    device_id: &Uuid,
+    metric: Metric,
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+    limit: u64,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<crate::entities::sensor_reading::Model>> {
+    Ok(crate::entities::sensor_reading::Entity::find()
+        .filter(
+            crate::entities::sensor_reading::Column::DeviceId.eq(*device_id),
+        )
+        .filter(crate::entities::sensor_reading::Column::Metric.eq(metric))
+        .filter(crate::entities::sensor_reading::Column::RecordedAt.gte(from))
+        .filter(crate::entities::sensor_reading::Column::RecordedAt.lt(to))
+        .order_by_asc(crate::entities::sensor_reading::Column::RecordedAt)
+        .limit(limit)
+        .all(db)
+        .await?)
+}
+
+/// Aggregates the readings of a metric recorded in `from..to` into buckets
+/// of `bucket_seconds`, aligned to the Unix epoch. Buckets without readings
+/// are left out.
+pub async fn get_buckets(
+    device_id: &Uuid,
+    metric: Metric,
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+    bucket_seconds: i64,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<Bucket>> {
+    // Inlined rather than bound, so that Postgres sees the same expression
+    // in the select list and the group by.
+    let start: SimpleExpr = Expr::cust(format!(
+        "to_timestamp(floor(extract(epoch FROM recorded_at) / \
+            {bucket_seconds}) * {bucket_seconds}) AT TIME ZONE 'UTC'"
+    ));
+    let value = || Expr::col(crate::entities::sensor_reading::Column::Value);
+    let buckets: Vec<(NaiveDateTime, f64, f64, f64, i64)> =
+        crate::entities::sensor_reading::Entity::find()
+            .select_only()
+            .column_as(start.clone(), "start")
+            .column_as(SimpleExpr::from(Func::min(value())), "min")
+            .column_as(SimpleExpr::from(Func::max(value())), "max")
+            .column_as(SimpleExpr::from(Func::avg(value())), "avg")
+            .column_as(SimpleExpr::from(Func::count(value())), "count")
+            .filter(
+                crate::entities::sensor_reading::Column::DeviceId
+                    .eq(*device_id),
+            )
+            .filter(crate::entities::sensor_reading::Column::Metric.eq(metric))
+            .filter(
+                crate::entities::sensor_reading::Column::RecordedAt.gte(from),
+            )
+            .filter(crate::entities::sensor_reading::Column::RecordedAt.lt(to))
+            .group_by(start.clone())
+            .order_by(start, Order::Asc)
+            .into_tuple()
+            .all(db)
+            .await?;
+    Ok(buckets
+        .into_iter()
+        .map(|(start, min, max, avg, count)| Bucket {
+            start,
+            min,
+            max,
+            avg,
+            count,
+        })
+        .collect())
+}
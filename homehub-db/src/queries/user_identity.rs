@@ -0,0 +1,76 @@
+use crate::{
+    user_identity::{ActiveModel, Column, Model},
+    UserIdentity as Entity,
+};
+use sea_orm::ActiveModelTrait;
+use sea_orm::ColumnTrait;
+use sea_orm::EntityTrait;
+use sea_orm::QueryFilter;
+use sea_orm::{prelude::Uuid, ActiveValue, DatabaseConnection};
+use sea_orm::{ConnectionTrait, TransactionTrait};
+
+pub async fn find_by_subject(
+    issuer: &str,
+    subject: &str,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<Model>> {
+    let identity = Entity::find()
+        .filter(Column::Issuer.eq(issuer))
+        .filter(Column::Subject.eq(subject))
+        .one(db)
+        .await?;
+    Ok(identity)
+}
+
+pub async fn create_identity(
+    user_id: Uuid,
+    issuer: &str,
+    subject: &str,
+    db: &impl ConnectionTrait,
+) -> anyhow::Result<Model> {
+    let identity = ActiveModel {
+        user_id: ActiveValue::Set(user_id),
+        issuer: ActiveValue::Set(issuer.to_owned()),
+        subject: ActiveValue::Set(subject.to_owned()),
+        ..Default::default()
+    };
+
+    Ok(identity.insert(db).await?)
+}
+
+/// Creates an account for someone signing in through an identity provider
+/// for the first time, together with the identity that links them, so that
+/// a failure cannot leave an account nobody can sign in to. Registration is
+/// gated like [`register_user`](crate::queries::app_user::register_user),
+/// except that there is no way to present an invite: when one is required,
+/// only the first user can register. Returns `None`, changing nothing, when
+/// the account may not be created.
+#[allow(clippy::too_many_arguments)]
+pub async fn create_user_with_identity(
+    name: &str,
+    email: &str,
+    password_hash: &str,
+    first_user_admin: bool,
+    invite_required: bool,
+    issuer: &str,
+    subject: &str,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<crate::app_user::Model>> {
+    let txn = db.begin().await?;
+    let Some(user) = crate::queries::app_user::register_user_in(
+        name,
+        email,
+        password_hash,
+        first_user_admin,
+        invite_required,
+        None,
+        &txn,
+    )
+    .await?
+    else {
+        return Ok(None);
+    };
+    create_identity(user.id, issuer, subject, &txn).await?;
+    txn.commit().await?;
+    Ok(Some(user))
+}
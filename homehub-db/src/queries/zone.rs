@@ -0,0 +1,245 @@
+use chrono::Utc;
+use sea_orm::sea_query::{Expr, OnConflict, Query};
+use sea_orm::{
+    prelude::Uuid, ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait,
+    DatabaseConnection, EntityTrait, JoinType, QueryFilter, QuerySelect,
+    RelationTrait, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::extra_models::light::LightState;
+use crate::pagination::{paginate, Page, PageRequest, SortKey, SortValue};
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ZoneSort {
+    #[default]
+    Name,
+}
+
+impl SortKey for ZoneSort {
+    type Entity = crate::entities::zone::Entity;
+
+    fn column(self) -> crate::entities::zone::Column {
+        match self {
+            ZoneSort::Name => crate::entities::zone::Column::Name,
+        }
+    }
+
+    fn value(self, model: &crate::entities::zone::Model) -> SortValue {
+        match self {
+            ZoneSort::Name => SortValue::Text(model.name.clone()),
+        }
+    }
+
+    fn id_column() -> crate::entities::zone::Column {
+        crate::entities::zone::Column::Id
+    }
+
+    fn id(model: &crate::entities::zone::Model) -> Uuid {
+        model.id
+    }
+}
+
+/// Adds lights to a zone, skipping those that are already in it.
+async fn add_members(
+    zone_id: Uuid,
+    light_ids: &[Uuid],
+    db: &impl ConnectionTrait,
+) -> anyhow::Result<()> {
+    if light_ids.is_empty() {
+        return Ok(());
+    }
+    crate::entities::zone_light::Entity::insert_many(light_ids.iter().map(
+        |light_id| crate::entities::zone_light::ActiveModel {
+            zone_id: ActiveValue::Set(zone_id),
+            light_id: ActiveValue::Set(*light_id),
+        },
+    ))
+    .on_conflict(
+        OnConflict::columns([
+            crate::entities::zone_light::Column::ZoneId,
+            crate::entities::zone_light::Column::LightId,
+        ])
+        .do_nothing()
+        .to_owned(),
+    )
+    .exec_without_returning(db)
+    .await?;
+    Ok(())
+}
+
+/// Bumps the version of a zone, if it is at one of the given versions.
+/// Returns whether it was.
+async fn bump_version(
+    id: &Uuid,
+    name: Option<&str>,
+    versions: Option<&[i32]>,
+    db: &impl ConnectionTrait,
+) -> anyhow::Result<bool> {
+    let mut update = crate::entities::zone::Entity::update_many()
+        .col_expr(
+            crate::entities::zone::Column::Version,
+            Expr::col(crate::entities::zone::Column::Version).add(1),
+        )
+        .col_expr(
+            crate::entities::zone::Column::UpdatedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(crate::entities::zone::Column::Id.eq(*id));
+    if let Some(name) = name {
+        update = update
+            .col_expr(crate::entities::zone::Column::Name, Expr::value(name));
+    }
+    if let Some(versions) = versions {
+        update = update.filter(
+            crate::entities::zone::Column::Version
+                .is_in(versions.iter().copied()),
+        );
+    }
+    Ok(update.exec(db).await?.rows_affected > 0)
+}
+
+pub async fn create_zone(
+    name: &str,
+    light_ids: &[Uuid],
+    db: &DatabaseConnection,
+) -> anyhow::Result<crate::entities::zone::Model> {
+    let txn = db.begin().await?;
+    let zone = crate::entities::zone::ActiveModel {
+        name: ActiveValue::Set(name.to_owned()),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await?;
+    add_members(zone.id, light_ids, &txn).await?;
+    txn.commit().await?;
+    Ok(zone)
+}
+
+pub async fn get_zone(
+    id: &Uuid,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<crate::entities::zone::Model>> {
+    let zone = crate::entities::zone::Entity::find_by_id(*id)
+        .one(db)
+        .await?;
+    Ok(zone)
+}
+
+/// Lists all zones, or only those a light is in.
+pub async fn get_zones(
+    light_id: Option<&Uuid>,
+    page: &PageRequest<ZoneSort>,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Page<crate::entities::zone::Model>> {
+    let mut select = crate::entities::zone::Entity::find();
+    if let Some(light_id) = light_id {
+        select = select.filter(
+            crate::entities::zone::Column::Id.in_subquery(
+                Query::select()
+                    .column(crate::entities::zone_light::Column::ZoneId)
+                    .from(crate::entities::zone_light::Entity)
+                    .and_where(
+                        crate::entities::zone_light::Column::LightId
+                            .eq(*light_id),
+                    )
+                    .to_owned(),
+            ),
+        );
+    }
+    paginate(select, page, db).await
+}
+
+/// Renames a zone and replaces its lights. Returns `None` when there is no
+/// zone with that id, or it is not at one of the given versions.
+pub async fn update_zone(
+    id: &Uuid,
+    name: Option<&str>,
+    light_ids: Option<&[Uuid]>,
+    versions: Option<&[i32]>,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<crate::entities::zone::Model>> {
+    let txn = db.begin().await?;
+    if !bump_version(id, name, versions, &txn).await? {
+        return Ok(None);
+    }
+    if let Some(light_ids) = light_ids {
+        crate::entities::zone_light::Entity::delete_many()
+            .filter(crate::entities::zone_light::Column::ZoneId.eq(*id))
+            .exec(&txn)
+            .await?;
+        add_members(*id, light_ids, &txn).await?;
+    }
+    txn.commit().await?;
+    get_zone(id, db).await
+}
+
+/// Adds a light to a zone, or removes it. Returns `None` when there is no
+/// zone with that id.
+pub async fn set_member(
+    id: &Uuid,
+    light_id: &Uuid,
+    member: bool,
+    db: &DatabaseConnection,
+) -> anyhow::Result<Option<crate::entities::zone::Model>> {
+    let txn = db.begin().await?;
+    if !bump_version(id, None, None, &txn).await? {
+        return Ok(None);
+    }
+    match member {
+        true => add_members(*id, &[*light_id], &txn).await?,
+        false => {
+            crate::entities::zone_light::Entity::delete_by_id((*id, *light_id))
+                .exec(&txn)
+                .await?;
+        }
+    }
+    txn.commit().await?;
+    get_zone(id, db).await
+}
+
+/// Deletes a zone, but not its lights. Returns whether the zone existed.
+pub async fn delete_zone(
+    id: &Uuid,
+    db: &DatabaseConnection,
+) -> anyhow::Result<bool> {
+    let txn = db.begin().await?;
+    crate::entities::zone_light::Entity::delete_many()
+        .filter(crate::entities::zone_light::Column::ZoneId.eq(*id))
+        .exec(&txn)
+        .await?;
+    let result = crate::entities::zone::Entity::delete_by_id(*id)
+        .exec(&txn)
+        .await?;
+    txn.commit().await?;
+    Ok(result.rows_affected > 0)
+}
+
+/// The lights in each of the given zones, as triples of zone id, light id
+/// and light state.
+pub async fn get_lights(
+    zone_ids: &[Uuid],
+    db: &DatabaseConnection,
+) -> anyhow::Result<Vec<(Uuid, Uuid, LightState)>> {
+    let lights = crate::entities::zone_light::Entity::find()
+        .select_only()
+        .column(crate::entities::zone_light::Column::ZoneId)
+        .column(crate::entities::light::Column::Id)
+        .column(crate::entities::light::Column::State)
+        .join(
+            JoinType::InnerJoin,
+            crate::entities::zone_light::Relation::Light.def(),
+        )
+        .filter(
+            crate::entities::zone_light::Column::ZoneId
+                .is_in(zone_ids.iter().copied()),
+        )
+        .into_tuple()
+        .all(db)
+        .await?;
+    Ok(lights)
+}
@@ -0,0 +1,152 @@
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use homehub_core::{error::Error, i18n::Locale, password::PasswordViolation};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// An error response in the locale of the request. Every error is sent in
+/// the same envelope, with a stable `code` for clients to branch on, a
+/// human readable `message` and, for some errors, `details`.
+#[derive(Debug)]
+pub struct ApiError {
+    pub error: Error,
+    pub locale: Locale,
+}
+
+impl ApiError {
+    pub fn new(error: Error, locale: &Locale) -> Self {
+        ApiError {
+            error,
+            locale: locale.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ErrorResponse {
+    status: &'static str,
+    #[schema(example = "light_not_found")]
+    code: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<ErrorDetails>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ErrorDetails {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    violations: Option<Vec<PasswordViolation>>,
+    /// Why the request could not be parsed, for malformed requests.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+impl From<&ApiError> for ErrorResponse {
+    fn from(value: &ApiError) -> Self {
+        let details = match &value.error {
+            Error::PasswordPolicyError(violations) => Some(ErrorDetails {
+                violations: Some(violations.clone()),
+                reason: None,
+            }),
+            error => error.reason().map(|reason| ErrorDetails {
+                violations: None,
+                reason: Some(reason.to_string()),
+            }),
+        };
+        ErrorResponse {
+            status: "error",
+            code: value.error.code(),
+            message: value.error.message(&value.locale),
+            details,
+        }
+    }
+}
+
+pub fn status_code(error: &Error) -> StatusCode {
+    match error {
+        Error::InvalidCredentialError
+        | Error::MissingTokenError
+        | Error::UnauthorizedError
+        | Error::OidcProviderError(_)
+        | Error::OidcCodeExchangeError(_)
+        | Error::OidcInvalidIdTokenError(_) => StatusCode::UNAUTHORIZED,
+        Error::AccountDisabledError
+        | Error::PasswordResetRequiredError
+        | Error::RegistrationDisabledError
+        | Error::InvalidInviteError
+        | Error::CurrentPasswordIncorrectError
+        | Error::MissingScopeError(_)
+        | Error::FirstPartyOnlyError
+        | Error::AdminRequiredError => StatusCode::FORBIDDEN,
+        Error::OidcNotConfiguredError
+        | Error::OAuthUnknownClientError
+        | Error::OAuthClientNotFoundError
+        | Error::UserNotFoundError
+        | Error::LightNotFoundError
+        | Error::RoomNotFoundError
+        | Error::LocationNotFoundError
+        | Error::ZoneNotFoundError
+        | Error::DeviceNotFoundError
+        | Error::DeviceProfileNotFoundError => StatusCode::NOT_FOUND,
+        Error::OidcUnverifiedEmailError(_)
+        | Error::IdempotencyKeyInProgressError => StatusCode::CONFLICT,
+        Error::PasswordPolicyError(_)
+        | Error::IdempotencyKeyReusedError
+        | Error::UnsupportedCapabilityError(_)
+        | Error::InvalidDeviceStateError
+        | Error::DeviceProfileKindError
+        | Error::InvalidReadingError
+        | Error::InvalidRequestBodyError(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        Error::RequestBodyTooLargeError => StatusCode::PAYLOAD_TOO_LARGE,
+        Error::UnsupportedMediaTypeError => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        Error::PreconditionFailedError => StatusCode::PRECONDITION_FAILED,
+        Error::TooManyLoginAttemptsError { .. }
+        | Error::TooManyRequestsError { .. } => StatusCode::TOO_MANY_REQUESTS,
+        Error::DbError(_)
+        | Error::CouldNotHashError
+        | Error::TokenGenerationError
+        | Error::MailError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        Error::UserAlreadyExistsError { .. }
+        | Error::InvalidResetTokenError
+        | Error::InvalidVerificationTokenError
+        | Error::OidcMissingCodeError
+        | Error::OidcUnknownStateError
+        | Error::OAuthInvalidRedirectUriError
+        | Error::OAuthInvalidScopeError(_)
+        | Error::OAuthUnsupportedResponseTypeError
+        | Error::OAuthInvalidCodeChallengeError
+        | Error::OAuthInvalidGrantError
+        | Error::OAuthInvalidClientError
+        | Error::OAuthUnsupportedGrantTypeError
+        | Error::AdminSelfModificationError
+        | Error::InvalidCursorError
+        | Error::InvalidReadingRangeError
+        | Error::InvalidIdempotencyKeyError
+        | Error::InvalidJsonError(_)
+        | Error::InvalidPathError(_)
+        | Error::InvalidQueryError(_) => StatusCode::BAD_REQUEST,
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = status_code(&self.error);
+        if status.is_server_error() {
+            tracing::error!("{:?}", self.error);
+        }
+
+        let mut response =
+            (status, Json(ErrorResponse::from(&self))).into_response();
+        if let Some(retry_after) = self.error.retry_after() {
+            let retry_after_secs =
+                retry_after.as_secs_f64().ceil().max(1.0) as u64;
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, retry_after_secs.into());
+        }
+        response
+    }
+}
@@ -0,0 +1,745 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_graphql::{
+    dataloader::{DataLoader, Loader},
+    Context, Enum, ErrorExtensions, InputObject, Object, OutputType, Schema,
+    SimpleObject, Subscription,
+};
+use chrono::NaiveDateTime;
+use homehub_core::{
+    error::Error,
+    light::{LightDto, LightFilter, RoomDto},
+    location::LocationDto,
+    pagination::{
+        page_request, PageRequest, SortKey, DEFAULT_PAGE_LIMIT, MAX_PAGE_LIMIT,
+    },
+    zone::ZoneDto,
+};
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+
+use crate::{
+    error::{status_code, ApiError},
+    middleware::jwt_auth::JWTAuthMiddleware,
+    state::AppState,
+};
+
+pub type HomeHubSchema = Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
+
+/// Deep enough for the introspection query that GraphiQL sends.
+const MAX_DEPTH: usize = 16;
+/// Every field costs one, and lists cost their page size times their items.
+/// This allows a list nested in a list at the default page size, but not
+/// three levels of them.
+const MAX_COMPLEXITY: usize = 50_000;
+
+pub fn schema() -> HomeHubSchema {
+    Schema::build(QueryRoot, MutationRoot, SubscriptionRoot)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+/// The complexity of a list field, which returns up to `limit` items.
+fn page_complexity(limit: Option<u64>, child_complexity: usize) -> usize {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT);
+    child_complexity.saturating_mul(limit as usize)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RoomId(uuid::Uuid);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LocationId(uuid::Uuid);
+
+/// Looks up the rooms and locations that nested fields refer to in batches,
+/// so that listing many lights or rooms does not query them one by one.
+pub struct HomeLoader {
+    data: Arc<AppState>,
+    jwt: JWTAuthMiddleware,
+}
+
+/// A loader for one request, which the handlers add to every operation.
+pub fn loader(
+    data: Arc<AppState>,
+    jwt: JWTAuthMiddleware,
+) -> DataLoader<HomeLoader> {
+    DataLoader::new(HomeLoader { data, jwt }, tokio::spawn)
+}
+
+impl Loader<RoomId> for HomeLoader {
+    type Value = RoomDto;
+    type Error = async_graphql::Error;
+
+    async fn load(
+        &self,
+        keys: &[RoomId],
+    ) -> Result<HashMap<RoomId, RoomDto>, Self::Error> {
+        let ids: Vec<_> = keys.iter().map(|key| key.0).collect();
+        let rooms =
+            homehub_core::location::get_rooms_by_ids(&ids, &self.data.db)
+                .await
+                .map_err(|e| self.jwt.error(e))?;
+        Ok(rooms
+            .into_iter()
+            .map(|room| (RoomId(room.id), room))
+            .collect())
+    }
+}
+
+impl Loader<LocationId> for HomeLoader {
+    type Value = LocationDto;
+    type Error = async_graphql::Error;
+
+    async fn load(
+        &self,
+        keys: &[LocationId],
+    ) -> Result<HashMap<LocationId, LocationDto>, Self::Error> {
+        let ids: Vec<_> = keys.iter().map(|key| key.0).collect();
+        let locations =
+            homehub_core::location::get_locations_by_ids(&ids, &self.data.db)
+                .await
+                .map_err(|e| self.jwt.error(e))?;
+        Ok(locations
+            .into_iter()
+            .map(|location| (LocationId(location.id), location))
+            .collect())
+    }
+}
+
+impl From<ApiError> for async_graphql::Error {
+    fn from(value: ApiError) -> Self {
+        if status_code(&value.error).is_server_error() {
+            tracing::error!("{:?}", value.error);
+        }
+        async_graphql::Error::new(value.error.message(&value.locale))
+            .extend_with(|_, extensions| {
+                extensions.set("code", value.error.code())
+            })
+    }
+}
+
+/// The state and user of the request, which the handlers add to every
+/// operation.
+fn request<'a>(
+    ctx: &Context<'a>,
+) -> (&'a Arc<AppState>, &'a JWTAuthMiddleware) {
+    (ctx.data_unchecked(), ctx.data_unchecked())
+}
+
+/// Reads the pagination arguments of a list field.
+fn page<S: SortKey + Default>(
+    jwt: &JWTAuthMiddleware,
+    sort: Option<S>,
+    order: Option<SortOrder>,
+    after: Option<String>,
+    limit: Option<u64>,
+) -> Result<PageRequest<S>, ApiError> {
+    page_request(
+        sort.unwrap_or_default(),
+        order.map(Into::into).unwrap_or_default(),
+        after.as_deref(),
+        limit,
+    )
+    .map_err(|e| jwt.error(e))
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(remote = "homehub_core::pagination::SortOrder")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(remote = "homehub_core::light::LightSort")]
+pub enum LightSort {
+    Name,
+    UpdatedAt,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(remote = "homehub_core::location::RoomSort")]
+pub enum RoomSort {
+    Name,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(remote = "homehub_core::location::LocationSort")]
+pub enum LocationSort {
+    Name,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(remote = "homehub_core::zone::ZoneSort")]
+pub enum ZoneSort {
+    Name,
+}
+
+/// One page of a list. Pass `nextCursor` as `after` to get the next page; it
+/// is null on the last one.
+#[derive(SimpleObject)]
+#[graphql(concrete(name = "LightPage", params(Light)))]
+#[graphql(concrete(name = "RoomPage", params(Room)))]
+#[graphql(concrete(name = "LocationPage", params(Location)))]
+#[graphql(concrete(name = "ZonePage", params(Zone)))]
+pub struct Page<T: OutputType> {
+    items: Vec<T>,
+    next_cursor: Option<String>,
+}
+
+impl<T: OutputType> Page<T> {
+    fn new<U>(
+        page: homehub_core::pagination::Page<U>,
+        f: impl FnMut(U) -> T,
+    ) -> Self {
+        let page = page.map(f);
+        Page {
+            items: page.items,
+            next_cursor: page.next_cursor,
+        }
+    }
+}
+
+/// Lists lights, optionally narrowed down by the filter arguments.
+async fn lights(
+    ctx: &Context<'_>,
+    filter: LightFilter,
+    sort: Option<LightSort>,
+    order: Option<SortOrder>,
+    after: Option<String>,
+    limit: Option<u64>,
+) -> async_graphql::Result<Page<Light>> {
+    let (data, jwt) = request(ctx);
+    let page = page(jwt, sort.map(Into::into), order, after, limit)?;
+    let lights = homehub_core::light::get_lights(&filter, &page, &data.db)
+        .await
+        .map_err(|e| jwt.error(e))?;
+    Ok(Page::new(lights, Light))
+}
+
+#[derive(SimpleObject, InputObject)]
+#[graphql(name = "LightState", input_name = "LightStateInput")]
+pub struct LightState {
+    on: bool,
+    colour: Option<[u8; 3]>,
+}
+
+impl From<homehub_core::light::LightState> for LightState {
+    fn from(value: homehub_core::light::LightState) -> Self {
+        LightState {
+            on: value.on,
+            colour: value.colour,
+        }
+    }
+}
+
+impl From<LightState> for homehub_core::light::LightState {
+    fn from(value: LightState) -> Self {
+        homehub_core::light::LightState {
+            on: value.on,
+            colour: value.colour,
+        }
+    }
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(remote = "homehub_core::light::AggregatePower")]
+pub enum AggregatePower {
+    AllOn,
+    SomeOn,
+    AllOff,
+}
+
+/// The combined state of a group of lights.
+#[derive(SimpleObject)]
+pub struct AggregateState {
+    power: AggregatePower,
+    /// The average colour of the lights that are on and have a colour.
+    colour: Option<[u8; 3]>,
+    light_count: usize,
+}
+
+impl From<homehub_core::light::AggregateState> for AggregateState {
+    fn from(value: homehub_core::light::AggregateState) -> Self {
+        AggregateState {
+            power: value.power.into(),
+            colour: value.colour,
+            light_count: value.light_count,
+        }
+    }
+}
+
+pub struct Location(LocationDto);
+
+#[Object]
+impl Location {
+    async fn id(&self) -> uuid::Uuid {
+        self.0.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn version(&self) -> i32 {
+        self.0.version
+    }
+
+    async fn state(&self) -> AggregateState {
+        self.0.state.clone().into()
+    }
+
+    #[graphql(complexity = "page_complexity(limit, child_complexity)")]
+    async fn rooms(
+        &self,
+        ctx: &Context<'_>,
+        sort: Option<RoomSort>,
+        order: Option<SortOrder>,
+        after: Option<String>,
+        limit: Option<u64>,
+    ) -> async_graphql::Result<Page<Room>> {
+        let (data, jwt) = request(ctx);
+        let page = page(jwt, sort.map(Into::into), order, after, limit)?;
+        let rooms = homehub_core::location::get_rooms(
+            Some(&self.0.id),
+            &page,
+            &data.db,
+        )
+        .await
+        .map_err(|e| jwt.error(e))?;
+        Ok(Page::new(rooms, Room))
+    }
+}
+
+pub struct Room(RoomDto);
+
+#[Object]
+impl Room {
+    async fn id(&self) -> uuid::Uuid {
+        self.0.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn version(&self) -> i32 {
+        self.0.version
+    }
+
+    async fn state(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<AggregateState> {
+        if let Some(state) = &self.0.state {
+            return Ok(state.clone().into());
+        }
+        // Rooms of lights come without their state.
+        let (_, jwt) = request(ctx);
+        let room = ctx
+            .data_unchecked::<DataLoader<HomeLoader>>()
+            .load_one(RoomId(self.0.id))
+            .await?
+            .ok_or_else(|| jwt.error(Error::RoomNotFoundError))?;
+        Ok(room.state.map(Into::into).unwrap_or_else(|| {
+            homehub_core::light::AggregateState::of([]).into()
+        }))
+    }
+
+    async fn location(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Location> {
+        let (_, jwt) = request(ctx);
+        let location = ctx
+            .data_unchecked::<DataLoader<HomeLoader>>()
+            .load_one(LocationId(self.0.location_id))
+            .await?
+            .ok_or_else(|| jwt.error(Error::LocationNotFoundError))?;
+        Ok(Location(location))
+    }
+
+    #[graphql(complexity = "page_complexity(limit, child_complexity)")]
+    #[allow(clippy::too_many_arguments)]
+    async fn lights(
+        &self,
+        ctx: &Context<'_>,
+        name: Option<String>,
+        on: Option<bool>,
+        sort: Option<LightSort>,
+        order: Option<SortOrder>,
+        after: Option<String>,
+        limit: Option<u64>,
+    ) -> async_graphql::Result<Page<Light>> {
+        let filter = LightFilter {
+            name,
+            room_id: Some(self.0.id),
+            on,
+            ..Default::default()
+        };
+        lights(ctx, filter, sort, order, after, limit).await
+    }
+}
+
+pub struct Zone(ZoneDto);
+
+#[Object]
+impl Zone {
+    async fn id(&self) -> uuid::Uuid {
+        self.0.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn version(&self) -> i32 {
+        self.0.version
+    }
+
+    async fn state(&self) -> AggregateState {
+        self.0.state.clone().into()
+    }
+
+    async fn updated_at(&self) -> NaiveDateTime {
+        self.0.updated_at
+    }
+
+    #[graphql(complexity = "page_complexity(limit, child_complexity)")]
+    #[allow(clippy::too_many_arguments)]
+    async fn lights(
+        &self,
+        ctx: &Context<'_>,
+        name: Option<String>,
+        on: Option<bool>,
+        sort: Option<LightSort>,
+        order: Option<SortOrder>,
+        after: Option<String>,
+        limit: Option<u64>,
+    ) -> async_graphql::Result<Page<Light>> {
+        let filter = LightFilter {
+            name,
+            zone_id: Some(self.0.id),
+            on,
+            ..Default::default()
+        };
+        lights(ctx, filter, sort, order, after, limit).await
+    }
+}
+
+pub struct Light(LightDto);
+
+#[Object]
+impl Light {
+    async fn id(&self) -> uuid::Uuid {
+        self.0.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn version(&self) -> i32 {
+        self.0.version
+    }
+
+    async fn state(&self) -> LightState {
+        self.0.state.clone().into()
+    }
+
+    async fn room(&self) -> Option<Room> {
+        self.0.room.clone().map(Room)
+    }
+
+    async fn updated_at(&self) -> NaiveDateTime {
+        self.0.updated_at
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    #[graphql(complexity = "page_complexity(limit, child_complexity)")]
+    async fn locations(
+        &self,
+        ctx: &Context<'_>,
+        sort: Option<LocationSort>,
+        order: Option<SortOrder>,
+        after: Option<String>,
+        limit: Option<u64>,
+    ) -> async_graphql::Result<Page<Location>> {
+        let (data, jwt) = request(ctx);
+        jwt.require_scope("lights:read")?;
+        let page = page(jwt, sort.map(Into::into), order, after, limit)?;
+        let locations = homehub_core::location::get_locations(&page, &data.db)
+            .await
+            .map_err(|e| jwt.error(e))?;
+        Ok(Page::new(locations, Location))
+    }
+
+    async fn location(
+        &self,
+        ctx: &Context<'_>,
+        id: uuid::Uuid,
+    ) -> async_graphql::Result<Location> {
+        let (data, jwt) = request(ctx);
+        jwt.require_scope("lights:read")?;
+        let location = homehub_core::location::get_location(&id, &data.db)
+            .await
+            .map_err(|e| jwt.error(e))?;
+        Ok(Location(location))
+    }
+
+    #[graphql(complexity = "page_complexity(limit, child_complexity)")]
+    async fn rooms(
+        &self,
+        ctx: &Context<'_>,
+        sort: Option<RoomSort>,
+        order: Option<SortOrder>,
+        after: Option<String>,
+        limit: Option<u64>,
+    ) -> async_graphql::Result<Page<Room>> {
+        let (data, jwt) = request(ctx);
+        jwt.require_scope("lights:read")?;
+        let page = page(jwt, sort.map(Into::into), order, after, limit)?;
+        let rooms = homehub_core::location::get_rooms(None, &page, &data.db)
+            .await
+            .map_err(|e| jwt.error(e))?;
+        Ok(Page::new(rooms, Room))
+    }
+
+    async fn room(
+        &self,
+        ctx: &Context<'_>,
+        id: uuid::Uuid,
+    ) -> async_graphql::Result<Room> {
+        let (data, jwt) = request(ctx);
+        jwt.require_scope("lights:read")?;
+        let room = homehub_core::location::get_room(&id, &data.db)
+            .await
+            .map_err(|e| jwt.error(e))?;
+        Ok(Room(room))
+    }
+
+    #[graphql(complexity = "page_complexity(limit, child_complexity)")]
+    #[allow(clippy::too_many_arguments)]
+    async fn lights(
+        &self,
+        ctx: &Context<'_>,
+        room_id: Option<uuid::Uuid>,
+        zone_id: Option<uuid::Uuid>,
+        name: Option<String>,
+        on: Option<bool>,
+        sort: Option<LightSort>,
+        order: Option<SortOrder>,
+        after: Option<String>,
+        limit: Option<u64>,
+    ) -> async_graphql::Result<Page<Light>> {
+        let (_, jwt) = request(ctx);
+        jwt.require_scope("lights:read")?;
+        let filter = LightFilter {
+            name,
+            room_id,
+            zone_id,
+            on,
+        };
+        lights(ctx, filter, sort, order, after, limit).await
+    }
+
+    #[graphql(complexity = "page_complexity(limit, child_complexity)")]
+    async fn zones(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "Only list zones this light is in.")] light_id: Option<
+            uuid::Uuid,
+        >,
+        sort: Option<ZoneSort>,
+        order: Option<SortOrder>,
+        after: Option<String>,
+        limit: Option<u64>,
+    ) -> async_graphql::Result<Page<Zone>> {
+        let (data, jwt) = request(ctx);
+        jwt.require_scope("lights:read")?;
+        let page = page(jwt, sort.map(Into::into), order, after, limit)?;
+        let zones =
+            homehub_core::zone::get_zones(light_id.as_ref(), &page, &data.db)
+                .await
+                .map_err(|e| jwt.error(e))?;
+        Ok(Page::new(zones, Zone))
+    }
+
+    async fn zone(
+        &self,
+        ctx: &Context<'_>,
+        id: uuid::Uuid,
+    ) -> async_graphql::Result<Zone> {
+        let (data, jwt) = request(ctx);
+        jwt.require_scope("lights:read")?;
+        let zone = homehub_core::zone::get_zone(&id, &data.db)
+            .await
+            .map_err(|e| jwt.error(e))?;
+        Ok(Zone(zone))
+    }
+
+    async fn light(
+        &self,
+        ctx: &Context<'_>,
+        id: uuid::Uuid,
+    ) -> async_graphql::Result<Light> {
+        let (data, jwt) = request(ctx);
+        jwt.require_scope("lights:read")?;
+        let light = homehub_core::light::get_light(&id, &data.db)
+            .await
+            .map_err(|e| jwt.error(e))?;
+        Ok(Light(light))
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    async fn set_light_state(
+        &self,
+        ctx: &Context<'_>,
+        id: uuid::Uuid,
+        state: LightState,
+        #[graphql(desc = "Only update the light if it is at this version.")]
+        version: Option<i32>,
+    ) -> async_graphql::Result<Light> {
+        let (data, jwt) = request(ctx);
+        jwt.require_scope("lights:write")?;
+        let light = homehub_core::light::set_light_state(
+            &id,
+            state.into(),
+            version.as_ref().map(std::slice::from_ref),
+            &data.db,
+            &data.light_events,
+        )
+        .await
+        .map_err(|e| jwt.error(e))?;
+        Ok(Light(light))
+    }
+
+    /// Switches every light in a room at once.
+    async fn set_room_state(
+        &self,
+        ctx: &Context<'_>,
+        id: uuid::Uuid,
+        state: LightState,
+    ) -> async_graphql::Result<Room> {
+        let (data, jwt) = request(ctx);
+        jwt.require_scope("lights:write")?;
+        let room = homehub_core::location::set_room_state(
+            &id,
+            state.into(),
+            &data.db,
+            &data.light_events,
+        )
+        .await
+        .map_err(|e| jwt.error(e))?;
+        Ok(Room(room))
+    }
+
+    /// Switches every light in all rooms of a location at once.
+    async fn set_location_state(
+        &self,
+        ctx: &Context<'_>,
+        id: uuid::Uuid,
+        state: LightState,
+    ) -> async_graphql::Result<Location> {
+        let (data, jwt) = request(ctx);
+        jwt.require_scope("lights:write")?;
+        let location = homehub_core::location::set_location_state(
+            &id,
+            state.into(),
+            &data.db,
+            &data.light_events,
+        )
+        .await
+        .map_err(|e| jwt.error(e))?;
+        Ok(Location(location))
+    }
+
+    /// Switches every light in a zone at once.
+    async fn set_zone_state(
+        &self,
+        ctx: &Context<'_>,
+        id: uuid::Uuid,
+        state: LightState,
+    ) -> async_graphql::Result<Zone> {
+        let (data, jwt) = request(ctx);
+        jwt.require_scope("lights:write")?;
+        let zone = homehub_core::zone::set_zone_state(
+            &id,
+            state.into(),
+            &data.db,
+            &data.light_events,
+        )
+        .await
+        .map_err(|e| jwt.error(e))?;
+        Ok(Zone(zone))
+    }
+}
+
+pub struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    /// Every change to the state of a light, or only of the given lights.
+    async fn light_state_changed(
+        &self,
+        ctx: &Context<'_>,
+        ids: Option<Vec<uuid::Uuid>>,
+    ) -> async_graphql::Result<impl Stream<Item = Light>> {
+        let (data, jwt) = request(ctx);
+        jwt.require_scope("lights:read")?;
+        // A listener that falls too far behind skips the changes it missed
+        // rather than ending the subscription.
+        let changes = BroadcastStream::new(data.light_events.subscribe())
+            .filter_map(Result::ok)
+            .filter(move |light| {
+                ids.as_ref().is_none_or(|ids| ids.contains(&light.id))
+            })
+            .map(Light);
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn validation_error(query: &str) -> String {
+        let response = schema().execute(query).await;
+        response.errors.into_iter().next().unwrap().message
+    }
+
+    #[tokio::test]
+    async fn rejects_deep_queries() {
+        let query = format!(
+            "{{ __schema {{ types {{ {} name {} }} }} }}",
+            "ofType { ".repeat(MAX_DEPTH),
+            "}".repeat(MAX_DEPTH),
+        );
+
+        assert!(validation_error(&query).await.contains("nested too deep"));
+    }
+
+    #[tokio::test]
+    async fn rejects_lists_nested_three_deep() {
+        let query = "{ locations { items { rooms { items { lights { items \
+                     { id } } } } } } }";
+
+        assert!(validation_error(query).await.contains("too complex"));
+    }
+
+    #[tokio::test]
+    async fn rejects_large_pages_of_nested_lists() {
+        let query = "{ rooms(limit: 200) { items { lights(limit: 200) \
+                     { items { id } } } } }";
+
+        assert!(validation_error(query).await.contains("too complex"));
+    }
+}
@@ -0,0 +1,382 @@
+// Every tonic API returns `Status`, so it cannot be made smaller here.
+#![allow(clippy::result_large_err)]
+
+use std::{pin::Pin, sync::Arc};
+
+use axum::http::StatusCode;
+use homehub_core::{
+    error::Error,
+    light::{LightDto, LightFilter, LightSort, LightState, RoomDto},
+    pagination::{page_request, SortOrder},
+};
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+use tonic::{
+    metadata::{MetadataMap, MetadataValue},
+    service::{interceptor, interceptor::InterceptorLayer, Interceptor},
+    Code, Request, Response, Status,
+};
+
+use crate::{
+    error::{status_code, ApiError},
+    middleware::{
+        jwt_auth::{authenticate, JWTAuthMiddleware},
+        rate_limit::RateLimitKey,
+    },
+    state::AppState,
+    util::{client_ip::client_ip_from, locale::accept_language_locale},
+};
+
+pub mod proto {
+    tonic::include_proto!("homehub.v1");
+}
+
+use proto::lights_server::{Lights, LightsServer};
+
+/// Sent with every failed call, like the `code` of HTTP error responses.
+const ERROR_CODE_METADATA: &str = "error-code";
+
+impl From<ApiError> for Status {
+    fn from(value: ApiError) -> Self {
+        let status = status_code(&value.error);
+        if status.is_server_error() {
+            tracing::error!("{:?}", value.error);
+        }
+        let code = match status {
+            StatusCode::UNAUTHORIZED => Code::Unauthenticated,
+            StatusCode::FORBIDDEN => Code::PermissionDenied,
+            StatusCode::NOT_FOUND => Code::NotFound,
+            StatusCode::CONFLICT => Code::AlreadyExists,
+            StatusCode::PRECONDITION_FAILED => Code::FailedPrecondition,
+            StatusCode::TOO_MANY_REQUESTS => Code::ResourceExhausted,
+            StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => {
+                Code::InvalidArgument
+            }
+            _ => Code::Internal,
+        };
+        let mut metadata = MetadataMap::new();
+        metadata.insert(
+            ERROR_CODE_METADATA,
+            MetadataValue::from_static(value.error.code()),
+        );
+        Status::with_metadata(
+            code,
+            value.error.message(&value.locale),
+            metadata,
+        )
+    }
+}
+
+/// Applies the HTTP API's rate limit to every call: per user for calls with
+/// a valid bearer token, per client IP otherwise. This runs before the call
+/// is authenticated, so the token is only checked, not the user behind it.
+pub fn rate_limit_layer(
+    data: Arc<AppState>,
+) -> InterceptorLayer<impl Interceptor + Clone> {
+    interceptor(move |request| rate_limit(&data, request))
+}
+
+fn rate_limit(
+    data: &AppState,
+    request: Request<()>,
+) -> Result<Request<()>, Status> {
+    let headers = request.metadata().clone().into_headers();
+    let user_id = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|auth_header| auth_header.to_str().ok())
+        .and_then(|auth_value| auth_value.strip_prefix("Bearer "))
+        .and_then(|access_token| {
+            homehub_core::token::verify_jwt_token(
+                &data.config.access_token_keys,
+                access_token,
+            )
+            .ok()
+        })
+        .map(|access_token| access_token.user_id);
+    let key = match user_id {
+        Some(user_id) => RateLimitKey::User(user_id),
+        None => RateLimitKey::Ip(
+            client_ip_from(
+                &headers,
+                request.remote_addr().map(|addr| addr.ip()),
+                data,
+            )
+            .ok_or_else(|| Status::internal("Unknown client address"))?,
+        ),
+    };
+    data.rate_limiter.acquire(key).map_err(|retry_after| {
+        ApiError::new(
+            Error::TooManyRequestsError { retry_after },
+            &accept_language_locale(&headers, None),
+        )
+    })?;
+    Ok(request)
+}
+
+fn parse_id(id: &str, field: &str) -> Result<uuid::Uuid, Status> {
+    id.parse().map_err(|_| {
+        Status::invalid_argument(format!("{field} is not a valid id"))
+    })
+}
+
+fn parse_colour_channel(value: u32) -> Result<u8, Status> {
+    u8::try_from(value).map_err(|_| {
+        Status::invalid_argument("colour channels must be between 0 and 255")
+    })
+}
+
+impl From<LightState> for proto::LightState {
+    fn from(value: LightState) -> Self {
+        proto::LightState {
+            on: value.on,
+            colour: value.colour.map(|[red, green, blue]| proto::Colour {
+                red: red.into(),
+                green: green.into(),
+                blue: blue.into(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<proto::LightState> for LightState {
+    type Error = Status;
+
+    fn try_from(value: proto::LightState) -> Result<Self, Self::Error> {
+        let colour = value
+            .colour
+            .map(|colour| {
+                Ok::<_, Status>([
+                    parse_colour_channel(colour.red)?,
+                    parse_colour_channel(colour.green)?,
+                    parse_colour_channel(colour.blue)?,
+                ])
+            })
+            .transpose()?;
+        Ok(LightState {
+            on: value.on,
+            colour,
+        })
+    }
+}
+
+impl From<RoomDto> for proto::Room {
+    fn from(value: RoomDto) -> Self {
+        proto::Room {
+            id: value.id.to_string(),
+            name: value.name,
+            location_id: value.location_id.to_string(),
+            version: value.version,
+        }
+    }
+}
+
+impl From<LightDto> for proto::Light {
+    fn from(value: LightDto) -> Self {
+        proto::Light {
+            id: value.id.to_string(),
+            name: value.name,
+            state: Some(value.state.into()),
+            room: value.room.map(Into::into),
+            version: value.version,
+            profile_id: value.profile.map(|profile| profile.id),
+        }
+    }
+}
+
+/// The `Lights` gRPC service, sharing its state with the HTTP API.
+pub struct LightService {
+    data: Arc<AppState>,
+}
+
+impl LightService {
+    pub fn server(data: Arc<AppState>) -> LightsServer<Self> {
+        LightsServer::new(LightService { data })
+    }
+
+    /// Authenticates a call by the bearer token in its `authorization`
+    /// metadata, as the HTTP API does with the header of the same name.
+    async fn authenticate<T>(
+        &self,
+        request: &Request<T>,
+    ) -> Result<JWTAuthMiddleware, Status> {
+        let headers = request.metadata().clone().into_headers();
+        Ok(authenticate(&self.data, &headers).await?)
+    }
+}
+
+type WatchLightsStream =
+    Pin<Box<dyn Stream<Item = Result<proto::Light, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Lights for LightService {
+    async fn list_lights(
+        &self,
+        request: Request<proto::ListLightsRequest>,
+    ) -> Result<Response<proto::ListLightsResponse>, Status> {
+        let jwt = self.authenticate(&request).await?;
+        jwt.require_scope("lights:read")?;
+        let payload = request.into_inner();
+        let sort = match payload.sort() {
+            proto::LightSort::Name => LightSort::Name,
+            proto::LightSort::UpdatedAt => LightSort::UpdatedAt,
+        };
+        let order = match payload.order() {
+            proto::SortOrder::Asc => SortOrder::Asc,
+            proto::SortOrder::Desc => SortOrder::Desc,
+        };
+        let page =
+            page_request(sort, order, payload.after.as_deref(), payload.limit)
+                .map_err(|e| jwt.error(e))?;
+        let filter = LightFilter {
+            room_id: payload
+                .room_id
+                .as_deref()
+                .map(|room_id| parse_id(room_id, "room_id"))
+                .transpose()?,
+            zone_id: payload
+                .zone_id
+                .as_deref()
+                .map(|zone_id| parse_id(zone_id, "zone_id"))
+                .transpose()?,
+            name: payload.name,
+            on: payload.on,
+        };
+        let lights =
+            homehub_core::light::get_lights(&filter, &page, &self.data.db)
+                .await
+                .map_err(|e| jwt.error(e))?;
+        Ok(Response::new(proto::ListLightsResponse {
+            lights: lights.items.into_iter().map(Into::into).collect(),
+            next_cursor: lights.next_cursor,
+        }))
+    }
+
+    async fn get_light(
+        &self,
+        request: Request<proto::GetLightRequest>,
+    ) -> Result<Response<proto::Light>, Status> {
+        let jwt = self.authenticate(&request).await?;
+        jwt.require_scope("lights:read")?;
+        let id = parse_id(&request.get_ref().id, "id")?;
+        let light = homehub_core::light::get_light(&id, &self.data.db)
+            .await
+            .map_err(|e| jwt.error(e))?;
+        Ok(Response::new(light.into()))
+    }
+
+    async fn create_light(
+        &self,
+        request: Request<proto::CreateLightRequest>,
+    ) -> Result<Response<proto::Light>, Status> {
+        let jwt = self.authenticate(&request).await?;
+        jwt.require_scope("lights:write")?;
+        let payload = request.into_inner();
+        let room_id = payload
+            .room_id
+            .as_deref()
+            .map(|room_id| parse_id(room_id, "room_id"))
+            .transpose()?;
+        let light = homehub_core::light::create_light(
+            &payload.name,
+            room_id,
+            payload.profile_id.as_deref(),
+            &self.data.db,
+        )
+        .await
+        .map_err(|e| jwt.error(e))?;
+        Ok(Response::new(light.into()))
+    }
+
+    async fn update_light(
+        &self,
+        request: Request<proto::UpdateLightRequest>,
+    ) -> Result<Response<proto::Light>, Status> {
+        let jwt = self.authenticate(&request).await?;
+        jwt.require_scope("lights:write")?;
+        let payload = request.into_inner();
+        let id = parse_id(&payload.id, "id")?;
+        let room_id = payload
+            .room
+            .map(|room| {
+                room.room_id
+                    .as_deref()
+                    .map(|room_id| parse_id(room_id, "room_id"))
+                    .transpose()
+            })
+            .transpose()?;
+        let profile_id = payload
+            .profile
+            .as_ref()
+            .map(|profile| profile.profile_id.as_deref());
+        let light = homehub_core::light::update_light(
+            &id,
+            payload.name.as_deref(),
+            room_id,
+            profile_id,
+            payload.version.as_ref().map(std::slice::from_ref),
+            &self.data.db,
+        )
+        .await
+        .map_err(|e| jwt.error(e))?;
+        Ok(Response::new(light.into()))
+    }
+
+    async fn delete_light(
+        &self,
+        request: Request<proto::DeleteLightRequest>,
+    ) -> Result<Response<proto::DeleteLightResponse>, Status> {
+        let jwt = self.authenticate(&request).await?;
+        jwt.require_scope("lights:write")?;
+        let id = parse_id(&request.get_ref().id, "id")?;
+        homehub_core::light::delete_light(&id, &self.data.db)
+            .await
+            .map_err(|e| jwt.error(e))?;
+        Ok(Response::new(proto::DeleteLightResponse {}))
+    }
+
+    async fn set_light_state(
+        &self,
+        request: Request<proto::SetLightStateRequest>,
+    ) -> Result<Response<proto::Light>, Status> {
+        let jwt = self.authenticate(&request).await?;
+        jwt.require_scope("lights:write")?;
+        let payload = request.into_inner();
+        let id = parse_id(&payload.id, "id")?;
+        let state = payload
+            .state
+            .ok_or_else(|| Status::invalid_argument("state is required"))?
+            .try_into()?;
+        let light = homehub_core::light::set_light_state(
+            &id,
+            state,
+            payload.version.as_ref().map(std::slice::from_ref),
+            &self.data.db,
+            &self.data.light_events,
+        )
+        .await
+        .map_err(|e| jwt.error(e))?;
+        Ok(Response::new(light.into()))
+    }
+
+    type WatchLightsStream = WatchLightsStream;
+
+    async fn watch_lights(
+        &self,
+        request: Request<proto::WatchLightsRequest>,
+    ) -> Result<Response<Self::WatchLightsStream>, Status> {
+        let jwt = self.authenticate(&request).await?;
+        jwt.require_scope("lights:read")?;
+        let ids = request
+            .get_ref()
+            .ids
+            .iter()
+            .map(|id| parse_id(id, "ids"))
+            .collect::<Result<Vec<_>, _>>()?;
+        // A watcher that falls too far behind skips the changes it missed
+        // rather than ending the stream.
+        let changes = BroadcastStream::new(self.data.light_events.subscribe())
+            .filter_map(Result::ok)
+            .filter(move |light| ids.is_empty() || ids.contains(&light.id))
+            .map(|light| Ok(light.into()));
+        Ok(Response::new(Box::pin(changes)))
+    }
+}
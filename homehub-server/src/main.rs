@@ -1,3 +1,4 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -5,8 +6,13 @@ use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use anyhow::Result;
 use axum::{middleware as axum_middleware, response::IntoResponse, routing};
 use axum::{Json, Router};
+use utoipa_redoc::{Redoc, Servable};
 
+mod error;
+mod graphql;
+mod grpc;
 mod middleware;
+mod openapi;
 mod routes;
 mod state;
 mod util;
@@ -19,10 +25,190 @@ async fn main() -> Result<()> {
     dotenvy::dotenv()?;
     let config = homehub_core::config::Config::from_env();
     let db = homehub_db::get_database(config.database_url.as_str()).await?;
-    let app_state = Arc::new(state::AppState { db, config });
+    if let Some(admin) =
+        homehub_core::user::bootstrap_admin(&db, &config).await?
+    {
+        tracing::info!("Created bootstrap administrator {}", admin.email);
+    }
+    let oidc = match &config.oidc {
+        Some(oidc_config) => {
+            Some(homehub_core::oidc::OidcProvider::discover(oidc_config).await?)
+        }
+        None => None,
+    };
+    let login_throttle = homehub_core::login_throttle::LoginThrottle::new(
+        config.login_throttle.clone(),
+    );
+    let rate_limiter =
+        middleware::rate_limit::RateLimiter::new(config.rate_limit.clone());
+    let mailer = homehub_core::mail::Mailer::new(config.mail.as_ref())?;
+    let profile_count = homehub_core::profile::load_profiles(
+        config.device_profiles_path.as_deref(),
+    )?;
+    tracing::info!("Loaded {} device profiles", profile_count);
+    let app_state = Arc::new(state::AppState {
+        db,
+        config,
+        oidc,
+        login_throttle,
+        rate_limiter,
+        mailer,
+        light_events: homehub_core::light::LightEvents::default(),
+        latest_readings: homehub_core::sensor::LatestReadings::default(),
+        graphql_schema: graphql::schema(),
+    });
 
-    let app = Router::new()
-        .route("/health", routing::get(health_check))
+    let authenticated = Router::new()
+        .route(
+            "/user",
+            routing::get(routes::user::get_me)
+                .patch(routes::user::update_me)
+                .delete(routes::user::delete_me),
+        )
+        .route(
+            "/user/password",
+            routing::post(routes::user::change_password),
+        )
+        .route(
+            "/oauth/clients",
+            routing::get(routes::oauth::list_clients)
+                .post(routes::oauth::register_client),
+        )
+        .route(
+            "/oauth/clients/:id",
+            routing::delete(routes::oauth::delete_client),
+        )
+        .route(
+            "/oauth/authorize",
+            routing::get(routes::oauth::get_consent_details)
+                .post(routes::oauth::authorize),
+        )
+        .route(
+            "/oauth/consents",
+            routing::get(routes::oauth::list_consents),
+        )
+        .route(
+            "/oauth/consents/:client_id",
+            routing::delete(routes::oauth::revoke_consent),
+        )
+        .route(
+            "/lights",
+            routing::get(routes::light::get_lights)
+                .post(routes::light::create_light),
+        )
+        .route(
+            "/lights/state",
+            routing::put(routes::light::set_light_states),
+        )
+        .route(
+            "/lights/:id",
+            routing::get(routes::light::get_light)
+                .patch(routes::light::update_light),
+        )
+        .route(
+            "/lights/:id/state",
+            routing::put(routes::light::set_light_state),
+        )
+        .route(
+            "/rooms/:id",
+            routing::get(routes::location::get_room)
+                .patch(routes::location::update_room),
+        )
+        .route(
+            "/rooms/:id/state",
+            routing::put(routes::location::set_room_state),
+        )
+        .route(
+            "/locations/:id",
+            routing::get(routes::location::get_location)
+                .patch(routes::location::update_location),
+        )
+        .route(
+            "/locations/:id/state",
+            routing::put(routes::location::set_location_state),
+        )
+        .route(
+            "/zones",
+            routing::get(routes::zone::get_zones)
+                .post(routes::zone::create_zone),
+        )
+        .route(
+            "/zones/:id",
+            routing::get(routes::zone::get_zone)
+                .patch(routes::zone::update_zone)
+                .delete(routes::zone::delete_zone),
+        )
+        .route(
+            "/zones/:id/lights/:light_id",
+            routing::put(routes::zone::add_light)
+                .delete(routes::zone::remove_light),
+        )
+        .route(
+            "/zones/:id/state",
+            routing::put(routes::zone::set_zone_state),
+        )
+        .route(
+            "/devices",
+            routing::get(routes::device::get_devices)
+                .post(routes::device::create_device),
+        )
+        .route(
+            "/devices/:id",
+            routing::get(routes::device::get_device)
+                .patch(routes::device::update_device)
+                .delete(routes::device::delete_device),
+        )
+        .route(
+            "/devices/:id/state",
+            routing::put(routes::device::set_device_state),
+        )
+        .route("/profiles", routing::get(routes::profile::get_profiles))
+        .route("/profiles/:id", routing::get(routes::profile::get_profile))
+        .route(
+            "/sensors/:id/readings",
+            routing::get(routes::sensor::get_readings)
+                .post(routes::sensor::record_readings),
+        )
+        .route(
+            "/sensors/:id/readings/latest",
+            routing::get(routes::sensor::get_latest_readings),
+        )
+        .route("/graphql", routing::post(routes::graphql::graphql))
+        .route("/admin/users", routing::get(routes::admin::list_users))
+        .route(
+            "/admin/users/:id",
+            routing::delete(routes::admin::delete_user),
+        )
+        .route(
+            "/admin/users/:id/disable",
+            routing::post(routes::admin::disable_user),
+        )
+        .route(
+            "/admin/users/:id/enable",
+            routing::post(routes::admin::enable_user),
+        )
+        .route(
+            "/admin/users/:id/password-reset",
+            routing::post(routes::admin::force_password_reset),
+        )
+        .route(
+            "/admin/invites",
+            routing::post(routes::admin::create_invite),
+        )
+        .route_layer(axum_middleware::from_fn_with_state(
+            app_state.clone(),
+            middleware::idempotency::idempotency,
+        ))
+        .route_layer(axum_middleware::from_fn_with_state(
+            app_state.clone(),
+            middleware::rate_limit::rate_limit,
+        ))
+        .route_layer(axum_middleware::from_fn_with_state(
+            app_state.clone(),
+            middleware::jwt_auth::auth,
+        ));
+
+    let anonymous = Router::new()
         .route("/auth/register", routing::post(routes::auth::register_user))
         .route("/auth/login", routing::post(routes::auth::login_user))
         .route(
@@ -30,22 +216,57 @@ async fn main() -> Result<()> {
             routing::post(routes::auth::refresh_access_token),
         )
         .route(
-            "/user",
-            routing::get(routes::user::get_me).route_layer(
-                axum_middleware::from_fn_with_state(
-                    app_state.clone(),
-                    middleware::jwt_auth::auth,
-                ),
-            ),
+            "/auth/verify-email",
+            routing::post(routes::user::verify_email),
+        )
+        .route(
+            "/auth/password-reset",
+            routing::post(routes::auth::reset_password),
         )
+        .route("/auth/oidc/login", routing::get(routes::oidc::begin_login))
+        .route("/auth/oidc/callback", routing::get(routes::oidc::callback))
+        .route("/oauth/token", routing::post(routes::oauth::token))
+        .route("/.well-known/jwks.json", routing::get(routes::auth::jwks))
+        // Authenticates itself once the websocket is open
+        .route("/graphql", routing::get(routes::graphql::graphql_ws))
+        .route_layer(axum_middleware::from_fn_with_state(
+            app_state.clone(),
+            middleware::rate_limit::rate_limit,
+        ));
+
+    let app = Router::new()
+        .route("/health", routing::get(health_check))
+        .route("/openapi.json", routing::get(openapi::openapi_json))
+        .merge(Redoc::with_url("/docs", "/openapi.json"))
+        .merge(anonymous)
+        .merge(authenticated)
         .layer(TraceLayer::new_for_http())
-        .with_state(app_state);
+        .with_state(app_state.clone());
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
+    let http = async {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .map_err(anyhow::Error::from)
+    };
+
+    let grpc = async {
+        let grpc_address = app_state.config.grpc_address;
+        tonic::transport::Server::builder()
+            .layer(grpc::rate_limit_layer(app_state.clone()))
+            .add_service(grpc::LightService::server(app_state))
+            .serve(grpc_address)
+            .await
+            .map_err(anyhow::Error::from)
+    };
 
-    axum::serve(listener, app).await?;
+    tokio::try_join!(http, grpc)?;
     Ok(())
 }
 
+#[utoipa::path(get, path = "/health", responses((status = 200)))]
 async fn health_check() -> impl IntoResponse {
     const MESSAGE: &str = "I'm alive!";
 
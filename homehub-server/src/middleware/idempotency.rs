@@ -0,0 +1,250 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::State,
+    http::{response, HeaderName, HeaderValue, Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use homehub_core::{
+    error::Error,
+    idempotency::{IdempotentRequest, StoredResponse},
+};
+
+use crate::{
+    error::ApiError, middleware::jwt_auth::JWTAuthMiddleware, state::AppState,
+    util::locale::request_locale,
+};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Set on responses that were replayed rather than produced again.
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// Marks a response that hands out a secret, such as a client secret or a
+/// reset token. Such responses are never stored for replaying, so a retry
+/// with the same key runs the request again.
+#[derive(Clone, Copy)]
+pub struct IssuesSecret;
+
+/// The same limit axum applies to request bodies by default.
+const MAX_BODY_SIZE: usize = 2 * 1024 * 1024;
+
+/// Makes POST requests with an `Idempotency-Key` header safe to retry: the
+/// first successful response to a key is stored, and later requests with the
+/// same key and body get it back without running the handler again. Keys are
+/// scoped to the user, so this has to run after `jwt_auth::auth`.
+pub async fn idempotency(
+    State(data): State<Arc<AppState>>,
+    req: Request<Body>,
+    next: Next,
+) -> Result<Response, Response> {
+    let (parts, body) = req.into_parts();
+    let key = parts.headers.get(IDEMPOTENCY_KEY_HEADER);
+    let jwt = parts.extensions.get::<JWTAuthMiddleware>();
+    let (true, Some(key), Some(jwt)) = (parts.method == Method::POST, key, jwt)
+    else {
+        return Ok(next.run(Request::from_parts(parts, body)).await);
+    };
+    let locale = request_locale(&parts);
+    let error = |e| ApiError::new(e, &locale).into_response();
+    let user_id = jwt.user.id;
+    let key = key
+        .to_str()
+        .map_err(|_| error(Error::InvalidIdempotencyKeyError))?
+        .to_owned();
+
+    let body = to_bytes(body, MAX_BODY_SIZE)
+        .await
+        .map_err(|_| error(Error::RequestBodyTooLargeError))?;
+    let path = parts
+        .uri
+        .path_and_query()
+        .map_or(parts.uri.path(), |path| path.as_str());
+    let fingerprint = homehub_core::idempotency::fingerprint(
+        parts.method.as_str(),
+        path,
+        &body,
+    );
+
+    match homehub_core::idempotency::begin(
+        user_id,
+        &key,
+        &fingerprint,
+        data.config.idempotency_key_ttl,
+        data.config.idempotency_key_lease,
+        &data.db,
+    )
+    .await
+    .map_err(error)?
+    {
+        IdempotentRequest::Replay(stored) => Ok(replay(stored)),
+        IdempotentRequest::New => {
+            // Finish the request and settle the key even if the client goes
+            // away halfway, so that its retry is not stuck behind a key that
+            // nothing will ever complete.
+            let task = tokio::spawn({
+                let data = data.clone();
+                let key = key.clone();
+                async move {
+                    let request = Request::from_parts(parts, Body::from(body));
+                    let response = tokio::select! {
+                        response = next.run(request) => response,
+                        never = hold_lease(&data, user_id, &key) => match never {},
+                    };
+                    store(&data, user_id, &key, response).await
+                }
+            });
+            match task.await {
+                Ok(response) => Ok(response),
+                Err(e) => {
+                    tracing::error!("Idempotent request failed: {:?}", e);
+                    release(&data, user_id, &key).await;
+                    Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+                }
+            }
+        }
+    }
+}
+
+/// Keeps renewing the lease on the key for as long as the request runs, so
+/// that a retry cannot take the key over from a request that is merely slow.
+async fn hold_lease(
+    data: &AppState,
+    user_id: uuid::Uuid,
+    key: &str,
+) -> Infallible {
+    let period =
+        (data.config.idempotency_key_lease / 3).max(Duration::from_secs(1));
+    let mut interval = tokio::time::interval(period);
+    // The key was claimed just now, so the first renewal can wait.
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        match homehub_core::idempotency::renew(user_id, key, &data.db).await {
+            Ok(true) => {}
+            Ok(false) => tracing::warn!("Lost idempotency key while in use"),
+            Err(e) => {
+                tracing::error!("Failed to renew idempotency key: {:?}", e)
+            }
+        }
+    }
+}
+
+async fn release(data: &AppState, user_id: uuid::Uuid, key: &str) {
+    if let Err(e) =
+        homehub_core::idempotency::abandon(user_id, key, &data.db).await
+    {
+        tracing::error!("Failed to release idempotency key: {:?}", e);
+    }
+}
+
+/// Whether a response is kept for replaying. Only successes are: a request
+/// that was rejected did nothing worth protecting, and its client should be
+/// able to correct it and retry with the same key rather than get the
+/// rejection back. Responses that hand out a secret are never kept.
+fn is_replayable(parts: &response::Parts) -> bool {
+    (parts.status.is_success() || parts.status.is_redirection())
+        && parts.extensions.get::<IssuesSecret>().is_none()
+}
+
+/// Stores a response for replaying if it is replayable, and otherwise
+/// releases the key so that the request can be retried.
+async fn store(
+    data: &AppState,
+    user_id: uuid::Uuid,
+    key: &str,
+    response: Response,
+) -> Response {
+    let (parts, body) = response.into_parts();
+    if !is_replayable(&parts) {
+        release(data, user_id, key).await;
+        return Response::from_parts(parts, body);
+    }
+
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::error!("Failed to read response body: {:?}", e);
+            release(data, user_id, key).await;
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let stored = StoredResponse {
+        status: parts.status.as_u16(),
+        headers: parts
+            .headers
+            .iter()
+            .filter_map(|(name, value)| {
+                Some((name.to_string(), value.to_str().ok()?.to_owned()))
+            })
+            .collect(),
+        body: body.to_vec(),
+    };
+    if let Err(e) =
+        homehub_core::idempotency::complete(user_id, key, stored, &data.db)
+            .await
+    {
+        tracing::error!("Failed to store idempotent response: {:?}", e);
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let mut response = Response::new(Body::from(stored.body));
+    *response.status_mut() =
+        StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+    let headers = response.headers_mut();
+    for (name, value) in stored.headers {
+        if let (Ok(name), Ok(value)) =
+            (HeaderName::try_from(name), HeaderValue::try_from(value))
+        {
+            headers.append(name, value);
+        }
+    }
+    headers
+        .insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(status: StatusCode) -> response::Parts {
+        let (mut parts, _) = Response::new(()).into_parts();
+        parts.status = status;
+        parts
+    }
+
+    #[test]
+    fn replays_successes() {
+        assert!(is_replayable(&parts(StatusCode::OK)));
+        assert!(is_replayable(&parts(StatusCode::CREATED)));
+    }
+
+    #[test]
+    fn does_not_replay_rejections_or_failures() {
+        for status in [
+            StatusCode::BAD_REQUEST,
+            StatusCode::NOT_FOUND,
+            StatusCode::CONFLICT,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            StatusCode::TOO_MANY_REQUESTS,
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ] {
+            assert!(!is_replayable(&parts(status)), "{}", status);
+        }
+    }
+
+    #[test]
+    fn does_not_replay_secrets() {
+        let mut parts = parts(StatusCode::CREATED);
+        parts.extensions.insert(IssuesSecret);
+
+        assert!(!is_replayable(&parts));
+    }
+}
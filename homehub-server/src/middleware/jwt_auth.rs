@@ -3,88 +3,101 @@ use std::sync::Arc;
 use axum::{
     body::Body,
     extract::State,
-    http::{header, Request, StatusCode},
+    http::{header, HeaderMap, Request},
     middleware::Next,
     response::IntoResponse,
-    Json,
 };
+use homehub_core::{error::Error, i18n::Locale};
 use serde::{Deserialize, Serialize};
 
-use crate::state::AppState;
-
-#[derive(Debug, Serialize)]
-pub struct ErrorResponse {
-    pub status: &'static str,
-    pub message: String,
-}
+use crate::{
+    error::ApiError, state::AppState, util::locale::accept_language_locale,
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct JWTAuthMiddleware {
     pub user: homehub_db::app_user::Model,
+    pub grant: Option<homehub_core::token::ClientGrant>,
+    #[serde(skip)]
+    pub locale: Locale,
+}
+
+impl JWTAuthMiddleware {
+    pub fn require_scope(&self, scope: &str) -> Result<(), ApiError> {
+        match homehub_core::oauth::grant_allows(self.grant.as_ref(), scope) {
+            true => Ok(()),
+            false => Err(self.error(Error::MissingScopeError(scope.into()))),
+        }
+    }
+
+    pub fn require_first_party(&self) -> Result<(), ApiError> {
+        match self.grant {
+            None => Ok(()),
+            Some(_) => Err(self.error(Error::FirstPartyOnlyError)),
+        }
+    }
+
+    pub fn require_admin(&self) -> Result<(), ApiError> {
+        self.require_first_party()?;
+        match self.user.is_admin {
+            true => Ok(()),
+            false => Err(self.error(Error::AdminRequiredError)),
+        }
+    }
+
+    /// Wraps an error for responding in the user's locale.
+    pub fn error(&self, error: Error) -> ApiError {
+        ApiError::new(error, &self.locale)
+    }
 }
 
 pub async fn auth(
     State(data): State<Arc<AppState>>,
     mut req: Request<Body>,
     next: Next,
-) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
-    let access_token = req
-        .headers()
+) -> Result<impl IntoResponse, ApiError> {
+    let jwt = authenticate(&data, req.headers()).await?;
+    req.extensions_mut().insert(jwt);
+
+    Ok(next.run(req).await)
+}
+
+/// Verifies the bearer token in the `Authorization` header and loads its
+/// user. Shared by every API that accepts access tokens.
+pub async fn authenticate(
+    data: &AppState,
+    headers: &HeaderMap,
+) -> Result<JWTAuthMiddleware, ApiError> {
+    let locale = accept_language_locale(headers, None);
+    let access_token = headers
         .get(header::AUTHORIZATION)
         .and_then(|auth_header| auth_header.to_str().ok())
-        .and_then(|auth_value| auth_value.strip_prefix("Bearer "));
+        .and_then(|auth_value| auth_value.strip_prefix("Bearer "))
+        .ok_or_else(|| ApiError::new(Error::MissingTokenError, &locale))?;
 
-    let access_token = access_token.ok_or_else(|| {
-        let error_response = ErrorResponse {
-            status: "error",
-            message: "No valid token found".to_string(),
-        };
-        (StatusCode::UNAUTHORIZED, Json(error_response))
-    })?;
-
-    let access_token = match homehub_core::token::verify_jwt_token(
-        data.config.access_token_public_key.to_owned(),
+    let access_token = homehub_core::token::verify_jwt_token(
+        &data.config.access_token_keys,
         access_token,
-    ) {
-        Ok(token) => token,
-        Err(_) => {
-            return Err((
-                StatusCode::UNAUTHORIZED,
-                Json(ErrorResponse {
-                    status: "error",
-                    message: "Unauthorized".to_owned(),
-                }),
-            ))
-        }
-    };
+    )
+    .map_err(|_| ApiError::new(Error::UnauthorizedError, &locale))?;
 
     let user_id = access_token.user_id;
+    let grant = access_token.grant;
 
-    let user = match homehub_core::user::find_by_id(user_id, &data.db).await {
-        Ok(user) => match user {
-            Some(user) => user,
-            None => {
-                return Err((
-                    StatusCode::UNAUTHORIZED,
-                    Json(ErrorResponse {
-                        status: "error",
-                        message: "Unauthorized".to_owned(),
-                    }),
-                ))
-            }
-        },
-        Err(_) => {
-            return Err((
-                StatusCode::UNAUTHORIZED,
-                Json(ErrorResponse {
-                    status: "error",
-                    message: "Unauthorized".to_owned(),
-                }),
-            ))
-        }
-    };
+    let user = homehub_core::user::find_by_id(user_id, &data.db)
+        .await
+        .ok()
+        .flatten()
+        .filter(|user| user.disabled_at.is_none())
+        .ok_or_else(|| ApiError::new(Error::UnauthorizedError, &locale))?;
+    if user.password_reset_required {
+        return Err(ApiError::new(Error::PasswordResetRequiredError, &locale));
+    }
 
-    req.extensions_mut().insert(JWTAuthMiddleware { user });
-
-    Ok(next.run(req).await)
+    let locale = accept_language_locale(headers, user.locale.as_deref());
+    Ok(JWTAuthMiddleware {
+        user,
+        grant,
+        locale,
+    })
 }
@@ -1 +1,3 @@
+pub mod idempotency;
 pub mod jwt_auth;
+pub mod rate_limit;
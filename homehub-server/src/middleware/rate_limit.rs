@@ -0,0 +1,144 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use homehub_core::{config::RateLimitConfig, error::Error};
+
+use crate::{
+    error::ApiError,
+    middleware::jwt_auth::JWTAuthMiddleware,
+    state::AppState,
+    util::{client_ip::client_ip, locale::request_locale},
+};
+
+/// Buckets are only pruned once there are this many, so that the common case
+/// of a handful of clients never has to scan the map.
+const PRUNE_THRESHOLD: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum RateLimitKey {
+    User(uuid::Uuid),
+    Ip(IpAddr),
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// Token bucket rate limiter. Every key may make `burst` requests at once,
+/// after which it regains `per_second` requests each second.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<HashMap<RateLimitKey, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a token from the key's bucket, or returns how long the caller
+    /// has to wait until one is available.
+    pub(crate) fn acquire(&self, key: RateLimitKey) -> Result<(), Duration> {
+        let now = Instant::now();
+        let burst = self.config.burst as f64;
+        let per_second = self.config.per_second;
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() >= PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| {
+                let elapsed = now.duration_since(bucket.updated_at);
+                bucket.tokens + elapsed.as_secs_f64() * per_second < burst
+            });
+        }
+
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: burst,
+            updated_at: now,
+        });
+        let elapsed = now.duration_since(bucket.updated_at);
+        bucket.tokens =
+            (bucket.tokens + elapsed.as_secs_f64() * per_second).min(burst);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        // A refill rate too small to represent the wait in a `Duration`
+        // means waiting practically forever.
+        Err(
+            Duration::try_from_secs_f64((1.0 - bucket.tokens) / per_second)
+                .unwrap_or(Duration::MAX),
+        )
+    }
+}
+
+/// Limits requests per user on authenticated routes and per client IP
+/// everywhere else. On authenticated routes this has to run after
+/// `jwt_auth::auth`, so it must be added as the inner layer.
+pub async fn rate_limit(
+    State(data): State<Arc<AppState>>,
+    req: Request<Body>,
+    next: Next,
+) -> Result<impl IntoResponse, Response> {
+    let (parts, body) = req.into_parts();
+    let key = match parts.extensions.get::<JWTAuthMiddleware>() {
+        Some(jwt) => RateLimitKey::User(jwt.user.id),
+        None => RateLimitKey::Ip(
+            client_ip(&parts, &data).map_err(IntoResponse::into_response)?,
+        ),
+    };
+    data.rate_limiter.acquire(key).map_err(|retry_after| {
+        ApiError::new(
+            Error::TooManyRequestsError { retry_after },
+            &request_locale(&parts),
+        )
+        .into_response()
+    })?;
+
+    Ok(next.run(Request::from_parts(parts, body)).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(burst: u32, per_second: f64) -> RateLimiter {
+        RateLimiter::new(RateLimitConfig { burst, per_second })
+    }
+
+    #[test]
+    fn allows_burst_then_asks_to_wait() {
+        let limiter = limiter(2, 1.0);
+        let key = RateLimitKey::Ip(IpAddr::from([127, 0, 0, 1]));
+
+        assert_eq!(limiter.acquire(key), Ok(()));
+        assert_eq!(limiter.acquire(key), Ok(()));
+        let retry_after = limiter.acquire(key).unwrap_err();
+        assert!(retry_after > Duration::ZERO);
+        assert!(retry_after <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn tiny_refill_rate_waits_forever() {
+        let limiter = limiter(0, f64::MIN_POSITIVE);
+        let key = RateLimitKey::Ip(IpAddr::from([127, 0, 0, 1]));
+
+        assert_eq!(limiter.acquire(key), Err(Duration::MAX));
+    }
+}
@@ -0,0 +1,221 @@
+use axum::{response::IntoResponse, Json};
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
+
+use crate::{error, routes};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        description = "Control the lights and rooms of a homehub.\n\nAuthenticated `POST` requests can be retried safely by sending an `Idempotency-Key` header: a retry with the same key and body within the configured window gets the original response back, marked with `Idempotent-Replayed: true`, instead of running again. Only successful responses are stored: a request that was rejected, or failed, can be corrected and retried with the same key. Responses that hand out a secret, such as a client secret, an authorization code, a reset token or an invite, are never stored, so retrying those runs them again."
+    ),
+    paths(
+        crate::health_check,
+        routes::auth::register_user,
+        routes::auth::login_user,
+        routes::auth::refresh_access_token,
+        routes::auth::reset_password,
+        routes::auth::jwks,
+        routes::oidc::begin_login,
+        routes::oidc::callback,
+        routes::user::get_me,
+        routes::user::update_me,
+        routes::user::delete_me,
+        routes::user::verify_email,
+        routes::user::change_password,
+        routes::oauth::register_client,
+        routes::oauth::list_clients,
+        routes::oauth::delete_client,
+        routes::oauth::get_consent_details,
+        routes::oauth::authorize,
+        routes::oauth::token,
+        routes::oauth::list_consents,
+        routes::oauth::revoke_consent,
+        routes::light::get_lights,
+        routes::light::create_light,
+        routes::light::get_light,
+        routes::light::update_light,
+        routes::light::set_light_state,
+        routes::light::set_light_states,
+        routes::location::get_room,
+        routes::location::update_room,
+        routes::location::set_room_state,
+        routes::location::get_location,
+        routes::location::update_location,
+        routes::location::set_location_state,
+        routes::zone::get_zones,
+        routes::zone::create_zone,
+        routes::zone::get_zone,
+        routes::zone::update_zone,
+        routes::zone::delete_zone,
+        routes::zone::add_light,
+        routes::zone::remove_light,
+        routes::zone::set_zone_state,
+        routes::device::get_devices,
+        routes::device::create_device,
+        routes::device::get_device,
+        routes::device::update_device,
+        routes::device::set_device_state,
+        routes::device::delete_device,
+        routes::profile::get_profiles,
+        routes::profile::get_profile,
+        routes::sensor::record_readings,
+        routes::sensor::get_readings,
+        routes::sensor::get_latest_readings,
+        routes::admin::list_users,
+        routes::admin::disable_user,
+        routes::admin::enable_user,
+        routes::admin::force_password_reset,
+        routes::admin::delete_user,
+        routes::admin::create_invite,
+    ),
+    components(schemas(
+        error::ErrorResponse,
+        error::ErrorDetails,
+        routes::StatusResponse,
+        routes::PageMetadata,
+        routes::auth::TokensResponse,
+        routes::auth::RegisterUserPayload,
+        routes::auth::LoginUserPayload,
+        routes::auth::RefreshAccessTokenPayload,
+        routes::auth::ResetPasswordPayload,
+        routes::user::UserResponse,
+        routes::user::UpdateMePayload,
+        routes::user::VerifyEmailPayload,
+        routes::user::ChangePasswordPayload,
+        routes::oauth::RegisteredClientResponse,
+        routes::oauth::ClientsResponse,
+        routes::oauth::ConsentDetailsResponse,
+        routes::oauth::RedirectResponse,
+        routes::oauth::ConsentsResponse,
+        routes::oauth::TokenErrorResponse,
+        routes::oauth::RegisterClientPayload,
+        routes::oauth::AuthorizePayload,
+        routes::light::LightsResponse,
+        routes::light::LightResponse,
+        routes::light::CreateLightPayload,
+        routes::light::UpdateLightPayload,
+        routes::light::SetLightStatesPayload,
+        routes::light::LightResult,
+        routes::light::LightResultsResponse,
+        routes::location::RoomResponse,
+        routes::location::LocationResponse,
+        routes::location::UpdateRoomPayload,
+        routes::location::UpdateLocationPayload,
+        routes::zone::ZonesResponse,
+        routes::zone::ZoneResponse,
+        routes::zone::CreateZonePayload,
+        routes::zone::UpdateZonePayload,
+        routes::device::DevicesResponse,
+        routes::device::DeviceResponse,
+        routes::device::CreateDevicePayload,
+        routes::device::UpdateDevicePayload,
+        routes::profile::ProfilesResponse,
+        routes::profile::ProfileResponse,
+        routes::sensor::LatestReadingsResponse,
+        routes::sensor::ReadingsResponse,
+        routes::sensor::NewReading,
+        routes::sensor::RecordReadingsPayload,
+        routes::admin::AdminUsersResponse,
+        routes::admin::AdminUserResponse,
+        routes::admin::PasswordResetResponse,
+        routes::admin::InviteResponse,
+        homehub_core::admin::AdminUserDto,
+        homehub_core::admin::PasswordReset,
+        homehub_core::admin::Invite,
+        homehub_core::admin::UserSort,
+        homehub_core::light::LightDto,
+        homehub_core::light::RoomDto,
+        homehub_core::light::LightState,
+        homehub_core::light::LightSort,
+        homehub_core::light::LightSelector,
+        homehub_core::light::AggregateState,
+        homehub_core::light::AggregatePower,
+        homehub_core::location::LocationDto,
+        homehub_core::zone::ZoneDto,
+        homehub_core::zone::ZoneSort,
+        homehub_core::device::DeviceDto,
+        homehub_core::device::DeviceKind,
+        homehub_core::device::Capability,
+        homehub_core::device::DeviceState,
+        homehub_core::device::DeviceSort,
+        homehub_core::profile::DeviceProfile,
+        homehub_core::profile::Gamut,
+        homehub_core::sensor::Metric,
+        homehub_core::sensor::ReadingDto,
+        homehub_core::sensor::BucketDto,
+        homehub_core::pagination::SortOrder,
+        homehub_core::oauth::OAuthClientDto,
+        homehub_core::oauth::AuthorizationRequest,
+        homehub_core::oauth::ConsentDetails,
+        homehub_core::oauth::ScopeDto,
+        homehub_core::oauth::ConsentDto,
+        homehub_core::oauth::TokenRequest,
+        homehub_core::oauth::OAuthTokens,
+        homehub_core::password::PasswordViolation,
+        homehub_db::queries::app_user::FilteredAppUserModel,
+    )),
+    modifiers(&BearerAuth),
+    tags(
+        (name = "auth", description = "Registration, login and tokens"),
+        (name = "user", description = "The logged in user's account"),
+        (name = "oauth", description = "Third-party applications"),
+        (name = "lights"),
+        (name = "rooms"),
+        (name = "locations"),
+        (name = "zones", description = "Groups of lights across rooms"),
+        (name = "devices", description = "Lights, switches, plugs, sensors and thermostats"),
+        (name = "sensors", description = "Readings of sensors over time"),
+        (name = "admin", description = "User administration"),
+    ),
+)]
+pub struct ApiDoc;
+
+/// Access tokens are sent as bearer tokens. Third-party tokens are limited
+/// to the scopes listed on each operation.
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "bearer",
+                SecurityScheme::Http(
+                    HttpBuilder::new()
+                        .scheme(HttpAuthScheme::Bearer)
+                        .bearer_format("JWT")
+                        .build(),
+                ),
+            );
+        }
+    }
+}
+
+pub async fn openapi_json() -> impl IntoResponse {
+    Json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Clients are generated from the committed `openapi.json`, so it has to
+    /// match the routes. Run with `UPDATE_OPENAPI=1` to regenerate it.
+    #[test]
+    fn committed_document_is_up_to_date() {
+        let generated = ApiDoc::openapi().to_pretty_json().unwrap() + "\n";
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json");
+        if std::env::var_os("UPDATE_OPENAPI").is_some() {
+            std::fs::write(path, &generated).unwrap();
+        }
+
+        let committed = std::fs::read_to_string(path).unwrap_or_default();
+        assert!(
+            committed == generated,
+            "openapi.json is out of date, regenerate it with \
+             `UPDATE_OPENAPI=1 cargo test`"
+        );
+    }
+}
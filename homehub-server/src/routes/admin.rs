@@ -0,0 +1,232 @@
+use crate::{
+    error::ApiError,
+    middleware::{idempotency::IssuesSecret, jwt_auth::JWTAuthMiddleware},
+    routes::{PageMetadata, StatusResponse},
+    state::AppState,
+    util::extract::{ApiPath, ApiQuery},
+};
+use axum::{
+    extract::State, http::StatusCode, response::IntoResponse, Extension, Json,
+};
+use homehub_core::{
+    admin::{AdminUserDto, Invite, PasswordReset, UserSort},
+    pagination::{page_request, SortOrder},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct AdminUsersResponse {
+    status: &'static str,
+    users: Vec<AdminUserDto>,
+    page: PageMetadata,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct AdminUserResponse {
+    status: &'static str,
+    user: AdminUserDto,
+}
+
+impl From<AdminUserDto> for AdminUserResponse {
+    fn from(user: AdminUserDto) -> Self {
+        AdminUserResponse {
+            status: "success",
+            user,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct PasswordResetResponse {
+    status: &'static str,
+    #[serde(flatten)]
+    reset: PasswordReset,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct InviteResponse {
+    status: &'static str,
+    #[serde(flatten)]
+    invite: Invite,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ListUsersQuery {
+    /// Only list users whose name or email contains this.
+    q: Option<String>,
+    sort: Option<UserSort>,
+    order: Option<SortOrder>,
+    /// The `next_cursor` of the previous page.
+    after: Option<String>,
+    /// How many users to return, at most 200.
+    limit: Option<u64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/users",
+    tag = "admin",
+    params(ListUsersQuery),
+    responses(
+        (status = 200, body = AdminUsersResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = [])),
+)]
+pub(crate) async fn list_users(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiQuery(query): ApiQuery<ListUsersQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_admin()?;
+    let page = page_request(
+        query.sort.unwrap_or_default(),
+        query.order.unwrap_or_default(),
+        query.after.as_deref(),
+        query.limit,
+    )
+    .map_err(|e| jwt.error(e))?;
+    homehub_core::admin::list_users(query.q.as_deref(), &page, &data.db)
+        .await
+        .map(|users| {
+            Json(AdminUsersResponse {
+                status: "success",
+                page: PageMetadata::from(&users),
+                users: users.items,
+            })
+        })
+        .map_err(|e| jwt.error(e))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/users/{id}/disable",
+    tag = "admin",
+    params(("id" = uuid::Uuid, Path, description = "User id")),
+    responses(
+        (status = 200, body = AdminUserResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = [])),
+)]
+pub(crate) async fn disable_user(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(user_id): ApiPath<uuid::Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_admin()?;
+    homehub_core::admin::disable_user(jwt.user.id, user_id, &data.db)
+        .await
+        .map(|user| Json(AdminUserResponse::from(user)))
+        .map_err(|e| jwt.error(e))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/users/{id}/enable",
+    tag = "admin",
+    params(("id" = uuid::Uuid, Path, description = "User id")),
+    responses(
+        (status = 200, body = AdminUserResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = [])),
+)]
+pub(crate) async fn enable_user(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(user_id): ApiPath<uuid::Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_admin()?;
+    homehub_core::admin::enable_user(user_id, &data.db)
+        .await
+        .map(|user| Json(AdminUserResponse::from(user)))
+        .map_err(|e| jwt.error(e))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/users/{id}/password-reset",
+    tag = "admin",
+    params(("id" = uuid::Uuid, Path, description = "User id")),
+    responses(
+        (status = 200, body = PasswordResetResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = [])),
+)]
+pub(crate) async fn force_password_reset(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(user_id): ApiPath<uuid::Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_admin()?;
+    homehub_core::admin::force_password_reset(user_id, &data.db)
+        .await
+        .map(|reset| {
+            (
+                Extension(IssuesSecret),
+                Json(PasswordResetResponse {
+                    status: "success",
+                    reset,
+                }),
+            )
+        })
+        .map_err(|e| jwt.error(e))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/users/{id}",
+    tag = "admin",
+    params(("id" = uuid::Uuid, Path, description = "User id")),
+    responses(
+        (status = 200, body = StatusResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = [])),
+)]
+pub(crate) async fn delete_user(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(user_id): ApiPath<uuid::Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_admin()?;
+    homehub_core::admin::delete_user(jwt.user.id, user_id, &data.db)
+        .await
+        .map(|_| Json(StatusResponse::success()))
+        .map_err(|e| jwt.error(e))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/invites",
+    tag = "admin",
+    responses(
+        (status = 201, body = InviteResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = [])),
+)]
+pub(crate) async fn create_invite(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_admin()?;
+    homehub_core::admin::create_invite(jwt.user.id, &data.db)
+        .await
+        .map(|invite| {
+            (
+                StatusCode::CREATED,
+                Extension(IssuesSecret),
+                Json(InviteResponse {
+                    status: "success",
+                    invite,
+                }),
+            )
+        })
+        .map_err(|e| jwt.error(e))
+}
@@ -1,153 +1,174 @@
-use crate::state::AppState;
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
-use serde::Deserialize;
+use crate::{
+    error::ApiError,
+    routes::user::UserResponse,
+    state::AppState,
+    util::{client_ip::ClientIp, extract::ApiJson, locale::UserLocale},
+};
+use axum::{extract::State, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use utoipa::ToSchema;
 
-#[derive(Deserialize)]
+#[derive(Serialize, ToSchema)]
+pub(crate) struct TokensResponse {
+    status: &'static str,
+    access_token: String,
+    refresh_token: String,
+}
+
+impl From<homehub_core::user::Tokens> for TokensResponse {
+    fn from(tokens: homehub_core::user::Tokens) -> Self {
+        TokensResponse {
+            status: "success",
+            access_token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
 pub(crate) struct RegisterUserPayload {
     name: String,
     email: String,
     password: String,
+    /// Required while registration is invite-only.
+    invite_token: Option<String>,
 }
 
-// #[debug_handler]
+#[utoipa::path(
+    post,
+    path = "/auth/register",
+    tag = "auth",
+    request_body = RegisterUserPayload,
+    responses(
+        (status = 200, body = UserResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+)]
 pub(crate) async fn register_user(
     State(data): State<Arc<AppState>>,
-    Json(payload): Json<RegisterUserPayload>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    UserLocale(locale): UserLocale,
+    ApiJson(payload): ApiJson<RegisterUserPayload>,
+) -> Result<impl IntoResponse, ApiError> {
     homehub_core::user::register_user(
         &data.db,
         &payload.name,
         &payload.email,
         &payload.password,
+        payload.invite_token.as_deref(),
+        &data.config,
     )
     .await
-    .map(|user| {
-        Json(serde_json::json!({
-            "status": "success",
-            "user": user,
-        }))
-    })
-    .map_err(|e| match e {
-        homehub_core::user::RegisterUserError::UserAlreadyExists { email } => (
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({
-                "status": "error",
-                "message": format!("User with email {} already exists", email),
-            })),
-        ),
-        homehub_core::user::RegisterUserError::DbError(_) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "status": "error",
-                "message": "Failed to insert user",
-            })),
-        ),
-        homehub_core::user::RegisterUserError::CouldNotHashError => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "status": "error",
-                "message": "Could not hash password",
-            })),
-        ),
-    })
+    .map(|user| Json(UserResponse::from(user)))
+    .map_err(|e| ApiError::new(e, &locale))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub(crate) struct LoginUserPayload {
     email: String,
     password: String,
 }
 
+#[utoipa::path(
+    post,
+    path = "/auth/login",
+    tag = "auth",
+    request_body = LoginUserPayload,
+    responses(
+        (status = 200, body = TokensResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+)]
 pub(crate) async fn login_user(
     State(data): State<Arc<AppState>>,
-    Json(payload): Json<LoginUserPayload>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    UserLocale(locale): UserLocale,
+    ClientIp(client_ip): ClientIp,
+    ApiJson(payload): ApiJson<LoginUserPayload>,
+) -> Result<impl IntoResponse, ApiError> {
     homehub_core::user::login_user(
         &data.db,
         &payload.email,
         &payload.password,
+        client_ip,
+        &data.login_throttle,
         &data.config,
     )
     .await
-    .map(
-        |homehub_core::user::Tokens {
-             access_token,
-             refresh_token,
-         }| {
-            Json(serde_json::json!({
-            "status": "success",
-            "access_token": access_token, 
-            "refresh_token": refresh_token}))
-        },
-    )
-    .map_err(translate_login_error)
+    .map(|tokens| Json(TokensResponse::from(tokens)))
+    .map_err(|e| ApiError::new(e, &locale))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub(crate) struct RefreshAccessTokenPayload {
     refresh_token: String,
 }
 
+#[utoipa::path(
+    post,
+    path = "/auth/refresh",
+    tag = "auth",
+    request_body = RefreshAccessTokenPayload,
+    responses(
+        (status = 200, body = TokensResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+)]
 pub(crate) async fn refresh_access_token(
     State(data): State<Arc<AppState>>,
-    Json(payload): Json<RefreshAccessTokenPayload>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    UserLocale(locale): UserLocale,
+    ApiJson(payload): ApiJson<RefreshAccessTokenPayload>,
+) -> Result<impl IntoResponse, ApiError> {
     homehub_core::user::refresh_access_token(
+        &data.db,
         &payload.refresh_token,
         &data.config,
     )
     .await
-    .map(
-        |homehub_core::user::Tokens {
-             access_token,
-             refresh_token,
-         }| {
-            Json(serde_json::json!({
-                "status": "success",
-                "access_token": access_token,
-                "refresh_token": refresh_token,
-            }))
-        },
+    .map(|tokens| Json(TokensResponse::from(tokens)))
+    .map_err(|e| ApiError::new(e, &locale))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct ResetPasswordPayload {
+    reset_token: String,
+    password: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/password-reset",
+    tag = "auth",
+    request_body = ResetPasswordPayload,
+    responses(
+        (status = 200, body = UserResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+)]
+pub(crate) async fn reset_password(
+    State(data): State<Arc<AppState>>,
+    UserLocale(locale): UserLocale,
+    ApiJson(payload): ApiJson<ResetPasswordPayload>,
+) -> Result<impl IntoResponse, ApiError> {
+    homehub_core::user::reset_password(
+        &data.db,
+        &payload.reset_token,
+        &payload.password,
+        &data.config,
     )
-    .map_err(translate_login_error)
+    .await
+    .map(|user| Json(UserResponse::from(user)))
+    .map_err(|e| ApiError::new(e, &locale))
 }
 
-fn translate_login_error(
-    e: homehub_core::user::LoginUserError,
-) -> (StatusCode, Json<serde_json::Value>) {
-    match e {
-        homehub_core::user::LoginUserError::UserNotFoundError(email) => (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({
-                "status": "error",
-                "message": format!("User {} not found", email),
-            })),
-        ),
-        homehub_core::user::LoginUserError::InvalidCredentialError => (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({
-                "status": "error",
-                "message": "Invalid credentials",
-            })),
-        ),
-        homehub_core::user::LoginUserError::DbError(_) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "status": "error",
-            })),
-        ),
-        homehub_core::user::LoginUserError::CouldNotHashError => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "status": "error",
-            })),
-        ),
-        homehub_core::user::LoginUserError::TokenGenerationError => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "status": "error",
-            })),
-        ),
-    }
+/// Public keys for verifying access tokens, as a JSON Web Key Set.
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    tag = "auth",
+    responses((status = 200, body = Object)),
+)]
+pub(crate) async fn jwks(
+    State(data): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    Json(data.config.access_token_keys.jwks())
 }
@@ -0,0 +1,318 @@
+use crate::{
+    error::ApiError,
+    middleware::jwt_auth::JWTAuthMiddleware,
+    routes::{PageMetadata, StatusResponse},
+    state::AppState,
+    util::{
+        deserialize::deserialize_some,
+        etag::{ETag, IfMatch},
+        extract::{ApiJson, ApiPath, ApiQuery},
+    },
+};
+use axum::{
+    extract::State, http::StatusCode, response::IntoResponse, Extension, Json,
+};
+use homehub_core::{
+    device::{
+        Capability, DeviceDto, DeviceFilter, DeviceKind, DeviceSort,
+        DeviceState,
+    },
+    pagination::{page_request, SortOrder},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct DevicesResponse {
+    status: &'static str,
+    devices: Vec<DeviceDto>,
+    page: PageMetadata,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct DeviceResponse {
+    status: &'static str,
+    device: DeviceDto,
+}
+
+impl From<DeviceDto> for DeviceResponse {
+    fn from(device: DeviceDto) -> Self {
+        DeviceResponse {
+            status: "success",
+            device,
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ListDevicesQuery {
+    /// Only list devices whose name contains this.
+    name: Option<String>,
+    /// Only list devices of this kind.
+    kind: Option<DeviceKind>,
+    /// Only list devices with this capability.
+    capability: Option<Capability>,
+    /// Only list devices in this room.
+    room_id: Option<uuid::Uuid>,
+    sort: Option<DeviceSort>,
+    order: Option<SortOrder>,
+    /// The `next_cursor` of the previous page.
+    after: Option<String>,
+    /// How many devices to return, at most 200.
+    limit: Option<u64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/devices",
+    tag = "devices",
+    params(ListDevicesQuery),
+    responses(
+        (status = 200, body = DevicesResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["devices:read"])),
+)]
+pub(crate) async fn get_devices(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiQuery(query): ApiQuery<ListDevicesQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("devices:read")?;
+    let page = page_request(
+        query.sort.unwrap_or_default(),
+        query.order.unwrap_or_default(),
+        query.after.as_deref(),
+        query.limit,
+    )
+    .map_err(|e| jwt.error(e))?;
+    let filter = DeviceFilter {
+        name: query.name,
+        kind: query.kind,
+        capability: query.capability,
+        room_id: query.room_id,
+    };
+    homehub_core::device::get_devices(&filter, &page, &data.db)
+        .await
+        .map(|devices| {
+            Json(DevicesResponse {
+                status: "success",
+                page: PageMetadata::from(&devices),
+                devices: devices.items,
+            })
+        })
+        .map_err(|e| jwt.error(e))
+}
+
+/// Lights are devices too, so changing one through these routes also takes
+/// the `lights:write` scope that the light routes ask for.
+fn require_write_scope(
+    jwt: &JWTAuthMiddleware,
+    kind: DeviceKind,
+) -> Result<(), ApiError> {
+    jwt.require_scope("devices:write")?;
+    if kind == DeviceKind::Light {
+        jwt.require_scope("lights:write")?;
+    }
+    Ok(())
+}
+
+/// Checks the scopes for changing the existing device `id`.
+async fn require_write_scope_for(
+    jwt: &JWTAuthMiddleware,
+    id: &uuid::Uuid,
+    data: &AppState,
+) -> Result<(), ApiError> {
+    jwt.require_scope("devices:write")?;
+    let device = homehub_core::device::get_device(id, &data.db)
+        .await
+        .map_err(|e| jwt.error(e))?;
+    require_write_scope(jwt, device.kind)
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct CreateDevicePayload {
+    name: String,
+    kind: DeviceKind,
+    /// Defaults to what is usual for the kind. Capabilities the kind
+    /// requires, such as `on_off` for switches, are always added.
+    capabilities: Option<Vec<Capability>>,
+    room_id: Option<uuid::Uuid>,
+    /// The id of the device's profile, which then decides its
+    /// capabilities.
+    profile_id: Option<String>,
+}
+
+/// Creating a light also takes the `lights:write` scope.
+#[utoipa::path(
+    post,
+    path = "/devices",
+    tag = "devices",
+    request_body = CreateDevicePayload,
+    responses(
+        (status = 201, body = DeviceResponse, headers(("ETag" = String, description = "The version of the device"))),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["devices:write"])),
+)]
+pub(crate) async fn create_device(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiJson(payload): ApiJson<CreateDevicePayload>,
+) -> Result<impl IntoResponse, ApiError> {
+    require_write_scope(&jwt, payload.kind)?;
+    homehub_core::device::create_device(
+        &payload.name,
+        payload.kind,
+        payload.capabilities.as_deref(),
+        payload.room_id,
+        payload.profile_id.as_deref(),
+        &data.db,
+    )
+    .await
+    .map(|device| {
+        (
+            StatusCode::CREATED,
+            ETag(device.version),
+            Json(DeviceResponse::from(device)),
+        )
+    })
+    .map_err(|e| jwt.error(e))
+}
+
+#[utoipa::path(
+    get,
+    path = "/devices/{id}",
+    tag = "devices",
+    params(("id" = uuid::Uuid, Path, description = "Device id")),
+    responses(
+        (status = 200, body = DeviceResponse, headers(("ETag" = String, description = "The version of the device"))),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["devices:read"])),
+)]
+pub(crate) async fn get_device(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(id): ApiPath<uuid::Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("devices:read")?;
+    homehub_core::device::get_device(&id, &data.db)
+        .await
+        .map(|device| {
+            (ETag(device.version), Json(DeviceResponse::from(device)))
+        })
+        .map_err(|e| jwt.error(e))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct UpdateDevicePayload {
+    name: Option<String>,
+    /// Moves the device to another room, or out of its room when `null`.
+    #[serde(default, deserialize_with = "deserialize_some")]
+    #[schema(value_type = Option<uuid::Uuid>)]
+    room_id: Option<Option<uuid::Uuid>>,
+    /// Links the device to another profile, or unlinks it when `null`.
+    #[serde(default, deserialize_with = "deserialize_some")]
+    #[schema(value_type = Option<String>)]
+    profile_id: Option<Option<String>>,
+}
+
+/// Updating a light also takes the `lights:write` scope.
+#[utoipa::path(
+    patch,
+    path = "/devices/{id}",
+    tag = "devices",
+    params(
+        ("id" = uuid::Uuid, Path, description = "Device id"),
+        ("If-Match" = Option<String>, Header, description = "Only update the device if its `ETag` is one of these"),
+    ),
+    request_body = UpdateDevicePayload,
+    responses(
+        (status = 200, body = DeviceResponse, headers(("ETag" = String, description = "The version of the device"))),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["devices:write"])),
+)]
+pub(crate) async fn update_device(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(id): ApiPath<uuid::Uuid>,
+    IfMatch(if_match): IfMatch,
+    ApiJson(payload): ApiJson<UpdateDevicePayload>,
+) -> Result<impl IntoResponse, ApiError> {
+    require_write_scope_for(&jwt, &id, &data).await?;
+    homehub_core::device::update_device(
+        &id,
+        payload.name.as_deref(),
+        payload.room_id,
+        payload.profile_id.as_ref().map(Option::as_deref),
+        if_match.as_deref(),
+        &data.db,
+    )
+    .await
+    .map(|device| (ETag(device.version), Json(DeviceResponse::from(device))))
+    .map_err(|e| jwt.error(e))
+}
+
+/// Controlling a light also takes the `lights:write` scope.
+#[utoipa::path(
+    put,
+    path = "/devices/{id}/state",
+    tag = "devices",
+    params(
+        ("id" = uuid::Uuid, Path, description = "Device id"),
+        ("If-Match" = Option<String>, Header, description = "Only update the device if its `ETag` is one of these"),
+    ),
+    request_body(content = DeviceState, description = "The fields to change. Each must belong to one of the device's capabilities."),
+    responses(
+        (status = 200, body = DeviceResponse, headers(("ETag" = String, description = "The version of the device"))),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["devices:write"])),
+)]
+pub(crate) async fn set_device_state(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(id): ApiPath<uuid::Uuid>,
+    IfMatch(if_match): IfMatch,
+    ApiJson(state): ApiJson<DeviceState>,
+) -> Result<impl IntoResponse, ApiError> {
+    require_write_scope_for(&jwt, &id, &data).await?;
+    homehub_core::device::set_device_state(
+        &id,
+        &state,
+        if_match.as_deref(),
+        &data.db,
+        &data.light_events,
+    )
+    .await
+    .map(|device| (ETag(device.version), Json(DeviceResponse::from(device))))
+    .map_err(|e| jwt.error(e))
+}
+
+/// Deleting a light also takes the `lights:write` scope.
+#[utoipa::path(
+    delete,
+    path = "/devices/{id}",
+    tag = "devices",
+    params(("id" = uuid::Uuid, Path, description = "Device id")),
+    responses(
+        (status = 200, body = StatusResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["devices:write"])),
+)]
+pub(crate) async fn delete_device(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(id): ApiPath<uuid::Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    require_write_scope_for(&jwt, &id, &data).await?;
+    homehub_core::device::delete_device(&id, &data.db)
+        .await
+        .map(|_| Json(StatusResponse::success()))
+        .map_err(|e| jwt.error(e))
+}
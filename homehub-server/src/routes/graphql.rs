@@ -0,0 +1,115 @@
+use std::sync::Arc;
+
+use async_graphql::{http::ALL_WEBSOCKET_PROTOCOLS, Data};
+use async_graphql_axum::{
+    GraphQLProtocol, GraphQLRequest, GraphQLResponse, GraphQLWebSocket,
+};
+use axum::{
+    extract::{State, WebSocketUpgrade},
+    http::{header, HeaderMap, HeaderValue},
+    response::IntoResponse,
+    Extension,
+};
+use serde_json::Value;
+
+use crate::{
+    middleware::jwt_auth::{authenticate, JWTAuthMiddleware},
+    state::AppState,
+};
+
+pub(crate) async fn graphql(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    req: GraphQLRequest,
+) -> GraphQLResponse {
+    let req = req
+        .into_inner()
+        .data(crate::graphql::loader(data.clone(), jwt.clone()))
+        .data(jwt)
+        .data(data.clone());
+    data.graphql_schema.execute(req).await.into()
+}
+
+/// Subscriptions over a websocket. Browsers cannot set headers on the
+/// upgrade request, so the connection is authenticated once it is open, by
+/// the `Authorization` field of the `connection_init` payload. Other clients
+/// may send the header with the upgrade request instead.
+pub(crate) async fn graphql_ws(
+    State(data): State<Arc<AppState>>,
+    headers: HeaderMap,
+    protocol: GraphQLProtocol,
+    upgrade: WebSocketUpgrade,
+) -> impl IntoResponse {
+    upgrade
+        .protocols(ALL_WEBSOCKET_PROTOCOLS)
+        .on_upgrade(move |stream| {
+            let mut connection_data = Data::default();
+            connection_data.insert(data.clone());
+            GraphQLWebSocket::new(stream, data.graphql_schema.clone(), protocol)
+                .with_data(connection_data)
+                .on_connection_init(move |payload| {
+                    connection_init(data, headers, payload)
+                })
+                .serve()
+        })
+}
+
+/// Authenticates a websocket connection like any other request, with the
+/// token of the `connection_init` payload in place of the header.
+async fn connection_init(
+    data: Arc<AppState>,
+    mut headers: HeaderMap,
+    payload: Value,
+) -> async_graphql::Result<Data> {
+    if let Some(authorization) = payload_authorization(&payload) {
+        headers.insert(header::AUTHORIZATION, authorization);
+    }
+    let jwt = authenticate(&data, &headers).await?;
+
+    let mut request_data = Data::default();
+    request_data.insert(crate::graphql::loader(data, jwt.clone()));
+    request_data.insert(jwt);
+    Ok(request_data)
+}
+
+/// The `Authorization` field of a `connection_init` payload, whatever its
+/// case, as clients differ in how they spell it.
+fn payload_authorization(payload: &Value) -> Option<HeaderValue> {
+    let (_, authorization) = payload
+        .as_object()?
+        .iter()
+        .find(|(field, _)| field.eq_ignore_ascii_case("authorization"))?;
+    HeaderValue::from_str(authorization.as_str()?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn takes_the_authorization_from_the_payload() {
+        for payload in [
+            json!({ "Authorization": "Bearer token" }),
+            json!({ "authorization": "Bearer token" }),
+        ] {
+            assert_eq!(
+                payload_authorization(&payload).unwrap(),
+                "Bearer token"
+            );
+        }
+    }
+
+    #[test]
+    fn ignores_payloads_without_an_authorization() {
+        for payload in [
+            Value::Null,
+            json!({}),
+            json!({ "authorization": 1 }),
+            json!({ "authorization": "Bearer\ntoken" }),
+        ] {
+            assert!(payload_authorization(&payload).is_none(), "{}", payload);
+        }
+    }
+}
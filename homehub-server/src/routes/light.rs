@@ -0,0 +1,330 @@
+use crate::{
+    error::{ApiError, ErrorResponse},
+    middleware::jwt_auth::JWTAuthMiddleware,
+    routes::PageMetadata,
+    state::AppState,
+    util::{
+        deserialize::deserialize_some,
+        etag::{ETag, IfMatch},
+        extract::{ApiJson, ApiPath, ApiQuery},
+    },
+};
+use axum::{
+    extract::State, http::StatusCode, response::IntoResponse, Extension, Json,
+};
+use homehub_core::{
+    light::{LightDto, LightFilter, LightSelector, LightSort, LightState},
+    pagination::{page_request, SortOrder},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct LightsResponse {
+    status: &'static str,
+    lights: Vec<LightDto>,
+    page: PageMetadata,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct LightResponse {
+    status: &'static str,
+    light: LightDto,
+}
+
+impl From<LightDto> for LightResponse {
+    fn from(light: LightDto) -> Self {
+        LightResponse {
+            status: "success",
+            light,
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ListLightsQuery {
+    /// Only list lights whose name contains this.
+    name: Option<String>,
+    /// Only list lights in this room.
+    room_id: Option<uuid::Uuid>,
+    /// Only list lights in this zone.
+    zone_id: Option<uuid::Uuid>,
+    /// Only list lights that are on, or off.
+    on: Option<bool>,
+    sort: Option<LightSort>,
+    order: Option<SortOrder>,
+    /// The `next_cursor` of the previous page.
+    after: Option<String>,
+    /// How many lights to return, at most 200.
+    limit: Option<u64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/lights",
+    tag = "lights",
+    params(ListLightsQuery),
+    responses(
+        (status = 200, body = LightsResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["lights:read"])),
+)]
+pub(crate) async fn get_lights(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiQuery(query): ApiQuery<ListLightsQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("lights:read")?;
+    let page = page_request(
+        query.sort.unwrap_or_default(),
+        query.order.unwrap_or_default(),
+        query.after.as_deref(),
+        query.limit,
+    )
+    .map_err(|e| jwt.error(e))?;
+    let filter = LightFilter {
+        name: query.name,
+        room_id: query.room_id,
+        zone_id: query.zone_id,
+        on: query.on,
+    };
+    homehub_core::light::get_lights(&filter, &page, &data.db)
+        .await
+        .map(|lights| {
+            Json(LightsResponse {
+                status: "success",
+                page: PageMetadata::from(&lights),
+                lights: lights.items,
+            })
+        })
+        .map_err(|e| jwt.error(e))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct CreateLightPayload {
+    name: String,
+    room_id: Option<uuid::Uuid>,
+    /// The id of the light's device profile.
+    profile_id: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/lights",
+    tag = "lights",
+    request_body = CreateLightPayload,
+    responses(
+        (status = 201, body = LightResponse, headers(("ETag" = String, description = "The version of the light"))),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["lights:write"])),
+)]
+pub(crate) async fn create_light(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiJson(payload): ApiJson<CreateLightPayload>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("lights:write")?;
+    homehub_core::light::create_light(
+        &payload.name,
+        payload.room_id,
+        payload.profile_id.as_deref(),
+        &data.db,
+    )
+    .await
+    .map(|light| {
+        (
+            StatusCode::CREATED,
+            ETag(light.version),
+            Json(LightResponse::from(light)),
+        )
+    })
+    .map_err(|e| jwt.error(e))
+}
+
+#[utoipa::path(
+    get,
+    path = "/lights/{id}",
+    tag = "lights",
+    params(("id" = uuid::Uuid, Path, description = "Light id")),
+    responses(
+        (status = 200, body = LightResponse, headers(("ETag" = String, description = "The version of the light"))),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["lights:read"])),
+)]
+pub(crate) async fn get_light(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(id): ApiPath<uuid::Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("lights:read")?;
+    homehub_core::light::get_light(&id, &data.db)
+        .await
+        .map(|light| (ETag(light.version), Json(LightResponse::from(light))))
+        .map_err(|e| jwt.error(e))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct UpdateLightPayload {
+    name: Option<String>,
+    /// Moves the light to another room, or out of its room when `null`.
+    #[serde(default, deserialize_with = "deserialize_some")]
+    #[schema(value_type = Option<uuid::Uuid>)]
+    room_id: Option<Option<uuid::Uuid>>,
+    /// Links the light to another device profile, or unlinks it when
+    /// `null`.
+    #[serde(default, deserialize_with = "deserialize_some")]
+    #[schema(value_type = Option<String>)]
+    profile_id: Option<Option<String>>,
+}
+
+#[utoipa::path(
+    patch,
+    path = "/lights/{id}",
+    tag = "lights",
+    params(
+        ("id" = uuid::Uuid, Path, description = "Light id"),
+        ("If-Match" = Option<String>, Header, description = "Only update the light if its `ETag` is one of these"),
+    ),
+    request_body = UpdateLightPayload,
+    responses(
+        (status = 200, body = LightResponse, headers(("ETag" = String, description = "The version of the light"))),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["lights:write"])),
+)]
+pub(crate) async fn update_light(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(id): ApiPath<uuid::Uuid>,
+    IfMatch(if_match): IfMatch,
+    ApiJson(payload): ApiJson<UpdateLightPayload>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("lights:write")?;
+    homehub_core::light::update_light(
+        &id,
+        payload.name.as_deref(),
+        payload.room_id,
+        payload.profile_id.as_ref().map(Option::as_deref),
+        if_match.as_deref(),
+        &data.db,
+    )
+    .await
+    .map(|light| (ETag(light.version), Json(LightResponse::from(light))))
+    .map_err(|e| jwt.error(e))
+}
+
+#[utoipa::path(
+    put,
+    path = "/lights/{id}/state",
+    tag = "lights",
+    params(
+        ("id" = uuid::Uuid, Path, description = "Light id"),
+        ("If-Match" = Option<String>, Header, description = "Only update the light if its `ETag` is one of these"),
+    ),
+    request_body = LightState,
+    responses(
+        (status = 200, body = LightResponse, headers(("ETag" = String, description = "The version of the light"))),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["lights:write"])),
+)]
+pub(crate) async fn set_light_state(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(id): ApiPath<uuid::Uuid>,
+    IfMatch(if_match): IfMatch,
+    ApiJson(state): ApiJson<LightState>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("lights:write")?;
+    homehub_core::light::set_light_state(
+        &id,
+        state,
+        if_match.as_deref(),
+        &data.db,
+        &data.light_events,
+    )
+    .await
+    .map(|light| (ETag(light.version), Json(LightResponse::from(light))))
+    .map_err(|e| jwt.error(e))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct SetLightStatesPayload {
+    lights: LightSelector,
+    state: LightState,
+}
+
+/// The outcome for one light: either the updated light or the error that
+/// kept it from being updated.
+#[derive(Serialize, ToSchema)]
+pub(crate) struct LightResult {
+    id: uuid::Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    light: Option<LightDto>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ErrorResponse>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct LightResultsResponse {
+    status: &'static str,
+    results: Vec<LightResult>,
+}
+
+/// Sets the state of many lights in one transaction. Lights selected by id
+/// get a result each, in the order asked for, failing on their own if they
+/// do not exist. A room, location or zone gets a result for each of its
+/// lights and fails as a whole if it does not exist. The state is only
+/// recorded; it is not sent to the lights themselves.
+#[utoipa::path(
+    put,
+    path = "/lights/state",
+    tag = "lights",
+    request_body = SetLightStatesPayload,
+    responses(
+        (status = 200, body = LightResultsResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["lights:write"])),
+)]
+pub(crate) async fn set_light_states(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiJson(payload): ApiJson<SetLightStatesPayload>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("lights:write")?;
+    homehub_core::light::set_light_states(
+        &payload.lights,
+        payload.state,
+        &data.db,
+        &data.light_events,
+    )
+    .await
+    .map(|results| {
+        let results = results
+            .into_iter()
+            .map(|result| match result.result {
+                Ok(light) => LightResult {
+                    id: result.id,
+                    light: Some(light),
+                    error: None,
+                },
+                Err(e) => LightResult {
+                    id: result.id,
+                    light: None,
+                    error: Some(ErrorResponse::from(&jwt.error(e))),
+                },
+            })
+            .collect();
+        Json(LightResultsResponse {
+            status: "success",
+            results,
+        })
+    })
+    .map_err(|e| jwt.error(e))
+}
@@ -0,0 +1,255 @@
+use crate::{
+    error::ApiError,
+    middleware::jwt_auth::JWTAuthMiddleware,
+    state::AppState,
+    util::{
+        etag::{ETag, IfMatch},
+        extract::{ApiJson, ApiPath},
+    },
+};
+use axum::{extract::State, response::IntoResponse, Extension, Json};
+use homehub_core::{
+    light::{LightState, RoomDto},
+    location::LocationDto,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct RoomResponse {
+    status: &'static str,
+    room: RoomDto,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct LocationResponse {
+    status: &'static str,
+    location: LocationDto,
+}
+
+#[utoipa::path(
+    get,
+    path = "/rooms/{id}",
+    tag = "rooms",
+    params(("id" = uuid::Uuid, Path, description = "Room id")),
+    responses(
+        (status = 200, body = RoomResponse, headers(("ETag" = String, description = "The version of the room"))),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["lights:read"])),
+)]
+pub(crate) async fn get_room(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(id): ApiPath<uuid::Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("lights:read")?;
+    homehub_core::location::get_room(&id, &data.db)
+        .await
+        .map(|room| {
+            (
+                ETag(room.version),
+                Json(RoomResponse {
+                    status: "success",
+                    room,
+                }),
+            )
+        })
+        .map_err(|e| jwt.error(e))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct UpdateRoomPayload {
+    name: Option<String>,
+}
+
+#[utoipa::path(
+    patch,
+    path = "/rooms/{id}",
+    tag = "rooms",
+    params(
+        ("id" = uuid::Uuid, Path, description = "Room id"),
+        ("If-Match" = Option<String>, Header, description = "Only update the room if its `ETag` is one of these"),
+    ),
+    request_body = UpdateRoomPayload,
+    responses(
+        (status = 200, body = RoomResponse, headers(("ETag" = String, description = "The version of the room"))),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["lights:write"])),
+)]
+pub(crate) async fn update_room(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(id): ApiPath<uuid::Uuid>,
+    IfMatch(if_match): IfMatch,
+    ApiJson(payload): ApiJson<UpdateRoomPayload>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("lights:write")?;
+    homehub_core::location::update_room(
+        &id,
+        payload.name.as_deref(),
+        if_match.as_deref(),
+        &data.db,
+    )
+    .await
+    .map(|room| {
+        (
+            ETag(room.version),
+            Json(RoomResponse {
+                status: "success",
+                room,
+            }),
+        )
+    })
+    .map_err(|e| jwt.error(e))
+}
+
+#[utoipa::path(
+    put,
+    path = "/rooms/{id}/state",
+    tag = "rooms",
+    params(("id" = uuid::Uuid, Path, description = "Room id")),
+    request_body = LightState,
+    responses(
+        (status = 200, body = RoomResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["lights:write"])),
+)]
+pub(crate) async fn set_room_state(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(id): ApiPath<uuid::Uuid>,
+    ApiJson(state): ApiJson<LightState>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("lights:write")?;
+    homehub_core::location::set_room_state(
+        &id,
+        state,
+        &data.db,
+        &data.light_events,
+    )
+    .await
+    .map(|room| {
+        Json(RoomResponse {
+            status: "success",
+            room,
+        })
+    })
+    .map_err(|e| jwt.error(e))
+}
+
+#[utoipa::path(
+    get,
+    path = "/locations/{id}",
+    tag = "locations",
+    params(("id" = uuid::Uuid, Path, description = "Location id")),
+    responses(
+        (status = 200, body = LocationResponse, headers(("ETag" = String, description = "The version of the location"))),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["lights:read"])),
+)]
+pub(crate) async fn get_location(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(id): ApiPath<uuid::Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("lights:read")?;
+    homehub_core::location::get_location(&id, &data.db)
+        .await
+        .map(|location| {
+            (
+                ETag(location.version),
+                Json(LocationResponse {
+                    status: "success",
+                    location,
+                }),
+            )
+        })
+        .map_err(|e| jwt.error(e))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct UpdateLocationPayload {
+    name: Option<String>,
+}
+
+#[utoipa::path(
+    patch,
+    path = "/locations/{id}",
+    tag = "locations",
+    params(
+        ("id" = uuid::Uuid, Path, description = "Location id"),
+        ("If-Match" = Option<String>, Header, description = "Only update the location if its `ETag` is one of these"),
+    ),
+    request_body = UpdateLocationPayload,
+    responses(
+        (status = 200, body = LocationResponse, headers(("ETag" = String, description = "The version of the location"))),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["lights:write"])),
+)]
+pub(crate) async fn update_location(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(id): ApiPath<uuid::Uuid>,
+    IfMatch(if_match): IfMatch,
+    ApiJson(payload): ApiJson<UpdateLocationPayload>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("lights:write")?;
+    homehub_core::location::update_location(
+        &id,
+        payload.name.as_deref(),
+        if_match.as_deref(),
+        &data.db,
+    )
+    .await
+    .map(|location| {
+        (
+            ETag(location.version),
+            Json(LocationResponse {
+                status: "success",
+                location,
+            }),
+        )
+    })
+    .map_err(|e| jwt.error(e))
+}
+
+#[utoipa::path(
+    put,
+    path = "/locations/{id}/state",
+    tag = "locations",
+    params(("id" = uuid::Uuid, Path, description = "Location id")),
+    request_body = LightState,
+    responses(
+        (status = 200, body = LocationResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["lights:write"])),
+)]
+pub(crate) async fn set_location_state(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(id): ApiPath<uuid::Uuid>,
+    ApiJson(state): ApiJson<LightState>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("lights:write")?;
+    homehub_core::location::set_location_state(
+        &id,
+        state,
+        &data.db,
+        &data.light_events,
+    )
+    .await
+    .map(|location| {
+        Json(LocationResponse {
+            status: "success",
+            location,
+        })
+    })
+    .map_err(|e| jwt.error(e))
+}
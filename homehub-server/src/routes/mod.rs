@@ -1,2 +1,46 @@
+use homehub_core::pagination::Page;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+pub mod admin;
 pub mod auth;
+pub mod device;
+pub mod graphql;
+pub mod light;
+pub mod location;
+pub mod oauth;
+pub mod oidc;
+pub mod profile;
+pub mod sensor;
 pub mod user;
+pub mod zone;
+
+/// The response of endpoints that have nothing to return but success.
+#[derive(Serialize, ToSchema)]
+pub(crate) struct StatusResponse {
+    status: &'static str,
+}
+
+impl StatusResponse {
+    pub(crate) fn success() -> Self {
+        StatusResponse { status: "success" }
+    }
+}
+
+/// Where a page sits in its collection. Pass `next_cursor` as `after` to get
+/// the next page; it is absent on the last one.
+#[derive(Serialize, ToSchema)]
+pub(crate) struct PageMetadata {
+    limit: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
+}
+
+impl<T> From<&Page<T>> for PageMetadata {
+    fn from(page: &Page<T>) -> Self {
+        PageMetadata {
+            limit: page.limit,
+            next_cursor: page.next_cursor.clone(),
+        }
+    }
+}
@@ -0,0 +1,356 @@
+use crate::{
+    error::{status_code, ApiError},
+    middleware::{idempotency::IssuesSecret, jwt_auth::JWTAuthMiddleware},
+    routes::StatusResponse,
+    state::AppState,
+    util::{
+        extract::{ApiJson, ApiPath, ApiQuery},
+        locale::UserLocale,
+    },
+};
+use axum::{
+    extract::{rejection::FormRejection, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Form, Json,
+};
+use homehub_core::oauth::{
+    AuthorizationRequest, ConsentDetails, ConsentDto, OAuthClientDto,
+    TokenRequest,
+};
+use homehub_core::{error::Error, i18n::Locale};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct RegisteredClientResponse {
+    status: &'static str,
+    client: OAuthClientDto,
+    /// Only returned once, for confidential clients.
+    client_secret: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ClientsResponse {
+    status: &'static str,
+    clients: Vec<OAuthClientDto>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ConsentDetailsResponse {
+    status: &'static str,
+    consent: ConsentDetails,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct RedirectResponse {
+    status: &'static str,
+    redirect_to: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ConsentsResponse {
+    status: &'static str,
+    consents: Vec<ConsentDto>,
+}
+
+/// Error response of the token endpoint, as defined by RFC 6749.
+#[derive(Serialize, ToSchema)]
+pub(crate) struct TokenErrorResponse {
+    #[schema(example = "invalid_grant")]
+    error: &'static str,
+    error_description: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct RegisterClientPayload {
+    name: String,
+    redirect_uris: Vec<String>,
+    /// Space separated scopes the client may ask for.
+    scope: String,
+    #[serde(default)]
+    confidential: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/oauth/clients",
+    tag = "oauth",
+    request_body = RegisterClientPayload,
+    responses(
+        (status = 200, body = RegisteredClientResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = [])),
+)]
+pub(crate) async fn register_client(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiJson(payload): ApiJson<RegisterClientPayload>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_first_party()?;
+    homehub_core::oauth::register_client(
+        jwt.user.id,
+        &payload.name,
+        payload.redirect_uris,
+        &payload.scope,
+        payload.confidential,
+        &data.db,
+    )
+    .await
+    .map(|registered| {
+        (
+            Extension(IssuesSecret),
+            Json(RegisteredClientResponse {
+                status: "success",
+                client: registered.client,
+                client_secret: registered.client_secret,
+            }),
+        )
+    })
+    .map_err(|e| jwt.error(e))
+}
+
+#[utoipa::path(
+    get,
+    path = "/oauth/clients",
+    tag = "oauth",
+    responses(
+        (status = 200, body = ClientsResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = [])),
+)]
+pub(crate) async fn list_clients(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_first_party()?;
+    homehub_core::oauth::list_clients(jwt.user.id, &data.db)
+        .await
+        .map(|clients| {
+            Json(ClientsResponse {
+                status: "success",
+                clients,
+            })
+        })
+        .map_err(|e| jwt.error(e))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/oauth/clients/{id}",
+    tag = "oauth",
+    params(("id" = uuid::Uuid, Path, description = "Client id")),
+    responses(
+        (status = 200, body = StatusResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = [])),
+)]
+pub(crate) async fn delete_client(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(client_id): ApiPath<uuid::Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_first_party()?;
+    homehub_core::oauth::delete_client(jwt.user.id, client_id, &data.db)
+        .await
+        .map(|_| Json(StatusResponse::success()))
+        .map_err(|e| jwt.error(e))
+}
+
+#[utoipa::path(
+    get,
+    path = "/oauth/authorize",
+    tag = "oauth",
+    params(AuthorizationRequest),
+    responses(
+        (status = 200, body = ConsentDetailsResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = [])),
+)]
+pub(crate) async fn get_consent_details(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiQuery(request): ApiQuery<AuthorizationRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_first_party()?;
+    homehub_core::oauth::get_consent_details(&request, jwt.user.id, &data.db)
+        .await
+        .map(|consent| {
+            Json(ConsentDetailsResponse {
+                status: "success",
+                consent,
+            })
+        })
+        .map_err(|e| jwt.error(e))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct AuthorizePayload {
+    #[serde(flatten)]
+    request: AuthorizationRequest,
+    approved: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/oauth/authorize",
+    tag = "oauth",
+    request_body = AuthorizePayload,
+    responses(
+        (status = 200, body = RedirectResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = [])),
+)]
+pub(crate) async fn authorize(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiJson(payload): ApiJson<AuthorizePayload>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_first_party()?;
+    let redirect_to = match payload.approved {
+        true => {
+            homehub_core::oauth::approve_authorization(
+                &payload.request,
+                jwt.user.id,
+                &data.db,
+            )
+            .await
+        }
+        false => {
+            homehub_core::oauth::deny_authorization(&payload.request, &data.db)
+                .await
+        }
+    };
+
+    redirect_to
+        .map(|redirect_to| {
+            (
+                Extension(IssuesSecret),
+                Json(RedirectResponse {
+                    status: "success",
+                    redirect_to,
+                }),
+            )
+        })
+        .map_err(|e| jwt.error(e))
+}
+
+#[utoipa::path(
+    post,
+    path = "/oauth/token",
+    tag = "oauth",
+    request_body(
+        content = TokenRequest,
+        content_type = "application/x-www-form-urlencoded",
+    ),
+    responses(
+        (status = 200, body = OAuthTokens),
+        (status = "default", body = TokenErrorResponse),
+    ),
+)]
+pub(crate) async fn token(
+    State(data): State<Arc<AppState>>,
+    UserLocale(locale): UserLocale,
+    request: Result<Form<TokenRequest>, FormRejection>,
+) -> Result<impl IntoResponse, (StatusCode, Json<TokenErrorResponse>)> {
+    let Form(request) = request.map_err(|rejection| {
+        translate_token_error(
+            Error::InvalidRequestBodyError(rejection.body_text()),
+            &locale,
+        )
+    })?;
+    homehub_core::oauth::exchange_token(&request, &data.db, &data.config)
+        .await
+        .map(Json)
+        .map_err(|e| translate_token_error(e, &locale))
+}
+
+#[utoipa::path(
+    get,
+    path = "/oauth/consents",
+    tag = "oauth",
+    responses(
+        (status = 200, body = ConsentsResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = [])),
+)]
+pub(crate) async fn list_consents(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_first_party()?;
+    homehub_core::oauth::list_consents(jwt.user.id, &data.db)
+        .await
+        .map(|consents| {
+            Json(ConsentsResponse {
+                status: "success",
+                consents,
+            })
+        })
+        .map_err(|e| jwt.error(e))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/oauth/consents/{client_id}",
+    tag = "oauth",
+    params(("client_id" = uuid::Uuid, Path, description = "Client id")),
+    responses(
+        (status = 200, body = StatusResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = [])),
+)]
+pub(crate) async fn revoke_consent(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(client_id): ApiPath<uuid::Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_first_party()?;
+    homehub_core::oauth::revoke_consent(jwt.user.id, client_id, &data.db)
+        .await
+        .map(|_| Json(StatusResponse::success()))
+        .map_err(|e| jwt.error(e))
+}
+
+/// The token endpoint is called by third-party clients, so its errors use
+/// the RFC 6749 format rather than our usual envelope.
+fn translate_token_error(
+    e: Error,
+    locale: &Locale,
+) -> (StatusCode, Json<TokenErrorResponse>) {
+    let (status, error) = match e {
+        Error::OAuthInvalidClientError | Error::OAuthUnknownClientError => {
+            (StatusCode::UNAUTHORIZED, "invalid_client")
+        }
+        Error::OAuthInvalidScopeError(_) => {
+            (StatusCode::BAD_REQUEST, "invalid_scope")
+        }
+        Error::OAuthUnsupportedGrantTypeError => {
+            (StatusCode::BAD_REQUEST, "unsupported_grant_type")
+        }
+        Error::InvalidRequestBodyError(_) => {
+            (StatusCode::BAD_REQUEST, "invalid_request")
+        }
+        _ if status_code(&e).is_server_error() => {
+            tracing::error!("{:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "server_error")
+        }
+        _ => (StatusCode::BAD_REQUEST, "invalid_grant"),
+    };
+
+    (
+        status,
+        Json(TokenErrorResponse {
+            error,
+            error_description: e.message(locale),
+        }),
+    )
+}
@@ -0,0 +1,82 @@
+use crate::{
+    error::ApiError,
+    routes::auth::TokensResponse,
+    state::AppState,
+    util::{extract::ApiQuery, locale::UserLocale},
+};
+use axum::{
+    extract::State,
+    response::{IntoResponse, Redirect},
+    Json,
+};
+use homehub_core::error::Error;
+use serde::Deserialize;
+use std::sync::Arc;
+use utoipa::IntoParams;
+
+/// Redirects to the identity provider to log in.
+#[utoipa::path(
+    get,
+    path = "/auth/oidc/login",
+    tag = "auth",
+    responses(
+        (status = 303, description = "Redirect to the identity provider"),
+        (status = "default", body = ErrorResponse),
+    ),
+)]
+pub(crate) async fn begin_login(
+    State(data): State<Arc<AppState>>,
+    UserLocale(locale): UserLocale,
+) -> Result<impl IntoResponse, ApiError> {
+    let oidc = data
+        .oidc
+        .as_ref()
+        .ok_or_else(|| ApiError::new(Error::OidcNotConfiguredError, &locale))?;
+    Ok(Redirect::to(&oidc.begin_login()))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/oidc/callback",
+    tag = "auth",
+    params(CallbackQuery),
+    responses(
+        (status = 200, body = TokensResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+)]
+pub(crate) async fn callback(
+    State(data): State<Arc<AppState>>,
+    UserLocale(locale): UserLocale,
+    ApiQuery(query): ApiQuery<CallbackQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let oidc = data
+        .oidc
+        .as_ref()
+        .ok_or_else(|| ApiError::new(Error::OidcNotConfiguredError, &locale))?;
+
+    if let Some(error) = query.error {
+        return Err(ApiError::new(
+            Error::OidcProviderError(query.error_description.unwrap_or(error)),
+            &locale,
+        ));
+    }
+
+    let (Some(code), Some(state)) = (query.code, query.state) else {
+        return Err(ApiError::new(Error::OidcMissingCodeError, &locale));
+    };
+
+    oidc.complete_login(&data.db, &code, &state, &data.config)
+        .await
+        .map(|tokens| Json(TokensResponse::from(tokens)))
+        .map_err(|e| ApiError::new(e, &locale))
+}
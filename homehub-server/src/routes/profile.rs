@@ -0,0 +1,69 @@
+use crate::{
+    error::ApiError, middleware::jwt_auth::JWTAuthMiddleware,
+    util::extract::ApiPath,
+};
+use axum::{response::IntoResponse, Extension, Json};
+use homehub_core::{error::Error, profile::DeviceProfile};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ProfilesResponse {
+    status: &'static str,
+    profiles: Vec<DeviceProfile>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ProfileResponse {
+    status: &'static str,
+    profile: DeviceProfile,
+}
+
+#[utoipa::path(
+    get,
+    path = "/profiles",
+    tag = "devices",
+    responses(
+        (status = 200, body = ProfilesResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["devices:read"])),
+)]
+pub(crate) async fn get_profiles(
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("devices:read")?;
+    Ok(Json(ProfilesResponse {
+        status: "success",
+        profiles: homehub_core::profile::get_profiles()
+            .into_iter()
+            .cloned()
+            .collect(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/profiles/{id}",
+    tag = "devices",
+    params(("id" = String, Path, description = "Profile id")),
+    responses(
+        (status = 200, body = ProfileResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["devices:read"])),
+)]
+pub(crate) async fn get_profile(
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(id): ApiPath<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("devices:read")?;
+    homehub_core::profile::get_profile(&id)
+        .map(|profile| {
+            Json(ProfileResponse {
+                status: "success",
+                profile: profile.clone(),
+            })
+        })
+        .ok_or_else(|| jwt.error(Error::DeviceProfileNotFoundError))
+}
@@ -0,0 +1,194 @@
+use crate::{
+    error::ApiError,
+    middleware::jwt_auth::JWTAuthMiddleware,
+    state::AppState,
+    util::extract::{ApiJson, ApiPath, ApiQuery},
+};
+use axum::{
+    extract::State, http::StatusCode, response::IntoResponse, Extension, Json,
+};
+use chrono::{Duration, NaiveDateTime, Utc};
+use homehub_core::sensor::{BucketDto, Metric, ReadingDto};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct LatestReadingsResponse {
+    status: &'static str,
+    /// The most recent reading of each metric.
+    readings: Vec<ReadingDto>,
+}
+
+/// Either the readings themselves, or their aggregates when a bucket was
+/// asked for.
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ReadingsResponse {
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    readings: Option<Vec<ReadingDto>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    buckets: Option<Vec<BucketDto>>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct NewReading {
+    metric: Metric,
+    value: f64,
+    /// When the value was measured. Defaults to when it was received.
+    recorded_at: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct RecordReadingsPayload {
+    readings: Vec<NewReading>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/sensors/{id}/readings",
+    tag = "sensors",
+    params(("id" = uuid::Uuid, Path, description = "Device id")),
+    request_body = RecordReadingsPayload,
+    responses(
+        (status = 201, body = LatestReadingsResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["sensors:write"])),
+)]
+pub(crate) async fn record_readings(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(id): ApiPath<uuid::Uuid>,
+    ApiJson(payload): ApiJson<RecordReadingsPayload>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("sensors:write")?;
+    let now = Utc::now().naive_utc();
+    let readings: Vec<_> = payload
+        .readings
+        .into_iter()
+        .map(|reading| ReadingDto {
+            metric: reading.metric,
+            value: reading.value,
+            recorded_at: reading.recorded_at.unwrap_or(now),
+        })
+        .collect();
+    homehub_core::sensor::record_readings(
+        &id,
+        &readings,
+        &data.db,
+        &data.latest_readings,
+    )
+    .await
+    .map(|readings| {
+        (
+            StatusCode::CREATED,
+            Json(LatestReadingsResponse {
+                status: "success",
+                readings,
+            }),
+        )
+    })
+    .map_err(|e| jwt.error(e))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ListReadingsQuery {
+    metric: Metric,
+    /// The start of the range, inclusive. Defaults to a day before `to`.
+    from: Option<NaiveDateTime>,
+    /// The end of the range, exclusive. Defaults to now.
+    to: Option<NaiveDateTime>,
+    /// Aggregate the readings into buckets of this many seconds instead of
+    /// listing them.
+    bucket: Option<u32>,
+    /// How many readings to return, at most 10000. Ignored for buckets.
+    limit: Option<u64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/sensors/{id}/readings",
+    tag = "sensors",
+    params(
+        ("id" = uuid::Uuid, Path, description = "Device id"),
+        ListReadingsQuery,
+    ),
+    responses(
+        (status = 200, body = ReadingsResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["sensors:read"])),
+)]
+pub(crate) async fn get_readings(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(id): ApiPath<uuid::Uuid>,
+    ApiQuery(query): ApiQuery<ListReadingsQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("sensors:read")?;
+    let response = match query.bucket {
+        Some(bucket) => homehub_core::sensor::get_reading_buckets(
+            &id,
+            query.metric,
+            query.from,
+            query.to,
+            Duration::seconds(bucket.into()),
+            &data.db,
+        )
+        .await
+        .map(|buckets| ReadingsResponse {
+            status: "success",
+            readings: None,
+            buckets: Some(buckets),
+        }),
+        None => homehub_core::sensor::get_readings(
+            &id,
+            query.metric,
+            query.from,
+            query.to,
+            query.limit,
+            &data.db,
+        )
+        .await
+        .map(|readings| ReadingsResponse {
+            status: "success",
+            readings: Some(readings),
+            buckets: None,
+        }),
+    };
+    response.map(Json).map_err(|e| jwt.error(e))
+}
+
+#[utoipa::path(
+    get,
+    path = "/sensors/{id}/readings/latest",
+    tag = "sensors",
+    params(("id" = uuid::Uuid, Path, description = "Device id")),
+    responses(
+        (status = 200, body = LatestReadingsResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["sensors:read"])),
+)]
+pub(crate) async fn get_latest_readings(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(id): ApiPath<uuid::Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("sensors:read")?;
+    homehub_core::sensor::get_latest_readings(
+        &id,
+        &data.db,
+        &data.latest_readings,
+    )
+    .await
+    .map(|readings| {
+        Json(LatestReadingsResponse {
+            status: "success",
+            readings,
+        })
+    })
+    .map_err(|e| jwt.error(e))
+}
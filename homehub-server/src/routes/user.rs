@@ -1,14 +1,174 @@
-use axum::{http::StatusCode, response::IntoResponse, Extension, Json};
+use axum::{extract::State, response::IntoResponse, Extension, Json};
 use homehub_db::queries::app_user::FilteredAppUserModel;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
 
-use crate::middleware::jwt_auth::JWTAuthMiddleware;
+use crate::{
+    error::ApiError,
+    middleware::jwt_auth::JWTAuthMiddleware,
+    routes::StatusResponse,
+    state::AppState,
+    util::{
+        deserialize::deserialize_some, extract::ApiJson, locale::UserLocale,
+    },
+};
 
+#[derive(Serialize, ToSchema)]
+pub(crate) struct UserResponse {
+    status: &'static str,
+    user: FilteredAppUserModel,
+}
+
+impl From<FilteredAppUserModel> for UserResponse {
+    fn from(user: FilteredAppUserModel) -> Self {
+        UserResponse {
+            status: "success",
+            user,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/user",
+    tag = "user",
+    responses(
+        (status = 200, body = UserResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["user:read"])),
+)]
 pub async fn get_me(
     Extension(jwt): Extension<JWTAuthMiddleware>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("user:read")?;
     let user: FilteredAppUserModel = jwt.user.into();
-    Ok(Json(serde_json::json!({
-        "status": "success",
-        "user": user,
-    })))
+    Ok(Json(UserResponse::from(user)))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct UpdateMePayload {
+    name: Option<String>,
+    /// Only takes effect once confirmed with the code sent to the new
+    /// address.
+    email: Option<String>,
+    /// Clears the locale when `null`.
+    #[serde(default, deserialize_with = "deserialize_some")]
+    #[schema(value_type = Option<String>)]
+    locale: Option<Option<String>>,
+}
+
+#[utoipa::path(
+    patch,
+    path = "/user",
+    tag = "user",
+    request_body = UpdateMePayload,
+    responses(
+        (status = 200, body = UserResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = [])),
+)]
+pub(crate) async fn update_me(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    UserLocale(locale): UserLocale,
+    ApiJson(payload): ApiJson<UpdateMePayload>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_first_party()?;
+    homehub_core::user::update_user(
+        &data.db,
+        jwt.user.id,
+        payload.name.as_deref(),
+        payload.email.as_deref(),
+        payload.locale.as_ref().map(Option::as_deref),
+        &data.mailer,
+    )
+    .await
+    .map(|user| Json(UserResponse::from(user)))
+    .map_err(|e| ApiError::new(e, &locale))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct VerifyEmailPayload {
+    verification_token: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/verify-email",
+    tag = "user",
+    request_body = VerifyEmailPayload,
+    responses(
+        (status = 200, body = UserResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+)]
+pub(crate) async fn verify_email(
+    State(data): State<Arc<AppState>>,
+    UserLocale(locale): UserLocale,
+    ApiJson(payload): ApiJson<VerifyEmailPayload>,
+) -> Result<impl IntoResponse, ApiError> {
+    homehub_core::user::verify_email(&data.db, &payload.verification_token)
+        .await
+        .map(|user| Json(UserResponse::from(user)))
+        .map_err(|e| ApiError::new(e, &locale))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct ChangePasswordPayload {
+    current_password: String,
+    new_password: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/user/password",
+    tag = "user",
+    request_body = ChangePasswordPayload,
+    responses(
+        (status = 200, body = StatusResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = [])),
+)]
+pub(crate) async fn change_password(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    UserLocale(locale): UserLocale,
+    ApiJson(payload): ApiJson<ChangePasswordPayload>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_first_party()?;
+    homehub_core::user::change_password(
+        &data.db,
+        jwt.user.id,
+        &payload.current_password,
+        &payload.new_password,
+        &data.config,
+    )
+    .await
+    .map(|_| Json(StatusResponse::success()))
+    .map_err(|e| ApiError::new(e, &locale))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/user",
+    tag = "user",
+    responses(
+        (status = 200, body = StatusResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = [])),
+)]
+pub(crate) async fn delete_me(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_first_party()?;
+    homehub_core::user::delete_user(&data.db, jwt.user.id)
+        .await
+        .map(|_| Json(StatusResponse::success()))
+        .map_err(|e| jwt.error(e))
 }
@@ -0,0 +1,293 @@
+use crate::{
+    error::ApiError,
+    middleware::jwt_auth::JWTAuthMiddleware,
+    routes::{PageMetadata, StatusResponse},
+    state::AppState,
+    util::{
+        etag::{ETag, IfMatch},
+        extract::{ApiJson, ApiPath, ApiQuery},
+    },
+};
+use axum::{
+    extract::State, http::StatusCode, response::IntoResponse, Extension, Json,
+};
+use homehub_core::{
+    light::LightState,
+    pagination::{page_request, SortOrder},
+    zone::{ZoneDto, ZoneSort},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ZonesResponse {
+    status: &'static str,
+    zones: Vec<ZoneDto>,
+    page: PageMetadata,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ZoneResponse {
+    status: &'static str,
+    zone: ZoneDto,
+}
+
+impl From<ZoneDto> for ZoneResponse {
+    fn from(zone: ZoneDto) -> Self {
+        ZoneResponse {
+            status: "success",
+            zone,
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ListZonesQuery {
+    /// Only list zones this light is in.
+    light_id: Option<uuid::Uuid>,
+    sort: Option<ZoneSort>,
+    order: Option<SortOrder>,
+    /// The `next_cursor` of the previous page.
+    after: Option<String>,
+    /// How many zones to return, at most 200.
+    limit: Option<u64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/zones",
+    tag = "zones",
+    params(ListZonesQuery),
+    responses(
+        (status = 200, body = ZonesResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["lights:read"])),
+)]
+pub(crate) async fn get_zones(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiQuery(query): ApiQuery<ListZonesQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("lights:read")?;
+    let page = page_request(
+        query.sort.unwrap_or_default(),
+        query.order.unwrap_or_default(),
+        query.after.as_deref(),
+        query.limit,
+    )
+    .map_err(|e| jwt.error(e))?;
+    homehub_core::zone::get_zones(query.light_id.as_ref(), &page, &data.db)
+        .await
+        .map(|zones| {
+            Json(ZonesResponse {
+                status: "success",
+                page: PageMetadata::from(&zones),
+                zones: zones.items,
+            })
+        })
+        .map_err(|e| jwt.error(e))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct CreateZonePayload {
+    name: String,
+    #[serde(default)]
+    light_ids: Vec<uuid::Uuid>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/zones",
+    tag = "zones",
+    request_body = CreateZonePayload,
+    responses(
+        (status = 201, body = ZoneResponse, headers(("ETag" = String, description = "The version of the zone"))),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["lights:write"])),
+)]
+pub(crate) async fn create_zone(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiJson(payload): ApiJson<CreateZonePayload>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("lights:write")?;
+    homehub_core::zone::create_zone(&payload.name, &payload.light_ids, &data.db)
+        .await
+        .map(|zone| {
+            (
+                StatusCode::CREATED,
+                ETag(zone.version),
+                Json(ZoneResponse::from(zone)),
+            )
+        })
+        .map_err(|e| jwt.error(e))
+}
+
+#[utoipa::path(
+    get,
+    path = "/zones/{id}",
+    tag = "zones",
+    params(("id" = uuid::Uuid, Path, description = "Zone id")),
+    responses(
+        (status = 200, body = ZoneResponse, headers(("ETag" = String, description = "The version of the zone"))),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["lights:read"])),
+)]
+pub(crate) async fn get_zone(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(id): ApiPath<uuid::Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("lights:read")?;
+    homehub_core::zone::get_zone(&id, &data.db)
+        .await
+        .map(|zone| (ETag(zone.version), Json(ZoneResponse::from(zone))))
+        .map_err(|e| jwt.error(e))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct UpdateZonePayload {
+    name: Option<String>,
+    /// Replaces the lights in the zone.
+    light_ids: Option<Vec<uuid::Uuid>>,
+}
+
+#[utoipa::path(
+    patch,
+    path = "/zones/{id}",
+    tag = "zones",
+    params(
+        ("id" = uuid::Uuid, Path, description = "Zone id"),
+        ("If-Match" = Option<String>, Header, description = "Only update the zone if its `ETag` is one of these"),
+    ),
+    request_body = UpdateZonePayload,
+    responses(
+        (status = 200, body = ZoneResponse, headers(("ETag" = String, description = "The version of the zone"))),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["lights:write"])),
+)]
+pub(crate) async fn update_zone(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(id): ApiPath<uuid::Uuid>,
+    IfMatch(if_match): IfMatch,
+    ApiJson(payload): ApiJson<UpdateZonePayload>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("lights:write")?;
+    homehub_core::zone::update_zone(
+        &id,
+        payload.name.as_deref(),
+        payload.light_ids.as_deref(),
+        if_match.as_deref(),
+        &data.db,
+    )
+    .await
+    .map(|zone| (ETag(zone.version), Json(ZoneResponse::from(zone))))
+    .map_err(|e| jwt.error(e))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/zones/{id}",
+    tag = "zones",
+    params(("id" = uuid::Uuid, Path, description = "Zone id")),
+    responses(
+        (status = 200, body = StatusResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["lights:write"])),
+)]
+pub(crate) async fn delete_zone(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(id): ApiPath<uuid::Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("lights:write")?;
+    homehub_core::zone::delete_zone(&id, &data.db)
+        .await
+        .map(|_| Json(StatusResponse::success()))
+        .map_err(|e| jwt.error(e))
+}
+
+#[utoipa::path(
+    put,
+    path = "/zones/{id}/lights/{light_id}",
+    tag = "zones",
+    params(
+        ("id" = uuid::Uuid, Path, description = "Zone id"),
+        ("light_id" = uuid::Uuid, Path, description = "Light id"),
+    ),
+    responses(
+        (status = 200, body = ZoneResponse, headers(("ETag" = String, description = "The version of the zone"))),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["lights:write"])),
+)]
+pub(crate) async fn add_light(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath((id, light_id)): ApiPath<(uuid::Uuid, uuid::Uuid)>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("lights:write")?;
+    homehub_core::zone::add_light(&id, &light_id, &data.db)
+        .await
+        .map(|zone| (ETag(zone.version), Json(ZoneResponse::from(zone))))
+        .map_err(|e| jwt.error(e))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/zones/{id}/lights/{light_id}",
+    tag = "zones",
+    params(
+        ("id" = uuid::Uuid, Path, description = "Zone id"),
+        ("light_id" = uuid::Uuid, Path, description = "Light id"),
+    ),
+    responses(
+        (status = 200, body = ZoneResponse, headers(("ETag" = String, description = "The version of the zone"))),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["lights:write"])),
+)]
+pub(crate) async fn remove_light(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath((id, light_id)): ApiPath<(uuid::Uuid, uuid::Uuid)>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("lights:write")?;
+    homehub_core::zone::remove_light(&id, &light_id, &data.db)
+        .await
+        .map(|zone| (ETag(zone.version), Json(ZoneResponse::from(zone))))
+        .map_err(|e| jwt.error(e))
+}
+
+#[utoipa::path(
+    put,
+    path = "/zones/{id}/state",
+    tag = "zones",
+    params(("id" = uuid::Uuid, Path, description = "Zone id")),
+    request_body = LightState,
+    responses(
+        (status = 200, body = ZoneResponse),
+        (status = "default", body = ErrorResponse),
+    ),
+    security(("bearer" = ["lights:write"])),
+)]
+pub(crate) async fn set_zone_state(
+    State(data): State<Arc<AppState>>,
+    Extension(jwt): Extension<JWTAuthMiddleware>,
+    ApiPath(id): ApiPath<uuid::Uuid>,
+    ApiJson(state): ApiJson<LightState>,
+) -> Result<impl IntoResponse, ApiError> {
+    jwt.require_scope("lights:write")?;
+    homehub_core::zone::set_zone_state(&id, state, &data.db, &data.light_events)
+        .await
+        .map(|zone| Json(ZoneResponse::from(zone)))
+        .map_err(|e| jwt.error(e))
+}
@@ -1,6 +1,15 @@
 use homehub_db::DatabaseConnection;
 
+use crate::middleware::rate_limit::RateLimiter;
+
 pub struct AppState {
     pub db: DatabaseConnection,
     pub config: homehub_core::config::Config,
+    pub oidc: Option<homehub_core::oidc::OidcProvider>,
+    pub login_throttle: homehub_core::login_throttle::LoginThrottle,
+    pub rate_limiter: RateLimiter,
+    pub mailer: homehub_core::mail::Mailer,
+    pub light_events: homehub_core::light::LightEvents,
+    pub latest_readings: homehub_core::sensor::LatestReadings,
+    pub graphql_schema: crate::graphql::HomeHubSchema,
 }
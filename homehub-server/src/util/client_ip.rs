@@ -0,0 +1,61 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, HeaderMap, StatusCode},
+};
+
+use crate::state::AppState;
+
+/// The address of the client making the request. When running behind a
+/// reverse proxy with `TRUST_PROXY_HEADERS` set, this is the address the
+/// proxy appended to `X-Forwarded-For` rather than the proxy itself.
+pub struct ClientIp(pub IpAddr);
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for ClientIp {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(ClientIp(client_ip(parts, state)?))
+    }
+}
+
+pub fn client_ip(
+    parts: &Parts,
+    state: &AppState,
+) -> Result<IpAddr, StatusCode> {
+    let peer_ip = parts
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    client_ip_from(&parts.headers, peer_ip, state)
+        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// The address of the client, given the headers of its request and the
+/// address of the peer that sent it.
+pub fn client_ip_from(
+    headers: &HeaderMap,
+    peer_ip: Option<IpAddr>,
+    state: &AppState,
+) -> Option<IpAddr> {
+    if state.config.trust_proxy_headers {
+        let forwarded_ip = headers
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .and_then(|value| value.trim().parse().ok());
+        if forwarded_ip.is_some() {
+            return forwarded_ip;
+        }
+    }
+    peer_ip
+}
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Deserializer};
+
+/// Distinguishes an explicit `null` (`Some(None)`) from a missing field
+/// (`None`) when used with `#[serde(default)]`.
+pub fn deserialize_some<'de, T, D>(
+    deserializer: D,
+) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Deserialize::deserialize(deserializer).map(Some)
+}
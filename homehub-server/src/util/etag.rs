@@ -0,0 +1,58 @@
+use std::convert::Infallible;
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderValue},
+    response::{IntoResponseParts, ResponseParts},
+};
+
+/// The `ETag` of a versioned resource, which is its version.
+pub struct ETag(pub i32);
+
+impl IntoResponseParts for ETag {
+    type Error = Infallible;
+
+    fn into_response_parts(
+        self,
+        mut res: ResponseParts,
+    ) -> Result<ResponseParts, Self::Error> {
+        if let Ok(etag) = HeaderValue::from_str(&format!("\"{}\"", self.0)) {
+            res.headers_mut().insert(header::ETAG, etag);
+        }
+        Ok(res)
+    }
+}
+
+/// The versions listed in an `If-Match` header, or `None` when there is no
+/// such header or it is `*`. Tags that are weak or not ours never match.
+pub struct IfMatch(pub Option<Vec<i32>>);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for IfMatch {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let Some(value) = parts.headers.get(header::IF_MATCH) else {
+            return Ok(IfMatch(None));
+        };
+        let value = value.to_str().unwrap_or_default().trim();
+        if value == "*" {
+            return Ok(IfMatch(None));
+        }
+        let versions = value
+            .split(',')
+            .filter_map(|tag| {
+                tag.trim()
+                    .strip_prefix('"')?
+                    .strip_suffix('"')?
+                    .parse()
+                    .ok()
+            })
+            .collect();
+        Ok(IfMatch(Some(versions)))
+    }
+}
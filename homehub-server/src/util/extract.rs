@@ -0,0 +1,235 @@
+use axum::{
+    async_trait,
+    extract::{
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+        FromRequest, FromRequestParts, Path, Query, Request,
+    },
+    http::{request::Parts, StatusCode},
+    Json,
+};
+use homehub_core::error::Error;
+use serde::de::DeserializeOwned;
+
+use crate::error::ApiError;
+use crate::util::locale::request_locale;
+
+/// Like [`Json`], but rejects malformed bodies with an [`ApiError`] so they
+/// are answered in the same envelope as every other error.
+pub struct ApiJson<T>(pub T);
+
+/// Like [`Path`], but rejects invalid parameters with an [`ApiError`].
+pub struct ApiPath<T>(pub T);
+
+/// Like [`Query`], but rejects invalid query strings with an [`ApiError`].
+pub struct ApiQuery<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ApiJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(
+        request: Request,
+        state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        // The locale has to be picked before the body is consumed
+        let (parts, body) = request.into_parts();
+        let locale = request_locale(&parts);
+        Json::from_request(Request::from_parts(parts, body), state)
+            .await
+            .map(|Json(value)| ApiJson(value))
+            .map_err(|rejection| ApiError::new(json_error(rejection), &locale))
+    }
+}
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ApiPath<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        Path::from_request_parts(parts, state)
+            .await
+            .map(|Path(value)| ApiPath(value))
+            .map_err(|rejection| {
+                ApiError::new(path_error(rejection), &request_locale(parts))
+            })
+    }
+}
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ApiQuery<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        Query::from_request_parts(parts, state)
+            .await
+            .map(|Query(value)| ApiQuery(value))
+            .map_err(|rejection| {
+                ApiError::new(query_error(rejection), &request_locale(parts))
+            })
+    }
+}
+
+fn json_error(rejection: JsonRejection) -> Error {
+    match rejection {
+        JsonRejection::JsonDataError(error) => {
+            Error::InvalidRequestBodyError(error.body_text())
+        }
+        JsonRejection::JsonSyntaxError(error) => {
+            Error::InvalidJsonError(error.body_text())
+        }
+        JsonRejection::MissingJsonContentType(_) => {
+            Error::UnsupportedMediaTypeError
+        }
+        rejection if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+            Error::RequestBodyTooLargeError
+        }
+        rejection => Error::InvalidJsonError(rejection.body_text()),
+    }
+}
+
+fn path_error(rejection: PathRejection) -> Error {
+    Error::InvalidPathError(rejection.body_text())
+}
+
+fn query_error(rejection: QueryRejection) -> Error {
+    Error::InvalidQueryError(rejection.body_text())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body},
+        http::{header, Method},
+        response::IntoResponse,
+        routing::post,
+        Router,
+    };
+    use serde::Deserialize;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Payload {
+        #[allow(dead_code)]
+        name: String,
+    }
+
+    #[derive(Deserialize)]
+    struct Filter {
+        #[allow(dead_code)]
+        limit: u64,
+    }
+
+    async fn handler(
+        ApiPath(_): ApiPath<uuid::Uuid>,
+        ApiQuery(_): ApiQuery<Filter>,
+        ApiJson(_): ApiJson<Payload>,
+    ) -> impl IntoResponse {
+        StatusCode::NO_CONTENT
+    }
+
+    async fn send(
+        uri: &str,
+        content_type: &str,
+        body: &'static str,
+    ) -> (StatusCode, Value) {
+        let app = Router::new().route("/things/:id", post(handler));
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::ACCEPT_LANGUAGE, "en-GB")
+            .body(Body::from(body))
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    const URI: &str = "/things/00000000-0000-0000-0000-000000000001?limit=1";
+
+    #[tokio::test]
+    async fn rejects_invalid_json_with_an_error_code() {
+        let (status, body) =
+            send(URI, "application/json", r#"{"name": "#).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["status"], "error");
+        assert_eq!(body["code"], "invalid_json");
+        assert_eq!(body["message"], "The request body is not valid JSON");
+        assert!(body["details"]["reason"].is_string());
+    }
+
+    #[tokio::test]
+    async fn rejects_bodies_of_the_wrong_shape() {
+        let (status, body) =
+            send(URI, "application/json", r#"{"name": 1}"#).await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "invalid_request_body");
+    }
+
+    #[tokio::test]
+    async fn rejects_bodies_that_are_not_json() {
+        let (status, body) = send(URI, "text/plain", "name").await;
+
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(body["code"], "unsupported_media_type");
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_path_parameters() {
+        let (status, body) = send(
+            "/things/not-a-uuid?limit=1",
+            "application/json",
+            r#"{"name": "lamp"}"#,
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "invalid_path");
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_query_strings() {
+        let (status, body) = send(
+            "/things/00000000-0000-0000-0000-000000000001?limit=many",
+            "application/json",
+            r#"{"name": "lamp"}"#,
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "invalid_query");
+    }
+
+    #[tokio::test]
+    async fn accepts_valid_requests() {
+        let (status, _) =
+            send(URI, "application/json", r#"{"name": "lamp"}"#).await;
+
+        assert_eq!(status, StatusCode::NO_CONTENT);
+    }
+}
@@ -0,0 +1,52 @@
+use std::convert::Infallible;
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap},
+};
+use homehub_core::i18n::Locale;
+
+use crate::middleware::jwt_auth::JWTAuthMiddleware;
+
+/// The locale to respond in: the authenticated user's chosen locale if they
+/// have one, otherwise the best match for the `Accept-Language` header.
+pub struct UserLocale(pub Locale);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for UserLocale {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(UserLocale(request_locale(parts)))
+    }
+}
+
+pub fn request_locale(parts: &Parts) -> Locale {
+    if let Some(jwt) = parts.extensions.get::<JWTAuthMiddleware>() {
+        return jwt.locale.clone();
+    }
+    accept_language_locale(&parts.headers, None)
+}
+
+/// Negotiates between the user's stored locale, if given, and the
+/// `Accept-Language` header.
+pub fn accept_language_locale(
+    headers: &HeaderMap,
+    user_locale: Option<&str>,
+) -> Locale {
+    let accepted_locales = headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .map(Locale::requested_in_accept_language)
+        .unwrap_or_default();
+
+    Locale::negotiate(
+        user_locale
+            .into_iter()
+            .chain(accepted_locales.iter().map(String::as_str)),
+    )
+}
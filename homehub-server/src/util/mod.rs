@@ -1,2 +1,5 @@
-
-
+pub mod client_ip;
+pub mod deserialize;
+pub mod etag;
+pub mod extract;
+pub mod locale;